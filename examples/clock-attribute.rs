@@ -28,7 +28,6 @@ extern crate relm;
 extern crate relm_attributes;
 #[macro_use]
 extern crate relm_derive;
-extern crate tokio_core;
 
 use std::time::Duration;
 
@@ -39,7 +38,6 @@ use gtk::{
 };
 use relm::{Relm, Widget};
 use relm_attributes::widget;
-use tokio_core::reactor::Interval;
 
 use self::Msg::*;
 
@@ -63,7 +61,7 @@ impl Widget for Win {
     }
 
     fn subscriptions(relm: &Relm<Msg>) {
-        let stream = Interval::new(Duration::from_secs(1), relm.handle()).unwrap();
+        let stream = relm.interval(Duration::from_secs(1));
         relm.connect_exec_ignore_err(stream, Tick);
     }
 
@@ -27,14 +27,12 @@ extern crate gtk;
 extern crate relm;
 #[macro_use]
 extern crate relm_derive;
-extern crate tokio_core;
 
 use std::time::Duration;
 
 use chrono::Local;
 use gtk::{ContainerExt, Inhibit, Label, WidgetExt, Window, WindowType};
 use relm::{Relm, RemoteRelm, Widget};
-use tokio_core::reactor::Interval;
 
 use self::Msg::*;
 
@@ -65,7 +63,7 @@ impl Widget for Win {
     }
 
     fn subscriptions(relm: &Relm<Msg>) {
-        let stream = Interval::new(Duration::from_secs(1), relm.handle()).unwrap();
+        let stream = relm.interval(Duration::from_secs(1));
         relm.connect_exec_ignore_err(stream, Tick);
     }
 
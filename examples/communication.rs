@@ -233,9 +233,14 @@ impl Widget for Win {
         let counter1 = hbox.add_widget::<Counter, _>(&relm, ());
         let counter2 = hbox.add_widget::<Counter, _>(&relm, ());
         let text = hbox.add_widget::<Text, _>(&relm, ());
-        connect!(text@Change(text), relm, TextChange(text));
-        connect!(text@Change(_), counter1, Increment);
-        connect!(counter1@Increment, counter2, Decrement);
+        let _ = text.forward_to(relm, |msg| match *msg {
+            Change(ref text) => Some(TextChange(text.clone())),
+        });
+        let _ = text.forward_to_component(&counter1, |_| Some(Increment));
+        let _ = counter1.forward_to_component(&counter2, |msg| match *msg {
+            Increment => Some(Decrement),
+            Decrement => None,
+        });
         connect!(button, connect_clicked(_), counter1, Decrement);
 
         let label = Label::new(None);
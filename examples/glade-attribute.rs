@@ -0,0 +1,92 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+#![feature(proc_macro)]
+
+extern crate gtk;
+#[macro_use]
+extern crate relm;
+extern crate relm_attributes;
+#[macro_use]
+extern crate relm_derive;
+
+use gtk::{
+    ButtonExt,
+    Inhibit,
+    WidgetExt,
+};
+use relm::Widget;
+use relm_attributes::widget;
+
+use self::Msg::*;
+
+// Define the structure of the model.
+#[derive(Clone)]
+pub struct Model {
+    counter: i32,
+}
+
+// The messages that can be sent to the update function.
+#[derive(Msg)]
+pub enum Msg {
+    Decrement,
+    Increment,
+    Quit,
+}
+
+#[widget]
+impl Widget for Win {
+    // The initial model.
+    fn model() -> Model {
+        Model {
+            counter: 0,
+        }
+    }
+
+    // Update the model according to the message received.
+    fn update(&mut self, event: Msg, model: &mut Model) {
+        match event {
+            Decrement => model.counter -= 1,
+            Increment => model.counter += 1,
+            Quit => gtk::main_quit(),
+        }
+    }
+
+    // Load the widgets from a glade file and bind the objects by their id.
+    view!(glade "examples/buttons.glade" {
+        window {
+            delete_event(_, _) => (Quit, Inhibit(false)),
+        },
+        plus_button {
+            clicked => Increment,
+        },
+        counter_label {
+            text: &model.counter.to_string(),
+        },
+        minus_button {
+            clicked => Decrement,
+        },
+    });
+}
+
+fn main() {
+    Win::run(()).unwrap();
+}
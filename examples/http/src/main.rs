@@ -54,7 +54,7 @@ use gtk::{
 use hyper::{Client, Error};
 use hyper_tls::HttpsConnector;
 use gtk::Orientation::Vertical;
-use relm::{Handle, Relm, RemoteRelm, Widget};
+use relm::{Commands, Handle, RemoteRelm, Widget};
 use simplelog::{Config, TermLogger};
 use simplelog::LogLevelFilter::Warn;
 
@@ -102,7 +102,7 @@ impl Widget for Win {
         &self.window
     }
 
-    fn update(&mut self, event: Msg, _model: &mut Model) {
+    fn update_with_commands(&mut self, event: Msg, model: &mut Model, commands: &mut Commands<Msg>) {
         match event {
             DownloadCompleted => {
                 self.button.set_sensitive(true);
@@ -116,6 +116,10 @@ impl Widget for Win {
                 // Disable the button because loading 2 images at the same time crashes the pixbuf
                 // loader.
                 self.button.set_sensitive(false);
+
+                let url = format!("https://api.giphy.com/v1/gifs/random?api_key=dc6zaTOxFJmzC&tag={}", model.topic);
+                let http_future = http_get(&url, commands.handle());
+                commands.connect(http_future, NewGif, hyper_error_to_msg);
             },
             HttpError(error) => {
                 self.button.set_sensitive(true);
@@ -125,27 +129,15 @@ impl Widget for Win {
             ImageChunk(chunk) => {
                 self.loader.loader_write(&chunk).unwrap();
             },
-            NewGif(_) => (),
-            Quit => gtk::main_quit(),
-        }
-    }
-
-    fn update_command(relm: &Relm<Msg>, event: Msg, model: &mut Model) {
-        match event {
-            FetchUrl => {
-                let url = format!("https://api.giphy.com/v1/gifs/random?api_key=dc6zaTOxFJmzC&tag={}", model.topic);
-                let http_future = http_get(&url, relm.handle());
-                relm.connect_exec(http_future, NewGif, hyper_error_to_msg);
-            },
             NewGif(result) => {
                 let string = String::from_utf8(result).unwrap();
                 let json = json::parse(&string).unwrap();
                 let url = &json["data"]["image_url"].as_str().unwrap();
-                let http_future = http_get_stream(url, relm.handle());
-                let future = relm.connect(http_future, ImageChunk, hyper_error_to_msg);
-                relm.connect_exec_ignore_err(future, DownloadCompleted);
+                let http_future = http_get_stream(url, commands.handle());
+                let future = commands.relm().connect(http_future, ImageChunk, hyper_error_to_msg);
+                commands.connect_ignore_err(future, DownloadCompleted);
             },
-            _ => (),
+            Quit => gtk::main_quit(),
         }
     }
 
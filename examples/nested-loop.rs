@@ -26,7 +26,6 @@ extern crate relm;
 #[macro_use]
 extern crate relm_derive;
 extern crate simplelog;
-extern crate tokio_core;
 
 use std::time::Duration;
 
@@ -48,7 +47,6 @@ use gtk::Orientation::Vertical;
 use relm::{Relm, RemoteRelm, Widget};
 use simplelog::{Config, TermLogger};
 use simplelog::LogLevelFilter::Warn;
-use tokio_core::reactor::Interval;
 
 use self::Msg::*;
 
@@ -81,7 +79,7 @@ impl Widget for Win {
     }
 
     fn subscriptions(relm: &Relm<Msg>) {
-        let stream = Interval::new(Duration::from_secs(1), relm.handle()).unwrap();
+        let stream = relm.interval(Duration::from_secs(1));
         relm.connect_exec_ignore_err(stream, Tick);
     }
 
@@ -0,0 +1,109 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+#![feature(proc_macro)]
+
+extern crate gtk;
+#[macro_use]
+extern crate relm;
+extern crate relm_attributes;
+#[macro_use]
+extern crate relm_derive;
+
+use std::time::Duration;
+
+use gtk::{
+    EditableSignals,
+    EntryExt,
+    Inhibit,
+    OrientableExt,
+    WidgetExt,
+};
+use gtk::Orientation::Vertical;
+use relm::{Policy, Relm, Widget};
+use relm_attributes::widget;
+
+use self::Msg::*;
+
+#[derive(Clone)]
+pub struct Model {
+    search: String,
+    ticks: u32,
+}
+
+#[derive(Msg)]
+pub enum Msg {
+    Quit,
+    Search(String),
+    Tick,
+}
+
+#[widget]
+impl Widget for Win {
+    fn model() -> Model {
+        Model {
+            search: String::new(),
+            ticks: 0,
+        }
+    }
+
+    fn subscriptions(relm: &Relm<Msg>) {
+        // The messages emitted during a frame are dispatched together.
+        relm.batch_per_frame();
+        // The search is only done when the user stops typing for 300ms.
+        relm.set_policy("Search", Policy::Debounce(Duration::from_millis(300)));
+        // At most 2 ticks per second get to update().
+        relm.set_policy("Tick", Policy::Throttle(Duration::from_millis(500)));
+
+        let stream = relm.interval(Duration::from_millis(5));
+        relm.connect_exec_ignore_err(stream, |()| Tick);
+    }
+
+    fn update(&mut self, event: Msg, model: &mut Model) {
+        match event {
+            Quit => gtk::main_quit(),
+            Search(text) => model.search = text,
+            Tick => model.ticks += 1,
+        }
+    }
+
+    view! {
+        gtk::Window {
+            gtk::Box {
+                orientation: Vertical,
+                gtk::Entry {
+                    changed(entry) => Search(entry.get_text().unwrap_or_else(String::new)),
+                },
+                gtk::Label {
+                    text: &format!("Searching for: {}", model.search),
+                },
+                gtk::Label {
+                    text: &format!("Ticks: {}", model.ticks),
+                },
+            },
+            delete_event(_, _) => (Quit, Inhibit(false)),
+        }
+    }
+}
+
+fn main() {
+    Win::run(()).unwrap();
+}
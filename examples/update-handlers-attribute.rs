@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+#![feature(proc_macro)]
+
+extern crate gtk;
+#[macro_use]
+extern crate relm;
+extern crate relm_attributes;
+#[macro_use]
+extern crate relm_derive;
+
+use gtk::{
+    ButtonExt,
+    Inhibit,
+    OrientableExt,
+    WidgetExt,
+};
+use gtk::Orientation::Vertical;
+use relm::Widget;
+use relm_attributes::widget;
+
+use self::Msg::*;
+
+// Define the structure of the model.
+#[derive(Clone)]
+pub struct Model {
+    counter: i32,
+}
+
+// The messages that can be sent to the update function.
+#[derive(Msg)]
+pub enum Msg {
+    Add(i32),
+    Decrement,
+    Increment,
+    Quit,
+}
+
+#[widget]
+impl Widget for Win {
+    type Msg = Msg;
+
+    // The initial model.
+    fn model() -> Model {
+        Model {
+            counter: 0,
+        }
+    }
+
+    // Instead of an update() method, every message is handled by the method specifying its pattern.
+    #[update(Decrement)]
+    fn decrement(&mut self, model: &mut Model) {
+        self.add(-1, model);
+    }
+
+    #[update(Increment)]
+    fn increment(&mut self, model: &mut Model) {
+        self.add(1, model);
+    }
+
+    // The variables bound by the pattern are given to the parameters of the same name.
+    #[update(Add(value))]
+    fn add(&mut self, value: i32, model: &mut Model) {
+        model.counter += value;
+    }
+
+    #[update(Quit)]
+    fn quit(&mut self) {
+        gtk::main_quit();
+    }
+
+    view! {
+        gtk::Window {
+            gtk::Box {
+                // Set the orientation property of the Box.
+                orientation: Vertical,
+                // Create a Button inside the Box.
+                gtk::Button {
+                    // Send the message Increment when the button is clicked.
+                    clicked => Increment,
+                    label: "+",
+                },
+                gtk::Label {
+                    // Bind the text property of the label to the counter attribute of the model.
+                    text: &model.counter.to_string(),
+                },
+                gtk::Button {
+                    clicked => Decrement,
+                    label: "-",
+                },
+                gtk::Button {
+                    clicked => Add(10),
+                    label: "+10",
+                },
+            },
+            delete_event(_, _) => (Quit, Inhibit(false)),
+        }
+    }
+}
+
+fn main() {
+    Win::run(()).unwrap();
+}
@@ -53,7 +53,7 @@ use gtk::{
 };
 use gtk::Orientation::Vertical;
 use rand::Rng;
-use relm::{Handle, Relm, Widget};
+use relm::{Commands, Handle, Relm, Widget};
 use relm_attributes::widget;
 use tokio_core::net::TcpStream;
 use tokio_proto::TcpClient;
@@ -104,12 +104,17 @@ impl Widget for Win {
         relm.exec(future);
     }
 
-    fn update(&mut self, event: Msg, model: &mut Model) {
+    fn update(&mut self, event: Msg, model: &mut Model, commands: &mut Commands<Msg>) {
         match event {
             Change(message) => model.message = message,
             Connected(service) => model.service = Some(service),
             Message(message) => model.text += &format!("{}\n", message),
             Send => {
+                if let Some(ref service) = model.service {
+                    // Send the message to the server.
+                    let send_future = ws_send(service, &model.message);
+                    commands.connect_ignore_err(send_future, Message);
+                }
                 model.message = String::new();
                 self.entry.grab_focus();
             },
@@ -117,16 +122,6 @@ impl Widget for Win {
         }
     }
 
-    fn update_command(relm: &Relm<Msg>, event: Msg, model: &mut Model) {
-        if let Send = event {
-            if let Some(ref service) = model.service {
-                // Send the message to the server.
-                let send_future = ws_send(service, &model.message);
-                relm.connect_exec_ignore_err(send_future, Message);
-            }
-        }
-    }
-
     view! {
         gtk::Window {
             gtk::Box {
@@ -61,7 +61,7 @@ use gtk::{
 };
 use gtk::Orientation::Vertical;
 use rand::Rng;
-use relm::{Handle, Relm, RemoteRelm, Widget};
+use relm::{Commands, Handle, Relm, RemoteRelm, Widget};
 use tokio_core::net::TcpStream;
 use tokio_proto::TcpClient;
 use tokio_proto::pipeline::ClientService;
@@ -116,7 +116,7 @@ impl Widget for Win {
         relm.exec(future);
     }
 
-    fn update(&mut self, event: Msg, model: &mut Model) {
+    fn update_with_commands(&mut self, event: Msg, model: &mut Model, commands: &mut Commands<Msg>) {
         match event {
             Connected(service) => {
                 model.service = Some(service);
@@ -125,7 +125,11 @@ impl Widget for Win {
                 model.text.push_str(&format!("{}\n", message));
                 self.label.set_text(&model.text);
             },
-            Send(_) => {
+            Send(message) => {
+                if let Some(ref service) = model.service {
+                    let send_future = ws_send(service, &message);
+                    commands.connect_ignore_err(send_future, Message);
+                }
                 self.entry.set_text("");
                 self.entry.grab_focus();
             },
@@ -133,15 +137,6 @@ impl Widget for Win {
         }
     }
 
-    fn update_command(relm: &Relm<Msg>, event: Msg, model: &mut Model) {
-        if let Send(message) = event {
-            if let Some(ref service) = model.service {
-                let send_future = ws_send(service, &message);
-                relm.connect_exec_ignore_err(send_future, Message);
-            }
-        }
-    }
-
     fn view(relm: &RemoteRelm<Self>, _model: &Self::Model) -> Self {
         let vbox = gtk::Box::new(Vertical, 0);
 
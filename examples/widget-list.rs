@@ -36,13 +36,13 @@ use gtk::{
     WindowType,
 };
 use gtk::Orientation::{Horizontal, Vertical};
-use relm::{Component, ContainerWidget, RemoteRelm, Widget};
+use relm::{ComponentList, RemoteRelm, Widget};
 
 use self::CounterMsg::*;
 use self::Msg::*;
 
 #[derive(Clone)]
-struct Model {
+struct CounterModel {
     counter: i32,
 }
 
@@ -60,12 +60,12 @@ struct Counter {
 
 impl Widget for Counter {
     type Root = gtk::Box;
-    type Model = Model;
+    type Model = CounterModel;
     type ModelParam = ();
     type Msg = CounterMsg;
 
-    fn model(_: ()) -> Model {
-        Model {
+    fn model(_: ()) -> CounterModel {
+        CounterModel {
             counter: 0,
         }
     }
@@ -74,7 +74,7 @@ impl Widget for Counter {
         &self.vbox
     }
 
-    fn update(&mut self, event: CounterMsg, model: &mut Model) {
+    fn update(&mut self, event: CounterMsg, model: &mut CounterModel) {
         let label = &self.counter_label;
 
         match event {
@@ -89,7 +89,7 @@ impl Widget for Counter {
         }
     }
 
-    fn view(relm: &RemoteRelm<Counter>, _model: &Model) -> Self {
+    fn view(relm: &RemoteRelm<Counter>, _model: &CounterModel) -> Self {
         let vbox = gtk::Box::new(Vertical, 0);
 
         let plus_button = Button::new_with_label("+");
@@ -113,62 +113,74 @@ impl Widget for Counter {
     }
 }
 
+#[derive(Clone)]
+struct Model {
+    counters: Vec<usize>,
+    next_id: usize,
+}
+
 #[derive(Msg)]
 enum Msg {
     Add,
+    CounterIncremented(usize),
     Quit,
     Remove,
+    Reverse,
 }
 
 #[derive(Clone)]
 struct Win {
-    counters: Vec<Component<Counter>>,
-    hbox: gtk::Box,
-    relm: RemoteRelm<Win>,
+    counters: ComponentList<usize, Counter>,
     window: Window,
 }
 
 impl Widget for Win {
-    type Model = ();
+    type Model = Model;
     type ModelParam = ();
     type Msg = Msg;
     type Root = Window;
 
-    fn model(_: ()) -> () {
-        ()
+    fn model(_: ()) -> Model {
+        Model {
+            counters: vec![],
+            next_id: 0,
+        }
     }
 
     fn root(&self) -> &Self::Root {
         &self.window
     }
 
-    fn update(&mut self, event: Msg, _model: &mut ()) {
+    fn update(&mut self, event: Msg, model: &mut Model) {
         match event {
             Add => {
-                let widget = self.hbox.add_widget::<Counter, _>(&self.relm, ());
-                self.counters.push(widget);
+                model.counters.push(model.next_id);
+                model.next_id += 1;
             },
+            CounterIncremented(id) => println!("Counter {} incremented", id),
             Quit => gtk::main_quit(),
             Remove => {
-                if let Some(counter) = self.counters.pop() {
-                    self.hbox.remove_widget(counter);
-                }
+                let _ = model.counters.pop();
             },
+            Reverse => model.counters.reverse(),
         }
+        self.counters.update(model.counters.iter().cloned(), |_| ());
     }
 
-    fn view(relm: &RemoteRelm<Self>, _model: &()) -> Self {
+    fn view(relm: &RemoteRelm<Self>, _model: &Model) -> Self {
         let window = Window::new(WindowType::Toplevel);
 
         let vbox = gtk::Box::new(Vertical, 0);
         let add_button = Button::new_with_label("Add");
         let remove_button = Button::new_with_label("Remove");
+        let reverse_button = Button::new_with_label("Reverse");
 
         let hbox = gtk::Box::new(Horizontal, 0);
         vbox.add(&hbox);
 
         vbox.add(&add_button);
         vbox.add(&remove_button);
+        vbox.add(&reverse_button);
 
         window.add(&vbox);
 
@@ -176,12 +188,16 @@ impl Widget for Win {
 
         connect!(relm, add_button, connect_clicked(_), Add);
         connect!(relm, remove_button, connect_clicked(_), Remove);
+        connect!(relm, reverse_button, connect_clicked(_), Reverse);
         connect!(relm, window, connect_delete_event(_, _) (Some(Quit), Inhibit(false)));
 
+        let counters = ComponentList::new(&hbox, relm, |&id, msg| match *msg {
+            Increment => Some(CounterIncremented(id)),
+            Decrement => None,
+        });
+
         Win {
-            counters: vec![],
-            hbox: hbox,
-            relm: relm.clone(),
+            counters: counters,
             window: window,
         }
     }
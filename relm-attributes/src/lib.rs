@@ -28,16 +28,16 @@ extern crate relm_gen_widget;
 extern crate syn;
 
 use proc_macro::TokenStream;
-use relm_gen_widget::gen_widget;
+use relm_gen_widget::gen_widget_with_attributes;
 use syn::parse_item;
 
 #[proc_macro_attribute]
-pub fn widget(_attributes: TokenStream, input: TokenStream) -> TokenStream {
+pub fn widget(attributes: TokenStream, input: TokenStream) -> TokenStream {
     let source = input.to_string();
     let ast = parse_item(&source).unwrap();
     let tokens = quote! {
         #ast
     };
-    let expanded = gen_widget(tokens);
+    let expanded = gen_widget_with_attributes(&attributes.to_string(), tokens);
     expanded.parse().unwrap()
 }
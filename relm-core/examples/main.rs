@@ -21,23 +21,17 @@
 
 extern crate chrono;
 extern crate futures;
-extern crate glib;
-extern crate glib_itc;
 extern crate gtk;
 extern crate relm_core;
-extern crate tokio_core;
 
-use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
 use std::time::Duration;
 
 use chrono::Local;
 use futures::Stream;
-use glib::Continue;
-use glib_itc::channel;
 use gtk::{Button, ButtonExt, ContainerExt, Inhibit, Label, WidgetExt, Window, WindowType};
 use gtk::Orientation::Vertical;
 use relm_core::{Core, EventStream};
-use tokio_core::reactor::Interval;
 
 use self::Msg::*;
 
@@ -80,14 +74,11 @@ fn main() {
     let window = Window::new(WindowType::Toplevel);
     window.add(&vbox);
 
-    let (sender, mut receiver) = channel();
-    let sender = Arc::new(Mutex::new(sender));
+    let stream = EventStream::new();
 
-    let stream = EventStream::new(sender.clone());
-
-    let other_widget_stream = EventStream::new(sender);
+    let other_widget_stream = EventStream::new();
     {
-        stream.observe(move |event: Msg| {
+        stream.observe(move |event: &Msg| {
             other_widget_stream.emit(Quit);
             println!("Event: {:?}", event);
         });
@@ -119,9 +110,9 @@ fn main() {
         });
     }
 
-    let mut model = Model {
+    let model = RefCell::new(Model {
         counter: 0,
-    };
+    });
 
     fn update(event: Msg, model: &mut Model, widgets: &Widgets) {
         match event {
@@ -141,34 +132,20 @@ fn main() {
         }
     }
 
-    {
-        let stream = stream.clone();
-        receiver.connect_recv(move || {
-            if let Some(event) = stream.pop_ui_events() {
-                update(event, &mut model, &widgets);
-            }
-            Continue(true)
-        });
-    }
-
-    let stream = stream.clone();
-    let remote = Core::run();
-    remote.spawn(move |handle| {
-        let interval = {
-            let interval = Interval::new(Duration::from_secs(1), handle).unwrap();
-            let stream = stream.clone();
-            interval.map_err(|_| ()).for_each(move |_| {
-                stream.emit(Clock);
-                Ok(())
-            })
-        };
-        handle.spawn(interval);
-
-        let event_future = stream.for_each(|_| Ok(()));
+    stream.set_callback(move |event| {
+        update(event, &mut *model.borrow_mut(), &widgets);
+    });
 
-        handle.spawn(event_future);
+    let core = Core::new().unwrap();
+    let interval = {
+        let interval = core.interval(Duration::from_secs(1));
+        let stream = stream.clone();
+        interval.map_err(|_| ()).for_each(move |_| {
+            stream.emit(Clock);
+            Ok(())
+        })
+    };
+    core.spawn(interval);
 
-        Ok(())
-    });
     gtk::main();
 }
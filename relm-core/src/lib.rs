@@ -20,134 +20,1185 @@
  */
 
 extern crate futures;
+extern crate glib;
 extern crate glib_itc;
 extern crate gtk;
 extern crate tokio_core;
 
-use std::collections::VecDeque;
-use std::io::Error;
+mod timer;
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::mem;
+use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::channel;
-use std::thread;
+use std::time::Duration;
 
-use futures::{Async, Poll, Stream};
-use futures::task::{self, Task};
-use glib_itc::Sender;
+use futures::{Async, Future};
+use futures::executor::{self, Notify, Spawn};
+use futures::task::{self, Task as ParkedTask};
+use glib::{Continue, SourceId};
+use glib_itc::{Receiver, Sender, channel};
 use tokio_core::reactor;
-pub use tokio_core::reactor::{Handle, Remote};
+pub use tokio_core::reactor::Handle;
+
+use timer::VirtualClock;
+pub use timer::{Interval, Timeout};
 
-pub struct Core { }
+type Task = Spawn<Box<Future<Item=(), Error=()>>>;
+
+struct Notifier {
+    ready: Mutex<Vec<usize>>,
+    sender: Mutex<Sender>,
+}
+
+impl Notify for Notifier {
+    fn notify(&self, id: usize) {
+        self.ready.lock().unwrap().push(id);
+        // Wake up the GLib main loop, since the notification might come from another thread.
+        self.sender.lock().unwrap().send();
+    }
+}
+
+struct Tasks {
+    next_id: usize,
+    // The task currently being polled, which is not in `tasks`, and whether it was cancelled while being
+    // polled.
+    polling: Option<(usize, bool)>,
+    tasks: HashMap<usize, Task>,
+}
+
+/// Identifier of a future spawned on the `Core`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TaskId(usize);
+
+/// Single-threaded executor polling the futures from the GLib main loop.
+///
+/// The futures are spawned on the thread where the `Core` is created (the GTK+ thread), so that
+/// they do not need to be `Send`.
+/// The futures spawned with the `Handle`, like the ones of the I/O objects, run in the tokio reactor.
+/// Since the reactor wakes up its own thread instead of the GLib main loop when they can make
+/// progress, it is turned every 10 ms by a GLib timeout, added the first time `handle()` is called.
+/// The timers should be created with `timeout()` and `interval()`, which use GLib timeouts, since
+/// the tokio timers created with the `Handle` only fire when a future of the `Core` is polled.
+///
+/// A `Core` created with `Core::new_virtual()` does not turn the reactor: its futures only make
+/// progress in `run_until_stalled()` and its timers, created with `timeout()` and `interval()`,
+/// use a virtual clock advanced by `advance()`.
+#[derive(Clone)]
+pub struct Core {
+    clock: Option<Rc<VirtualClock>>,
+    handle: Handle,
+    notifier: Arc<Notifier>,
+    reactor: Rc<RefCell<reactor::Core>>,
+    reactor_timer: Rc<Cell<bool>>,
+    _receiver: Rc<Receiver>,
+    tasks: Rc<RefCell<Tasks>>,
+}
 
 impl Core {
-    pub fn run() -> Remote {
-        let (sender, receiver) = channel();
-        thread::spawn(move || {
-            let mut core = reactor::Core::new().unwrap();
-            sender.send(core.remote()).unwrap();
-            loop {
-                core.turn(None);
-            }
+    /// Create a new executor attached to the GLib main loop of the current thread.
+    pub fn new() -> io::Result<Self> {
+        Core::with_clock(None)
+    }
+
+    /// Create a new executor for the tests, with a virtual clock.
+    pub fn new_virtual() -> io::Result<Self> {
+        Core::with_clock(Some(Rc::new(VirtualClock::new())))
+    }
+
+    fn with_clock(clock: Option<Rc<VirtualClock>>) -> io::Result<Self> {
+        let reactor = Rc::new(RefCell::new(reactor::Core::new()?));
+        let handle = reactor.borrow().handle();
+
+        let (sender, mut receiver) = channel();
+        let notifier = Arc::new(Notifier {
+            ready: Mutex::new(vec![]),
+            sender: Mutex::new(sender),
         });
+        let tasks = Rc::new(RefCell::new(Tasks {
+            next_id: 0,
+            polling: None,
+            tasks: HashMap::new(),
+        }));
+        {
+            let notifier = notifier.clone();
+            let tasks = Rc::downgrade(&tasks);
+            // The virtual core never turns the reactor, so that nothing happens behind the test's back.
+            let reactor = if clock.is_none() { Some(Rc::downgrade(&reactor)) } else { None };
+            receiver.connect_recv(move || {
+                poll_ready(&notifier, &tasks);
+                // Let the reactor run the futures spawned with the `Handle` that the futures just
+                // polled might have woken up.
+                if let Some(reactor) = reactor.as_ref().and_then(|reactor| reactor.upgrade()) {
+                    if let Ok(mut reactor) = reactor.try_borrow_mut() {
+                        reactor.turn(Some(Duration::from_millis(0)));
+                    }
+                }
+                Continue(true)
+            });
+        }
+
+        Ok(Core {
+            clock: clock,
+            handle: handle,
+            notifier: notifier,
+            reactor: reactor,
+            reactor_timer: Rc::new(Cell::new(false)),
+            _receiver: Rc::new(receiver),
+            tasks: tasks,
+        })
+    }
+
+    /// Advance the virtual clock by `duration`, running the futures until they are stalled every
+    /// time a timer expires, so that the timers fire in order.
+    ///
+    /// Panics if the `Core` was not created with `Core::new_virtual()`.
+    pub fn advance(&self, duration: Duration) {
+        let clock = self.clock.as_ref().expect("Core::advance() requires a Core created with Core::new_virtual()");
+        let target = clock.now() + duration;
+        loop {
+            self.run_until_stalled();
+            match clock.next_deadline() {
+                Some(deadline) if deadline <= target => clock.set_now(deadline),
+                _ => break,
+            }
+        }
+        clock.set_now(target);
+        self.run_until_stalled();
+    }
+
+    /// Cancel the future identified by `id`, by dropping it.
+    ///
+    /// Returns `false` if the future already completed.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        let task = {
+            let mut tasks = self.tasks.borrow_mut();
+            if let Some((polling_id, ref mut cancelled)) = tasks.polling {
+                if polling_id == id.0 {
+                    *cancelled = true;
+                    return true;
+                }
+            }
+            tasks.tasks.remove(&id.0)
+        };
+        // The future is dropped outside of the borrow since its destructor might use the Core.
+        task.is_some()
+    }
+
+    /// Get a handle to the tokio reactor, to create I/O objects.
+    ///
+    /// From the first call, the reactor is turned every 10 ms, so that the futures spawned with the
+    /// `Handle` make progress.
+    /// Use `timeout()` and `interval()` to create timers.
+    pub fn handle(&self) -> &Handle {
+        if self.clock.is_none() && !self.reactor_timer.get() {
+            self.reactor_timer.set(true);
+            let reactor = Rc::downgrade(&self.reactor);
+            let _ = gtk::timeout_add(10, move || {
+                match reactor.upgrade() {
+                    Some(reactor) => {
+                        // The reactor is already borrowed when a future spawned with the `Handle`
+                        // runs the main loop.
+                        if let Ok(mut reactor) = reactor.try_borrow_mut() {
+                            reactor.turn(Some(Duration::from_millis(0)));
+                        }
+                        Continue(true)
+                    },
+                    None => Continue(false),
+                }
+            });
+        }
+        &self.handle
+    }
+
+    /// Create a stream yielding `()` every `period`.
+    pub fn interval(&self, period: Duration) -> Interval {
+        Interval::new(period, self.clock.as_ref())
+    }
+
+    /// Check whether the future identified by `id` is still running.
+    pub fn is_running(&self, id: TaskId) -> bool {
+        let tasks = self.tasks.borrow();
+        tasks.tasks.contains_key(&id.0) || tasks.polling.map(|(polling_id, _)| polling_id) == Some(id.0)
+    }
+
+    /// Get the time elapsed on the virtual clock, or `None` if the `Core` does not have one.
+    pub fn now(&self) -> Option<Duration> {
+        self.clock.as_ref().map(|clock| clock.now())
+    }
+
+    /// Poll the futures ready to make progress and run the pending iterations of the GLib main loop,
+    /// until there is nothing left to do.
+    pub fn run_until_stalled(&self) {
+        let tasks = Rc::downgrade(&self.tasks);
+        loop {
+            poll_ready(&self.notifier, &tasks);
+            let mut idle = true;
+            while gtk::events_pending() {
+                let _ = gtk::main_iteration_do(false);
+                idle = false;
+            }
+            if idle && self.notifier.ready.lock().unwrap().is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Drop all the futures.
+    ///
+    /// The futures spawned afterwards are still run.
+    pub fn shutdown(&self) {
+        let tasks = {
+            let mut tasks = self.tasks.borrow_mut();
+            if let Some((_, ref mut cancelled)) = tasks.polling {
+                *cancelled = true;
+            }
+            mem::replace(&mut tasks.tasks, HashMap::new())
+        };
+        // The futures are dropped outside of the borrow since their destructor might use the Core.
+        drop(tasks);
+        self.notifier.ready.lock().unwrap().clear();
+    }
+
+    /// Spawn a future on the GLib main loop.
+    ///
+    /// The future is first polled on the next iteration of the main loop.
+    pub fn spawn<F: Future<Item=(), Error=()> + 'static>(&self, future: F) -> TaskId {
+        let id = {
+            let mut tasks = self.tasks.borrow_mut();
+            let id = tasks.next_id;
+            tasks.next_id += 1;
+            let future: Box<Future<Item=(), Error=()>> = Box::new(future);
+            let _ = tasks.tasks.insert(id, executor::spawn(future));
+            id
+        };
+        self.notifier.notify(id);
+        TaskId(id)
+    }
+
+    /// Create a future completing after `duration`.
+    pub fn timeout(&self, duration: Duration) -> Timeout {
+        Timeout::new(duration, self.clock.as_ref())
+    }
+}
 
-        receiver.recv().unwrap()
+fn poll_ready(notifier: &Arc<Notifier>, tasks: &Weak<RefCell<Tasks>>) {
+    let tasks =
+        match tasks.upgrade() {
+            Some(tasks) => tasks,
+            None => return,
+        };
+    let ready = mem::replace(&mut *notifier.ready.lock().unwrap(), vec![]);
+    for id in ready {
+        // Remove the task while polling it, so that it can spawn or cancel other tasks.
+        let task = {
+            let mut tasks = tasks.borrow_mut();
+            let task = tasks.tasks.remove(&id);
+            if task.is_some() {
+                tasks.polling = Some((id, false));
+            }
+            task
+        };
+        if let Some(mut task) = task {
+            let result = task.poll_future_notify(notifier, id);
+            let cancelled = tasks.borrow_mut().polling.take().map(|(_, cancelled)| cancelled).unwrap_or(false);
+            if let Ok(Async::NotReady) = result {
+                if !cancelled {
+                    let _ = tasks.borrow_mut().tasks.insert(id, task);
+                }
+            }
+        }
     }
 }
 
+/// How the messages of a variant are dispatched, set with `EventStream::set_policy()`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Policy {
+    /// A new message replaces the pending message of the same variant, at its position in the queue.
+    Coalesce,
+    /// A message is only dispatched once no other message of the same variant was emitted for
+    /// this duration.
+    Debounce(Duration),
+    /// At most one message of the variant is dispatched per interval of this duration: the last
+    /// message emitted during the interval is dispatched at its end.
+    Throttle(Duration),
+}
+
+/// What to do with a message emitted when the queue of an `EventStream` is full, set with
+/// `EventStream::set_capacity()`.
+pub enum Overflow<MSG> {
+    /// The message is queued anyway, but the producers checking `EventStream::poll_ready()`, like
+    /// the futures connected by relm, are parked until a message is dispatched.
+    Block,
+    /// The new message is dropped.
+    DropNewest,
+    /// The oldest pending message is dropped to make room for the new message.
+    /// With a capacity of 0, the new message is dropped instead.
+    DropOldest,
+    /// The new message is dropped and the message returned by this function is queued instead.
+    /// It is only queued once until the queue has room again.
+    Message(Rc<Fn(MSG) -> MSG>),
+}
+
+impl<MSG> Clone for Overflow<MSG> {
+    fn clone(&self) -> Self {
+        match *self {
+            Overflow::Block => Overflow::Block,
+            Overflow::DropNewest => Overflow::DropNewest,
+            Overflow::DropOldest => Overflow::DropOldest,
+            Overflow::Message(ref message) => Overflow::Message(message.clone()),
+        }
+    }
+}
+
+/// Statistics about the queue of an `EventStream`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct QueueMetrics {
+    /// Number of messages dropped because the queue was full.
+    pub dropped: usize,
+    /// Number of pending messages.
+    pub len: usize,
+    /// Highest number of pending messages.
+    pub peak_len: usize,
+}
+
+// A message waiting for the timer of a debounced or throttled variant.
+struct Delayed<MSG> {
+    message: Option<MSG>,
+    source: SourceId,
+}
+
 struct _EventStream<MSG> {
+    // The functions to call on the next dispatch, added with EventStream::exec().
+    actions: VecDeque<Box<Fn()>>,
+    batched: bool,
+    // Whether the scheduler of the batched stream was called since the last flush.
+    batch_requested: bool,
+    batch_scheduler: Option<Rc<Fn()>>,
+    callback: Option<Rc<Fn(MSG)>>,
+    capacity: Option<(usize, Overflow<MSG>)>,
+    close_callbacks: Vec<Box<Fn()>>,
+    delayed: HashMap<&'static str, Delayed<MSG>>,
+    dropped: usize,
     events: VecDeque<MSG>,
-    observers: Vec<Box<Fn(MSG) + Send>>,
-    sender: Arc<Mutex<Sender>>,
-    task: Option<Task>,
+    next_observer_id: usize,
+    observers: Vec<(ObserverId, Rc<Fn(&MSG)>)>,
+    // Whether the message of Overflow::Message was queued since the queue was last full.
+    overflowed: bool,
+    // The producers waiting for room in the queue.
+    parked: Vec<ParkedTask>,
+    peak_len: usize,
+    policies: HashMap<&'static str, Policy>,
+    source: Option<SourceId>,
     terminated: bool,
-    ui_events: VecDeque<MSG>,
+    variant: Option<Rc<Fn(&MSG) -> &'static str>>,
 }
 
-#[derive(Clone)]
+impl<MSG> _EventStream<MSG> {
+    /*
+     * Add a message at the end of the queue, according to its capacity.
+     */
+    fn enqueue(&mut self, event: MSG) {
+        let overflow =
+            match self.capacity {
+                Some((capacity, ref overflow)) if self.events.len() >= capacity => Some(overflow.clone()),
+                _ => None,
+            };
+        match overflow {
+            None => {
+                self.overflowed = false;
+                self.events.push_back(event);
+            },
+            Some(Overflow::Block) => self.events.push_back(event),
+            Some(Overflow::DropNewest) => self.dropped += 1,
+            Some(Overflow::DropOldest) => {
+                let _ = self.events.pop_front();
+                self.dropped += 1;
+                self.events.push_back(event);
+            },
+            Some(Overflow::Message(message)) => {
+                self.dropped += 1;
+                if !self.overflowed {
+                    self.overflowed = true;
+                    self.events.push_back(message(event));
+                }
+            },
+        }
+        self.peak_len = self.peak_len.max(self.events.len());
+    }
+
+    fn has_room(&self) -> bool {
+        match self.capacity {
+            Some((capacity, _)) => self.events.len() < capacity,
+            None => true,
+        }
+    }
+}
+
+/// Identifier of an observer added with `EventStream::observe()`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ObserverId(usize);
+
+/// Stream of messages of a widget.
+///
+/// The messages are dispatched to the callback from the GLib main loop.
 pub struct EventStream<MSG> {
-    stream: Arc<Mutex<_EventStream<MSG>>>,
+    stream: Rc<RefCell<_EventStream<MSG>>>,
 }
 
-impl<MSG> EventStream<MSG> {
-    pub fn new(sender: Arc<Mutex<Sender>>) -> Self {
+impl<MSG> Clone for EventStream<MSG> {
+    fn clone(&self) -> Self {
         EventStream {
-            stream: Arc::new(Mutex::new(_EventStream {
+            stream: self.stream.clone(),
+        }
+    }
+}
+
+impl<MSG: 'static> EventStream<MSG> {
+    pub fn new() -> Self {
+        EventStream {
+            stream: Rc::new(RefCell::new(_EventStream {
+                actions: VecDeque::new(),
+                batched: false,
+                batch_requested: false,
+                batch_scheduler: None,
+                callback: None,
+                capacity: None,
+                close_callbacks: vec![],
+                delayed: HashMap::new(),
+                dropped: 0,
                 events: VecDeque::new(),
+                next_observer_id: 0,
                 observers: vec![],
-                sender: sender,
-                task: None,
+                overflowed: false,
+                parked: vec![],
+                peak_len: 0,
+                policies: HashMap::new(),
+                source: None,
                 terminated: false,
-                ui_events: VecDeque::new(),
+                variant: None,
             })),
         }
     }
 
-    pub fn close(&self) -> Result<(), Error> {
-        let mut stream = self.stream.lock().unwrap();
-        stream.sender.lock().unwrap().close()?;
-        stream.terminated = true;
-        if let Some(ref task) = stream.task {
-            task.unpark();
+    /// Stop dispatching the messages, remove the pending GLib source and drop the callback, the
+    /// batch scheduler and the observers.
+    /// The functions registered with `on_close()` are then called.
+    pub fn close(&self) {
+        let (callback, close_callbacks, delayed, observers, source) = {
+            let mut stream = self.stream.borrow_mut();
+            if stream.terminated {
+                return;
+            }
+            stream.terminated = true;
+            stream.actions.clear();
+            stream.batch_scheduler = None;
+            stream.events.clear();
+            stream.parked.clear();
+            let delayed: Vec<_> = stream.delayed.drain().map(|(_, delayed)| delayed.source).collect();
+            (stream.callback.take(), mem::replace(&mut stream.close_callbacks, vec![]), delayed,
+                mem::replace(&mut stream.observers, vec![]), stream.source.take())
+        };
+        if let Some(source) = source {
+            glib::source_remove(source);
+        }
+        for source in delayed {
+            glib::source_remove(source);
+        }
+        // Drop the closures outside of the borrow since they might own components using this stream.
+        drop(callback);
+        drop(observers);
+        for close_callback in close_callbacks {
+            close_callback();
         }
-        Ok(())
     }
 
-    pub fn emit(&self, event: MSG)
-        where MSG: Clone + 'static
-    {
-        let mut stream = self.stream.lock().unwrap();
-        if let Some(ref task) = stream.task {
-            task.unpark();
+    /// Send a message to the observers and add it to the queue of the callback.
+    ///
+    /// The observers receive a reference, so that the message is not cloned.
+    pub fn emit(&self, event: MSG) {
+        let observers: Vec<_> = {
+            let stream = self.stream.borrow();
+            if stream.terminated {
+                return;
+            }
+            stream.observers.iter().map(|&(_, ref observer)| observer.clone()).collect()
+        };
+
+        for observer in observers {
+            observer(&event);
         }
-        // TODO: try to avoid clone by sending a reference.
-        stream.events.push_back(event.clone());
 
-        for observer in &stream.observers {
-            observer(event.clone());
+        {
+            let mut stream = self.stream.borrow_mut();
+            // An observer might have closed the stream.
+            if stream.terminated {
+                return;
+            }
+            self.push(&mut stream, event);
         }
+
+        self.schedule();
     }
 
-    fn get_event(&self) -> Option<MSG> {
-        self.stream.lock().unwrap().events.pop_front()
+    /// Call `action` when the stream is dispatched, before the pending messages.
+    ///
+    /// This is used to update the model from a GTK+ callback called while the model is borrowed,
+    /// like when a property is set from the `update()` method.
+    pub fn exec<ACTION: Fn() + 'static>(&self, action: ACTION) {
+        {
+            let mut stream = self.stream.borrow_mut();
+            if stream.terminated {
+                return;
+            }
+            stream.actions.push_back(Box::new(action));
+        }
+        self.schedule();
     }
 
-    fn is_terminated(&self) -> bool {
-        let stream = self.stream.lock().unwrap();
-        stream.terminated
+    /// Dispatch the pending messages right away.
+    ///
+    /// This is how the messages are dispatched when the stream is batched.
+    pub fn flush(&self) {
+        let source = self.stream.borrow_mut().source.take();
+        if let Some(source) = source {
+            glib::source_remove(source);
+        }
+        self.dispatch();
+        self.stream.borrow_mut().batch_requested = false;
     }
 
-    pub fn observe<CALLBACK: Fn(MSG) + Send + 'static>(&self, callback: CALLBACK) {
-        self.stream.lock().unwrap().observers.push(Box::new(callback));
+    /// Check whether the messages are only dispatched by `flush()`.
+    pub fn is_batched(&self) -> bool {
+        self.stream.borrow().batched
     }
 
-    pub fn pop_ui_events(&self) -> Option<MSG> {
-        self.stream.lock().unwrap().ui_events.pop_front()
+    /// Check whether the stream was closed.
+    pub fn is_closed(&self) -> bool {
+        self.stream.borrow().terminated
+    }
+
+    /// Get statistics about the queue of pending messages.
+    pub fn metrics(&self) -> QueueMetrics {
+        let stream = self.stream.borrow();
+        QueueMetrics {
+            dropped: stream.dropped,
+            len: stream.events.len(),
+            peak_len: stream.peak_len,
+        }
+    }
+
+    /// Call `callback` with a reference to every message emitted on this stream.
+    pub fn observe<CALLBACK: Fn(&MSG) + 'static>(&self, callback: CALLBACK) -> ObserverId {
+        let mut stream = self.stream.borrow_mut();
+        let id = ObserverId(stream.next_observer_id);
+        stream.next_observer_id += 1;
+        stream.observers.push((id, Rc::new(callback)));
+        id
+    }
+
+    /// Add a function to call when the stream is closed.
+    /// It is called right away if the stream is already closed.
+    pub fn on_close<CALLBACK: Fn() + 'static>(&self, callback: CALLBACK) {
+        if self.is_closed() {
+            callback();
+        }
+        else {
+            self.stream.borrow_mut().close_callbacks.push(Box::new(callback));
+        }
+    }
+
+    /// Only dispatch the messages when `flush()` is called, instead of on the next iteration of
+    /// the main loop.
+    pub fn set_batched(&self, batched: bool) {
+        let source = {
+            let mut stream = self.stream.borrow_mut();
+            stream.batched = batched;
+            if batched { stream.source.take() } else { None }
+        };
+        if let Some(source) = source {
+            glib::source_remove(source);
+        }
+        self.schedule();
     }
-}
 
-impl<MSG: Clone + 'static> Stream for EventStream<MSG> {
-    type Item = MSG;
-    type Error = ();
+    /// Set the function called when a message is queued while the stream is batched, to arrange
+    /// for `flush()` to be called later, like at the next frame.
+    ///
+    /// It is only called again after `flush()` is called.
+    pub fn set_batch_scheduler<SCHEDULER: Fn() + 'static>(&self, scheduler: SCHEDULER) {
+        self.stream.borrow_mut().batch_scheduler = Some(Rc::new(scheduler));
+        self.schedule();
+    }
+
+    /// Set how the messages of `variant` are dispatched to the callback.
+    ///
+    /// The variant of a message is given by the function set with `set_variant()`.
+    /// The observers still receive every message.
+    pub fn set_policy(&self, variant: &'static str, policy: Policy) {
+        let _ = self.stream.borrow_mut().policies.insert(variant, policy);
+    }
+
+    /// Set the function giving the name of the variant of a message, used to apply the policies.
+    pub fn set_variant<VARIANT: Fn(&MSG) -> &'static str + 'static>(&self, variant: VARIANT) {
+        self.stream.borrow_mut().variant = Some(Rc::new(variant));
+    }
+
+    /// Set the function called on the GTK+ thread for every message emitted on this stream.
+    pub fn set_callback<CALLBACK: Fn(MSG) + 'static>(&self, callback: CALLBACK) {
+        {
+            let mut stream = self.stream.borrow_mut();
+            if stream.terminated {
+                return;
+            }
+            stream.callback = Some(Rc::new(callback));
+        }
+        self.schedule();
+    }
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        if self.is_terminated() {
-            Ok(Async::Ready(None))
+    /// Check whether a producer can emit a message.
+    ///
+    /// When the queue is full with the `Overflow::Block` policy, this returns `NotReady` and the
+    /// current task is notified once a message is dispatched: hence, this must be called from a future.
+    pub fn poll_ready(&self) -> Async<()> {
+        let mut stream = self.stream.borrow_mut();
+        let blocked =
+            match stream.capacity {
+                Some((_, Overflow::Block)) => !stream.terminated && !stream.has_room(),
+                _ => false,
+            };
+        if blocked {
+            stream.parked.push(task::current());
+            Async::NotReady
         }
         else {
-            match self.get_event() {
-                Some(event) => {
-                    let mut stream = self.stream.lock().unwrap();
-                    stream.task = None;
-                    stream.ui_events.push_back(event.clone());
-                    stream.sender.lock().unwrap().send();
-                    // TODO: try to avoid clone by sending a reference.
-                    Ok(Async::Ready(Some(event)))
-                },
-                None => {
-                    let mut stream = self.stream.lock().unwrap();
-                    stream.task = Some(task::park());
-                    Ok(Async::NotReady)
-                },
+            Async::Ready(())
+        }
+    }
+
+    /// Limit the number of pending messages to `capacity`, applying `overflow` to the messages
+    /// emitted when the queue is full.
+    pub fn set_capacity(&self, capacity: usize, overflow: Overflow<MSG>) {
+        let overflow =
+            match overflow {
+                // There is no room to make for the new message.
+                Overflow::DropOldest if capacity == 0 => Overflow::DropNewest,
+                overflow => overflow,
+            };
+        self.stream.borrow_mut().capacity = Some((capacity, overflow));
+        self.unpark();
+    }
+
+    /// Remove an observer added with `observe()`.
+    pub fn unobserve(&self, id: ObserverId) {
+        let observer = {
+            let mut stream = self.stream.borrow_mut();
+            let index = stream.observers.iter().position(|&(observer_id, _)| observer_id == id);
+            index.map(|index| stream.observers.remove(index))
+        };
+        drop(observer);
+    }
+
+    fn dispatch(&self) {
+        self.stream.borrow_mut().source = None;
+        loop {
+            let action = self.stream.borrow_mut().actions.pop_front();
+            if let Some(action) = action {
+                action();
+                continue;
+            }
+            let (callback, event) = {
+                let mut stream = self.stream.borrow_mut();
+                let callback =
+                    match stream.callback {
+                        Some(ref callback) => callback.clone(),
+                        None => return,
+                    };
+                match stream.events.pop_front() {
+                    Some(event) => (callback, event),
+                    None => return,
+                }
+            };
+            self.unpark();
+            callback(event);
+        }
+    }
+
+    /*
+     * Notify the parked producers if there is room in the queue.
+     */
+    fn unpark(&self) {
+        let parked = {
+            let mut stream = self.stream.borrow_mut();
+            if !stream.has_room() {
+                return;
+            }
+            mem::replace(&mut stream.parked, vec![])
+        };
+        for task in parked {
+            task.notify();
+        }
+    }
+
+    /*
+     * Add the message to the queue, according to the policy of its variant.
+     */
+    fn push(&self, stream: &mut _EventStream<MSG>, event: MSG) {
+        let variant_of = match stream.variant {
+            Some(ref variant) if !stream.policies.is_empty() => variant.clone(),
+            _ => {
+                stream.enqueue(event);
+                return;
+            },
+        };
+        let variant = variant_of(&event);
+        match stream.policies.get(variant).cloned() {
+            None => stream.enqueue(event),
+            Some(Policy::Coalesce) => {
+                match stream.events.iter().position(|pending| variant_of(pending) == variant) {
+                    Some(index) => stream.events[index] = event,
+                    None => stream.enqueue(event),
+                }
+            },
+            Some(Policy::Debounce(duration)) => {
+                if let Some(delayed) = stream.delayed.remove(variant) {
+                    glib::source_remove(delayed.source);
+                }
+                let _ = stream.delayed.insert(variant, Delayed {
+                    message: Some(event),
+                    source: self.release_after(variant, duration),
+                });
+            },
+            Some(Policy::Throttle(duration)) => {
+                if let Some(delayed) = stream.delayed.get_mut(variant) {
+                    delayed.message = Some(event);
+                    return;
+                }
+                stream.enqueue(event);
+                let _ = stream.delayed.insert(variant, Delayed {
+                    message: None,
+                    source: self.release_after(variant, duration),
+                });
+            },
+        }
+    }
+
+    /*
+     * Queue the delayed message of the variant when the duration elapses.
+     */
+    fn release_after(&self, variant: &'static str, duration: Duration) -> SourceId {
+        let millis = duration.as_secs() as u32 * 1000 + duration.subsec_nanos() / 1_000_000;
+        let stream = self.clone();
+        gtk::timeout_add(millis, move || {
+            Continue(stream.release(variant))
+        })
+    }
+
+    /*
+     * Returns whether the timer must keep running.
+     */
+    fn release(&self, variant: &'static str) -> bool {
+        let keep_timer = {
+            let mut stream = self.stream.borrow_mut();
+            let message = stream.delayed.get_mut(variant).and_then(|delayed| delayed.message.take());
+            // A throttled variant waits for another interval after a message is dispatched.
+            let throttled =
+                match stream.policies.get(variant) {
+                    Some(&Policy::Throttle(_)) => true,
+                    _ => false,
+                };
+            let keep_timer = message.is_some() && throttled;
+            if let Some(message) = message {
+                stream.enqueue(message);
+            }
+            if !keep_timer {
+                let _ = stream.delayed.remove(variant);
+            }
+            keep_timer
+        };
+        self.schedule();
+        keep_timer
+    }
+
+    fn schedule(&self) {
+        let batch_scheduler = {
+            let mut stream = self.stream.borrow_mut();
+            if stream.batched {
+                if stream.batch_requested || (stream.events.is_empty() && stream.actions.is_empty()) {
+                    return;
+                }
+                let scheduler =
+                    match stream.batch_scheduler {
+                        Some(ref scheduler) => scheduler.clone(),
+                        None => return,
+                    };
+                stream.batch_requested = true;
+                Some(scheduler)
+            }
+            else {
+                let nothing_to_dispatch = stream.source.is_some() ||
+                    ((stream.callback.is_none() || stream.events.is_empty()) && stream.actions.is_empty());
+                if nothing_to_dispatch {
+                    return;
+                }
+                None
             }
+        };
+        // The scheduler is called outside of the borrow since it could flush the stream.
+        if let Some(batch_scheduler) = batch_scheduler {
+            batch_scheduler();
+            return;
+        }
+        let stream = self.clone();
+        let source = gtk::idle_add(move || {
+            stream.dispatch();
+            Continue(false)
+        });
+        self.stream.borrow_mut().source = Some(source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use futures::{Async, Future, Poll, future, task};
+    use futures::executor::{self, Notify};
+
+    use super::{Core, EventStream, Overflow, Policy, QueueMetrics, poll_ready};
+
+    type Message = (&'static str, u32);
+
+    // Set the flag when dropped, to check that a future was dropped.
+    struct DropFlag(Rc<Cell<bool>>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    // Count the notifications of a task.
+    struct Notified(AtomicUsize);
+
+    impl Notify for Notified {
+        fn notify(&self, _id: usize) {
+            let _ = self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Record the messages dispatched by a batched stream whose variant is the first element of the message.
+    fn batched_stream() -> (EventStream<Message>, Rc<RefCell<Vec<Message>>>) {
+        let stream = EventStream::new();
+        stream.set_batched(true);
+        stream.set_variant(|&(variant, _): &Message| variant);
+        let messages = Rc::new(RefCell::new(vec![]));
+        {
+            let messages = messages.clone();
+            stream.set_callback(move |message| messages.borrow_mut().push(message));
+        }
+        (stream, messages)
+    }
+
+    // Poll the futures until none is ready, without running the GLib main loop which requires GTK+.
+    fn poll(core: &Core) {
+        let tasks = Rc::downgrade(&core.tasks);
+        while !core.notifier.ready.lock().unwrap().is_empty() {
+            poll_ready(&core.notifier, &tasks);
+        }
+    }
+
+    #[test]
+    fn batch_scheduler() {
+        let stream = EventStream::new();
+        stream.set_batched(true);
+        let messages = Rc::new(RefCell::new(vec![]));
+        {
+            let messages = messages.clone();
+            stream.set_callback(move |msg: u32| messages.borrow_mut().push(msg));
+        }
+        let requests = Rc::new(Cell::new(0));
+        {
+            let requests = requests.clone();
+            stream.set_batch_scheduler(move || requests.set(requests.get() + 1));
+        }
+        assert_eq!(requests.get(), 0);
+
+        // The scheduler is only called once until the stream is flushed.
+        stream.emit(1);
+        stream.emit(2);
+        assert_eq!(requests.get(), 1);
+        assert!(messages.borrow().is_empty());
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![1, 2]);
+
+        stream.flush();
+        assert_eq!(requests.get(), 1);
+        stream.emit(3);
+        assert_eq!(requests.get(), 2);
+
+        // The scheduler is dropped with the stream.
+        stream.close();
+        assert_eq!(Rc::strong_count(&requests), 1);
+    }
+
+    #[test]
+    fn spawn() {
+        let core = Core::new_virtual().unwrap();
+        let done = Rc::new(Cell::new(false));
+        let id = {
+            let done = done.clone();
+            core.spawn(future::lazy(move || {
+                done.set(true);
+                Ok(())
+            }))
+        };
+        // The future is first polled by the main loop.
+        assert!(!done.get());
+        assert!(core.is_running(id));
+        poll(&core);
+        assert!(done.get());
+        assert!(!core.is_running(id));
+    }
+
+    #[test]
+    fn spawn_while_polling() {
+        let core = Core::new_virtual().unwrap();
+        let done = Rc::new(Cell::new(false));
+        {
+            let done = done.clone();
+            let spawner = core.clone();
+            core.spawn(future::lazy(move || {
+                spawner.spawn(future::lazy(move || {
+                    done.set(true);
+                    Ok(())
+                }));
+                Ok(())
+            }));
+        }
+        poll(&core);
+        assert!(done.get());
+    }
+
+    #[test]
+    fn cancel() {
+        let core = Core::new_virtual().unwrap();
+        let dropped = Rc::new(Cell::new(false));
+        let fired = Rc::new(Cell::new(false));
+        let id = {
+            let flag = DropFlag(dropped.clone());
+            let fired = fired.clone();
+            core.spawn(core.timeout(Duration::from_millis(10))
+                .map(move |()| {
+                    drop(flag);
+                    fired.set(true);
+                })
+                .map_err(|_| ()))
+        };
+        poll(&core);
+        assert!(core.is_running(id));
+        assert!(core.cancel(id));
+        assert!(dropped.get());
+        assert!(!core.is_running(id));
+        assert!(!core.cancel(id));
+
+        core.clock.as_ref().unwrap().set_now(Duration::from_millis(10));
+        poll(&core);
+        assert!(!fired.get());
+    }
+
+    #[test]
+    fn cancel_while_polling() {
+        let core = Core::new_virtual().unwrap();
+        let id = Rc::new(Cell::new(None));
+        let polls = Rc::new(Cell::new(0));
+        let task_id = {
+            let canceller = core.clone();
+            let id = id.clone();
+            let polls = polls.clone();
+            core.spawn(future::poll_fn(move || {
+                polls.set(polls.get() + 1);
+                assert!(canceller.cancel(id.get().unwrap()));
+                task::current().notify();
+                Ok(Async::NotReady)
+            }))
+        };
+        id.set(Some(task_id));
+        poll(&core);
+        // The future notified itself, but it is not polled again since it cancelled itself.
+        assert_eq!(polls.get(), 1);
+        assert!(!core.is_running(task_id));
+    }
+
+    #[test]
+    fn shutdown() {
+        let core = Core::new_virtual().unwrap();
+        let dropped: Vec<_> = (0..2).map(|_| Rc::new(Cell::new(false))).collect();
+        let ids: Vec<_> = dropped.iter()
+            .map(|dropped| {
+                let flag = DropFlag(dropped.clone());
+                core.spawn(future::empty::<(), ()>().map(move |()| drop(flag)))
+            })
+            .collect();
+        poll(&core);
+        core.shutdown();
+        assert!(dropped.iter().all(|dropped| dropped.get()));
+        assert!(ids.iter().all(|&id| !core.is_running(id)));
+
+        // The futures spawned after the shutdown are still run.
+        let done = Rc::new(Cell::new(false));
+        {
+            let done = done.clone();
+            core.spawn(future::lazy(move || {
+                done.set(true);
+                Ok(())
+            }));
+        }
+        poll(&core);
+        assert!(done.get());
+    }
+
+    #[test]
+    fn coalesce() {
+        let (stream, messages) = batched_stream();
+        stream.set_policy("A", Policy::Coalesce);
+        let observed = Rc::new(Cell::new(0));
+        {
+            let observed = observed.clone();
+            stream.observe(move |_| observed.set(observed.get() + 1));
         }
+        stream.emit(("A", 1));
+        stream.emit(("B", 1));
+        stream.emit(("A", 2));
+        stream.emit(("B", 2));
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("A", 2), ("B", 1), ("B", 2)]);
+        // The observers still receive every message.
+        assert_eq!(observed.get(), 4);
+
+        // A dispatched message is not replaced.
+        stream.emit(("A", 3));
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("A", 2), ("B", 1), ("B", 2), ("A", 3)]);
+    }
+
+    // This is the only test using GTK+, since it can only be used from the thread where it was
+    // initialized and every test runs in its own thread.
+    #[test]
+    fn debounce_and_throttle() {
+        unsafe {
+            gtk::set_initialized();
+        }
+        // The timers never expire during the test: their expiration is simulated by calling release().
+        let (stream, messages) = batched_stream();
+        stream.set_policy("D", Policy::Debounce(Duration::from_secs(60)));
+        stream.set_policy("T", Policy::Throttle(Duration::from_secs(60)));
+
+        // The last message is dispatched once no message was emitted for the duration.
+        stream.emit(("D", 1));
+        stream.emit(("D", 2));
+        stream.flush();
+        assert!(messages.borrow().is_empty());
+        assert!(!stream.release("D"));
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("D", 2)]);
+
+        // The first message is dispatched right away and the last one at the end of the interval.
+        stream.emit(("T", 1));
+        stream.emit(("T", 2));
+        stream.emit(("T", 3));
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("D", 2), ("T", 1)]);
+        assert!(stream.release("T"));
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("D", 2), ("T", 1), ("T", 3)]);
+
+        // The timer stops after an interval without messages.
+        assert!(!stream.release("T"));
+        stream.emit(("T", 4));
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("D", 2), ("T", 1), ("T", 3), ("T", 4)]);
+        stream.close();
+    }
+
+    #[test]
+    fn overflow_drop() {
+        let (stream, messages) = batched_stream();
+        stream.set_capacity(2, Overflow::DropNewest);
+        stream.emit(("A", 1));
+        stream.emit(("A", 2));
+        stream.emit(("A", 3));
+        assert_eq!(stream.metrics(), QueueMetrics { dropped: 1, len: 2, peak_len: 2 });
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("A", 1), ("A", 2)]);
+
+        stream.set_capacity(2, Overflow::DropOldest);
+        stream.emit(("A", 4));
+        stream.emit(("A", 5));
+        stream.emit(("A", 6));
+        assert_eq!(stream.metrics(), QueueMetrics { dropped: 2, len: 2, peak_len: 2 });
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("A", 1), ("A", 2), ("A", 5), ("A", 6)]);
+        assert_eq!(stream.metrics().len, 0);
+    }
+
+    #[test]
+    fn overflow_drop_oldest_without_capacity() {
+        let (stream, messages) = batched_stream();
+        stream.set_capacity(0, Overflow::DropOldest);
+        stream.emit(("A", 1));
+        stream.emit(("A", 2));
+        assert_eq!(stream.metrics(), QueueMetrics { dropped: 2, len: 0, peak_len: 0 });
+        stream.flush();
+        assert!(messages.borrow().is_empty());
+    }
+
+    #[test]
+    fn overflow_message() {
+        let (stream, messages) = batched_stream();
+        stream.set_capacity(1, Overflow::Message(Rc::new(|(_, value)| ("Overflow", value))));
+        stream.emit(("A", 1));
+        // The overflow message is only queued once until the queue has room again.
+        stream.emit(("A", 2));
+        stream.emit(("A", 3));
+        assert_eq!(stream.metrics(), QueueMetrics { dropped: 2, len: 2, peak_len: 2 });
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("A", 1), ("Overflow", 2)]);
+
+        stream.emit(("A", 4));
+        stream.emit(("A", 5));
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("A", 1), ("Overflow", 2), ("A", 4), ("Overflow", 5)]);
+    }
+
+    #[test]
+    fn overflow_block() {
+        let (stream, messages) = batched_stream();
+        stream.set_capacity(1, Overflow::Block);
+        let notified = Arc::new(Notified(AtomicUsize::new(0)));
+        let mut producer = {
+            let stream = stream.clone();
+            executor::spawn(future::poll_fn(move || -> Poll<(), ()> {
+                Ok(stream.poll_ready())
+            }))
+        };
+        assert_eq!(producer.poll_future_notify(&notified, 0), Ok(Async::Ready(())));
+        stream.emit(("A", 1));
+        // The producer is parked while the queue is full, but its messages are still queued.
+        assert_eq!(producer.poll_future_notify(&notified, 0), Ok(Async::NotReady));
+        stream.emit(("A", 2));
+        assert_eq!(stream.metrics(), QueueMetrics { dropped: 0, len: 2, peak_len: 2 });
+
+        // The producer is notified once a message is dispatched and the queue has room.
+        stream.flush();
+        assert_eq!(*messages.borrow(), vec![("A", 1), ("A", 2)]);
+        assert_eq!(notified.0.load(Ordering::SeqCst), 1);
+        assert_eq!(producer.poll_future_notify(&notified, 0), Ok(Async::Ready(())));
     }
 }
@@ -0,0 +1,275 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::{Async, Future, Poll, Stream};
+use futures::task::{self, Task as ParkedTask};
+use glib::{self, Continue, SourceId};
+
+/// Clock of a `Core` created with `Core::new_virtual()`: the time only passes when
+/// `Core::advance()` is called.
+pub struct VirtualClock {
+    now: Cell<Duration>,
+    timers: RefCell<Vec<(Duration, ParkedTask)>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        VirtualClock {
+            now: Cell::new(Duration::from_secs(0)),
+            timers: RefCell::new(vec![]),
+        }
+    }
+
+    /// Get the earliest deadline for which a timer is waiting.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.timers.borrow().iter().map(|&(deadline, _)| deadline).min()
+    }
+
+    /// Get the time elapsed since the creation of the clock.
+    pub fn now(&self) -> Duration {
+        self.now.get()
+    }
+
+    /// Move the clock to `now` and wake up the timers whose deadline is reached.
+    pub fn set_now(&self, now: Duration) {
+        self.now.set(now);
+        let timers = mem::replace(&mut *self.timers.borrow_mut(), vec![]);
+        let (expired, waiting): (Vec<_>, Vec<_>) = timers.into_iter()
+            .partition(|&(deadline, _)| deadline <= now);
+        *self.timers.borrow_mut() = waiting;
+        for (_, task) in expired {
+            task.notify();
+        }
+    }
+
+    fn wait(&self, deadline: Duration) -> Async<()> {
+        if deadline <= self.now.get() {
+            Async::Ready(())
+        }
+        else {
+            self.timers.borrow_mut().push((deadline, task::current()));
+            Async::NotReady
+        }
+    }
+}
+
+// Timer firing from a GLib timeout.
+struct GlibTimer {
+    source: RefCell<Option<SourceId>>,
+    task: RefCell<Option<ParkedTask>>,
+    // Number of times the timer fired and which were not yet consumed by `poll()`.
+    ticks: Cell<u32>,
+}
+
+impl GlibTimer {
+    fn new(duration: Duration, repeat: bool) -> Rc<Self> {
+        let timer = Rc::new(GlibTimer {
+            source: RefCell::new(None),
+            task: RefCell::new(None),
+            ticks: Cell::new(0),
+        });
+        let millis = duration.as_secs() as u32 * 1000 + duration.subsec_nanos() / 1_000_000;
+        let source = {
+            let timer = Rc::downgrade(&timer);
+            gtk::timeout_add(millis, move || {
+                let timer =
+                    match timer.upgrade() {
+                        Some(timer) => timer,
+                        None => return Continue(false),
+                    };
+                timer.ticks.set(timer.ticks.get() + 1);
+                if let Some(task) = timer.task.borrow_mut().take() {
+                    task.notify();
+                }
+                if !repeat {
+                    // The source is removed by GLib.
+                    *timer.source.borrow_mut() = None;
+                }
+                Continue(repeat)
+            })
+        };
+        *timer.source.borrow_mut() = Some(source);
+        timer
+    }
+
+    fn poll_tick(&self) -> Async<()> {
+        let ticks = self.ticks.get();
+        if ticks > 0 {
+            self.ticks.set(ticks - 1);
+            Async::Ready(())
+        }
+        else {
+            *self.task.borrow_mut() = Some(task::current());
+            Async::NotReady
+        }
+    }
+}
+
+impl Drop for GlibTimer {
+    fn drop(&mut self) {
+        if let Some(source) = self.source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+    }
+}
+
+enum Timer {
+    Real(Rc<GlibTimer>),
+    Virtual(Rc<VirtualClock>, Duration),
+}
+
+impl Timer {
+    fn new(duration: Duration, repeat: bool, clock: Option<&Rc<VirtualClock>>) -> Self {
+        match clock {
+            Some(clock) => Timer::Virtual(clock.clone(), clock.now() + duration),
+            None => Timer::Real(GlibTimer::new(duration, repeat)),
+        }
+    }
+}
+
+/// Future completing after a duration, created with `Core::timeout()`.
+///
+/// It uses a GLib timeout, or the virtual clock if the `Core` has one.
+/// The error type is the one of the tokio timers, but it never fails.
+pub struct Timeout {
+    timer: Timer,
+}
+
+impl Timeout {
+    #[doc(hidden)]
+    pub fn new(duration: Duration, clock: Option<&Rc<VirtualClock>>) -> Self {
+        Timeout {
+            timer: Timer::new(duration, false, clock),
+        }
+    }
+}
+
+impl Future for Timeout {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        match self.timer {
+            Timer::Real(ref timer) => Ok(timer.poll_tick()),
+            Timer::Virtual(ref clock, deadline) => Ok(clock.wait(deadline)),
+        }
+    }
+}
+
+/// Stream yielding `()` periodically, created with `Core::interval()`.
+///
+/// It uses a GLib timeout, or the virtual clock if the `Core` has one.
+/// The error type is the one of the tokio timers, but it never fails.
+pub struct Interval {
+    period: Duration,
+    timer: Timer,
+}
+
+impl Interval {
+    #[doc(hidden)]
+    pub fn new(period: Duration, clock: Option<&Rc<VirtualClock>>) -> Self {
+        Interval {
+            period: period,
+            timer: Timer::new(period, true, clock),
+        }
+    }
+}
+
+impl Stream for Interval {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<()>, io::Error> {
+        match self.timer {
+            Timer::Real(ref timer) => Ok(timer.poll_tick().map(Some)),
+            Timer::Virtual(ref clock, ref mut deadline) =>
+                match clock.wait(*deadline) {
+                    Async::Ready(()) => {
+                        *deadline += self.period;
+                        Ok(Async::Ready(Some(())))
+                    },
+                    Async::NotReady => Ok(Async::NotReady),
+                },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use futures::Async;
+    use futures::executor::{self, Notify};
+
+    use super::{Interval, Timeout, VirtualClock};
+
+    // Count the notifications of a task.
+    struct Notified(AtomicUsize);
+
+    impl Notify for Notified {
+        fn notify(&self, _id: usize) {
+            let _ = self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn virtual_timeout() {
+        let clock = Rc::new(VirtualClock::new());
+        clock.set_now(Duration::from_millis(100));
+        let notified = Arc::new(Notified(AtomicUsize::new(0)));
+        // The deadline is relative to the current time of the clock.
+        let mut timeout = executor::spawn(Timeout::new(Duration::from_millis(10), Some(&clock)));
+        assert_eq!(timeout.poll_future_notify(&notified, 0).unwrap(), Async::NotReady);
+        assert_eq!(clock.next_deadline(), Some(Duration::from_millis(110)));
+
+        clock.set_now(Duration::from_millis(109));
+        assert_eq!(notified.0.load(Ordering::SeqCst), 0);
+        clock.set_now(Duration::from_millis(110));
+        assert_eq!(notified.0.load(Ordering::SeqCst), 1);
+        assert_eq!(clock.next_deadline(), None);
+        assert_eq!(timeout.poll_future_notify(&notified, 0).unwrap(), Async::Ready(()));
+    }
+
+    #[test]
+    fn virtual_interval() {
+        let clock = Rc::new(VirtualClock::new());
+        let notified = Arc::new(Notified(AtomicUsize::new(0)));
+        let mut interval = executor::spawn(Interval::new(Duration::from_millis(10), Some(&clock)));
+        assert_eq!(interval.poll_stream_notify(&notified, 0).unwrap(), Async::NotReady);
+
+        // The ticks missed while the clock jumped are yielded right away.
+        clock.set_now(Duration::from_millis(25));
+        assert_eq!(notified.0.load(Ordering::SeqCst), 1);
+        assert_eq!(interval.poll_stream_notify(&notified, 0).unwrap(), Async::Ready(Some(())));
+        assert_eq!(interval.poll_stream_notify(&notified, 0).unwrap(), Async::Ready(Some(())));
+        assert_eq!(interval.poll_stream_notify(&notified, 0).unwrap(), Async::NotReady);
+        assert_eq!(clock.next_deadline(), Some(Duration::from_millis(30)));
+    }
+}
@@ -117,7 +117,7 @@ fn derive_clone(ast: &MacroInput) -> Tokens {
     }
 }
 
-fn derive_clone_enum(name: &Ident, typ: Tokens, mut generics: Generics, variants: &[Variant]) -> Tokens {
+fn derive_clone_enum(name: &Ident, typ: Tokens, generics: Generics, variants: &[Variant]) -> Tokens {
     let variant_idents_values: Vec<_> = variants.iter().map(|variant| {
         let has_value =
             if let VariantData::Tuple(_) = variant.data {
@@ -153,6 +153,21 @@ fn derive_clone_enum(name: &Ident, typ: Tokens, mut generics: Generics, variants
         }
     });
 
+    let generics = clone_generics(generics);
+
+    quote! {
+        impl #generics Clone for #typ {
+            fn clone(&self) -> Self {
+                match *self {
+                    #(#variant_patterns => #variant_values,)*
+                }
+            }
+        }
+    }
+}
+
+// Require the type parameter to be Clone.
+fn clone_generics(mut generics: Generics) -> Generics {
     let path = quote! {
         Clone
     };
@@ -165,16 +180,7 @@ fn derive_clone_enum(name: &Ident, typ: Tokens, mut generics: Generics, variants
             }, TraitBoundModifier::None)
         ];
     }
-
-    quote! {
-        impl #generics Clone for #typ {
-            fn clone(&self) -> Self {
-                match *self {
-                    #(#variant_patterns => #variant_values,)*
-                }
-            }
-        }
-    }
+    generics
 }
 
 fn derive_clone_struct(name: &Ident, typ: Tokens, generics: &Generics, fields: &[Field]) -> Tokens {
@@ -193,11 +199,15 @@ fn derive_clone_struct(name: &Ident, typ: Tokens, generics: &Generics, fields: &
 }
 
 fn derive_display_variant(ast: &MacroInput) -> Tokens {
-    let generics = &ast.generics;
     let name = &ast.ident;
-    let typ = quote! {
-        #name #generics
+    let typ = {
+        let generics = &ast.generics;
+        quote! {
+            #name #generics
+        }
     };
+    // The messages are cloned for update_command().
+    let generics = &clone_generics(ast.generics.clone());
 
     if let Body::Enum(ref variants) = ast.body {
         let variant_idents_values: Vec<_> = variants.iter().map(|variant| {
@@ -233,6 +243,10 @@ fn derive_display_variant(ast: &MacroInput) -> Tokens {
                         #(#variant_patterns => #variant_names,)*
                     }
                 }
+
+                fn clone_for_command(&self) -> Option<Self> {
+                    Some(self.clone())
+                }
             }
         }
     }
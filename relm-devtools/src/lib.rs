@@ -0,0 +1,337 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Message inspector and time-travel debugger for relm.
+//!
+//! A `Recorder` attached to a component records every message it receives with the time spent in
+//! the update methods and, when they implement `Debug`, the model before and after the update.
+//! The records can be shown in a `DevTools` window or written to a log, for instance on a socket,
+//! and the messages can be replayed on a new component to reproduce a bug:
+//!
+//! ```ignore
+//! let recorder = Recorder::new("Win").with_debug().with_replay();
+//! recorder.attach(&component);
+//! let devtools = DevTools::new();
+//! devtools.add(&recorder);
+//! // Later, on a new component:
+//! recorder.replay(&new_component);
+//! ```
+
+extern crate glib;
+extern crate gtk;
+extern crate relm;
+
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::Duration;
+
+use glib::Type;
+use gtk::{CellLayoutExt, ContainerExt, WidgetExt, WindowExt};
+use gtk::WindowType::Toplevel;
+use relm::{Component, DisplayVariant, Inspector, Widget};
+
+const COLUMNS: &[&str] = &["Component", "Message", "Duration", "Model before", "Model after"];
+
+/// Update of a component recorded by a `Recorder`.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// Name of the component given to `Recorder::new()`.
+    pub component: String,
+    /// Time spent in the update methods.
+    pub duration: Duration,
+    /// The message formatted with `Debug` when enabled with `Recorder::with_debug()`, its variant
+    /// name otherwise.
+    pub message: String,
+    /// The model after the update, when `Debug` is enabled.
+    pub model_after: Option<String>,
+    /// The model before the update, when `Debug` is enabled.
+    pub model_before: Option<String>,
+    /// Name of the variant of the message.
+    pub variant: &'static str,
+}
+
+impl Record {
+    /// Get the duration of the update in milliseconds.
+    pub fn millis(&self) -> f64 {
+        self.duration.as_secs() as f64 * 1000.0 + self.duration.subsec_nanos() as f64 / 1_000_000.0
+    }
+}
+
+// The start of the record, saved before the update.
+struct Pending {
+    message: String,
+    model_before: Option<String>,
+    variant: &'static str,
+}
+
+/// Recorder of the messages of a component.
+pub struct Recorder<WIDGET: Widget> {
+    clone_message: Option<fn(&WIDGET::Msg) -> WIDGET::Msg>,
+    format: Option<(fn(&WIDGET::Msg) -> String, fn(&WIDGET::Model) -> String)>,
+    listeners: Rc<RefCell<Vec<Box<Fn(&Record)>>>>,
+    messages: Rc<RefCell<Vec<WIDGET::Msg>>>,
+    name: String,
+    pending: Rc<RefCell<Option<Pending>>>,
+    records: Rc<RefCell<Vec<Record>>>,
+}
+
+impl<WIDGET: Widget> Clone for Recorder<WIDGET> {
+    fn clone(&self) -> Self {
+        Recorder {
+            clone_message: self.clone_message,
+            format: self.format,
+            listeners: self.listeners.clone(),
+            messages: self.messages.clone(),
+            name: self.name.clone(),
+            pending: self.pending.clone(),
+            records: self.records.clone(),
+        }
+    }
+}
+
+impl<WIDGET> Recorder<WIDGET>
+    where WIDGET: Widget + 'static,
+          WIDGET::Msg: DisplayVariant + 'static,
+{
+    /// Create a recorder for the component `name`, only recording the variant name of the messages.
+    pub fn new(name: &str) -> Self {
+        Recorder {
+            clone_message: None,
+            format: None,
+            listeners: Rc::new(RefCell::new(vec![])),
+            messages: Rc::new(RefCell::new(vec![])),
+            name: name.to_string(),
+            pending: Rc::new(RefCell::new(None)),
+            records: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    /// Record the messages and the model formatted with `Debug`.
+    pub fn with_debug(mut self) -> Self
+        where WIDGET::Model: Debug,
+              WIDGET::Msg: Debug,
+    {
+        self.format = Some((format_debug::<WIDGET::Msg>, format_debug::<WIDGET::Model>));
+        self
+    }
+
+    /// Keep a copy of the messages to be able to replay them with `replay()`.
+    pub fn with_replay(mut self) -> Self
+        where WIDGET::Msg: Clone,
+    {
+        self.clone_message = Some(<WIDGET::Msg as Clone>::clone);
+        self
+    }
+
+    /// Record the updates of `component`.
+    pub fn attach(&self, component: &Component<WIDGET>) {
+        component.inspect(self.clone());
+    }
+
+    /// Remove the records and the messages recorded so far.
+    pub fn clear(&self) {
+        self.messages.borrow_mut().clear();
+        self.records.borrow_mut().clear();
+    }
+
+    /// Write every new record to `writer`, like a `TcpStream`, in the format of `write_log()`.
+    ///
+    /// The writing stops at the first error.
+    pub fn log_to<WRITER: Write + 'static>(&self, writer: WRITER) {
+        let writer = RefCell::new(Some(writer));
+        self.on_record(move |record| {
+            let mut writer = writer.borrow_mut();
+            let failed =
+                match *writer {
+                    Some(ref mut writer) => write_record(writer, record).and_then(|()| writer.flush()).is_err(),
+                    None => false,
+                };
+            if failed {
+                *writer = None;
+            }
+        });
+    }
+
+    /// Get the messages recorded since `with_replay()` was called.
+    pub fn messages(&self) -> Vec<WIDGET::Msg>
+        where WIDGET::Msg: Clone,
+    {
+        self.messages.borrow().clone()
+    }
+
+    /// Call `callback` for every new record.
+    pub fn on_record<CALLBACK: Fn(&Record) + 'static>(&self, callback: CALLBACK) {
+        self.listeners.borrow_mut().push(Box::new(callback));
+    }
+
+    /// Get the records of the updates.
+    pub fn records(&self) -> Vec<Record> {
+        self.records.borrow().clone()
+    }
+
+    /// Send the recorded messages, in order, to `component`, usually a new component created to
+    /// reproduce a bug.
+    pub fn replay(&self, component: &Component<WIDGET>)
+        where WIDGET::Msg: Clone,
+    {
+        for message in self.messages() {
+            component.emit(message);
+        }
+    }
+
+    /// Write the records to `writer`, one update per line followed by the models, if any.
+    pub fn write_log<WRITER: Write>(&self, writer: &mut WRITER) -> io::Result<()> {
+        for record in self.records.borrow().iter() {
+            write_record(writer, record)?;
+        }
+        Ok(())
+    }
+}
+
+impl<WIDGET> Inspector<WIDGET> for Recorder<WIDGET>
+    where WIDGET: Widget,
+          WIDGET::Msg: DisplayVariant,
+{
+    fn before_update(&self, event: &WIDGET::Msg, model: &WIDGET::Model) {
+        if let Some(clone_message) = self.clone_message {
+            self.messages.borrow_mut().push(clone_message(event));
+        }
+        let (message, model_before) =
+            match self.format {
+                Some((format_message, format_model)) => (format_message(event), Some(format_model(model))),
+                None => (event.display_variant().to_string(), None),
+            };
+        *self.pending.borrow_mut() = Some(Pending {
+            message: message,
+            model_before: model_before,
+            variant: event.display_variant(),
+        });
+    }
+
+    fn after_update(&self, model: &WIDGET::Model, duration: Duration) {
+        let pending =
+            match self.pending.borrow_mut().take() {
+                Some(pending) => pending,
+                None => return,
+            };
+        let record = Record {
+            component: self.name.clone(),
+            duration: duration,
+            message: pending.message,
+            model_after: self.format.map(|(_, format_model)| format_model(model)),
+            model_before: pending.model_before,
+            variant: pending.variant,
+        };
+        for listener in self.listeners.borrow().iter() {
+            listener(&record);
+        }
+        self.records.borrow_mut().push(record);
+    }
+}
+
+/// Window listing the updates recorded by `Recorder`s.
+pub struct DevTools {
+    store: gtk::ListStore,
+    window: gtk::Window,
+}
+
+impl DevTools {
+    /// Create and show the window.
+    pub fn new() -> Self {
+        let types: Vec<_> = COLUMNS.iter().map(|_| Type::String).collect();
+        let store = gtk::ListStore::new(&types);
+        let tree_view = gtk::TreeView::new_with_model(&store);
+        for (index, title) in COLUMNS.iter().enumerate() {
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            let cell = gtk::CellRendererText::new();
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", index as i32);
+            let _ = tree_view.append_column(&column);
+        }
+        let scrolled_window = gtk::ScrolledWindow::new(None, None);
+        scrolled_window.add(&tree_view);
+
+        let window = gtk::Window::new(Toplevel);
+        window.set_title("relm devtools");
+        window.set_default_size(800, 400);
+        window.add(&scrolled_window);
+        window.show_all();
+
+        DevTools {
+            store: store,
+            window: window,
+        }
+    }
+
+    /// Show the records of `recorder` in this window, as they are created.
+    pub fn add<WIDGET>(&self, recorder: &Recorder<WIDGET>)
+        where WIDGET: Widget + 'static,
+              WIDGET::Msg: DisplayVariant + 'static,
+    {
+        for record in recorder.records() {
+            self.add_record(&record);
+        }
+        let devtools = self.clone();
+        recorder.on_record(move |record| devtools.add_record(record));
+    }
+
+    /// Get the window.
+    pub fn window(&self) -> &gtk::Window {
+        &self.window
+    }
+
+    fn add_record(&self, record: &Record) {
+        let duration = format!("{:.3}ms", record.millis());
+        let model_before = record.model_before.clone().unwrap_or_default();
+        let model_after = record.model_after.clone().unwrap_or_default();
+        let columns: Vec<_> = (0..COLUMNS.len() as u32).collect();
+        let _ = self.store.insert_with_values(None, &columns,
+            &[&record.component, &record.message, &duration, &model_before, &model_after]);
+    }
+}
+
+impl Clone for DevTools {
+    fn clone(&self) -> Self {
+        DevTools {
+            store: self.store.clone(),
+            window: self.window.clone(),
+        }
+    }
+}
+
+fn format_debug<T: Debug>(value: &T) -> String {
+    format!("{:?}", value)
+}
+
+fn write_record<WRITER: Write>(writer: &mut WRITER, record: &Record) -> io::Result<()> {
+    writeln!(writer, "{} {} ({:.3}ms)", record.component, record.message, record.millis())?;
+    if let Some(ref model) = record.model_before {
+        writeln!(writer, "    before: {}", model)?;
+    }
+    if let Some(ref model) = record.model_after {
+        writeln!(writer, "    after: {}", model)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gtk;
+extern crate relm;
+extern crate relm_devtools;
+
+use relm::{DisplayVariant, RemoteRelm, TestRuntime, Widget};
+use relm_devtools::Recorder;
+
+use self::Msg::*;
+
+#[derive(Clone, Debug)]
+enum Msg {
+    Add(i32),
+    Double,
+    Reset,
+}
+
+impl DisplayVariant for Msg {
+    fn display_variant(&self) -> &'static str {
+        match *self {
+            Add(_) => "Add",
+            Double => "Double",
+            Reset => "Reset",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Counter {
+    label: gtk::Label,
+}
+
+impl Widget for Counter {
+    type Model = Vec<i32>;
+    type ModelParam = ();
+    type Msg = Msg;
+    type Root = gtk::Label;
+
+    fn model(_: ()) -> Vec<i32> {
+        vec![]
+    }
+
+    fn root(&self) -> &gtk::Label {
+        &self.label
+    }
+
+    fn update(&mut self, event: Msg, model: &mut Vec<i32>) {
+        let last = model.last().cloned().unwrap_or(0);
+        match event {
+            Add(value) => model.push(last + value),
+            Double => model.push(last * 2),
+            Reset => model.clear(),
+        }
+    }
+
+    fn view(_relm: &RemoteRelm<Self>, _model: &Vec<i32>) -> Self {
+        Counter {
+            label: gtk::Label::new(None),
+        }
+    }
+}
+
+#[test]
+fn replay() {
+    let runtime = TestRuntime::new().unwrap();
+    let counter = runtime.component::<Counter>(());
+    let recorder = Recorder::new("Counter").with_debug().with_replay();
+    recorder.attach(counter.component());
+
+    counter.send(Add(2));
+    counter.send(Double);
+    counter.send(Reset);
+    counter.send(Add(3));
+    counter.send(Double);
+    assert_eq!(*counter.model(), vec![3, 6]);
+
+    let records = recorder.records();
+    let variants: Vec<_> = records.iter().map(|record| record.variant).collect();
+    assert_eq!(variants, vec!["Add", "Double", "Reset", "Add", "Double"]);
+    assert_eq!(records[0].message, "Add(2)");
+    assert_eq!(records[0].model_before, Some("[]".to_string()));
+    assert_eq!(records[1].model_after, Some("[2, 4]".to_string()));
+
+    // Replaying the messages on a fresh component reproduces the same model.
+    let replayed = runtime.component::<Counter>(());
+    recorder.replay(replayed.component());
+    replayed.process();
+    assert_eq!(*replayed.model(), *counter.model());
+    assert_eq!(records.last().and_then(|record| record.model_after.clone()), Some(format!("{:?}", *replayed.model())));
+    // The replayed component is not recorded.
+    assert_eq!(recorder.records().len(), 5);
+}
@@ -19,43 +19,27 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use std::boxed;
+/*
+ * Insert the calls to the property setters of the widgets depending on the model fields mutated in
+ * a method.
+ *
+ * The setters are inserted once at the end of every branch of the control flow (the arms of a
+ * match and the branches of an if at the end of a block) and before the return expressions.
+ * Since the types are unknown, a field is considered mutated when it is assigned to (including
+ * nested paths like model.a.b), borrowed mutably or when a method known to mutate its receiver (like
+ * push() or a method ending with _mut) is called on it. The whole model is considered mutated when it
+ * is given to a function or a method.
+ */
 
 use quote::Tokens;
-use syn;
-use syn::{Expr, ExprKind, Ident, Stmt, parse_expr};
-use syn::ExprKind::{Assign, AssignOp, Block, Field};
+use syn::{self, Arm, Block, Expr, ExprKind, Ident, Mutability, Stmt, UnOp, parse_expr};
 use syn::fold::{Folder, noop_fold_expr};
-use syn::Stmt::Semi;
-use syn::Unsafety::Normal;
+use syn::visit::{Visitor, walk_expr};
 
+use computed::COMPUTED_PREFIX;
 use super::PropertyModelMap;
 
-macro_rules! fold_assign {
-    ($_self:expr, $lhs:expr, $new_assign:expr) => {{
-        let mut statements = vec![];
-        let new_statements =
-            if let Field(ref field_expr, ref ident) = $lhs.node {
-                if is_model_path(field_expr) {
-                    Some(create_stmts(ident, $_self.map))
-                }
-                else {
-                    None
-                }
-            }
-            else {
-                None
-            };
-        let mut new_assign = $new_assign;
-        if let Some(mut stmts) = new_statements {
-            let new_expr = Expr { node: new_assign, attrs: vec![] };
-            statements.push(Semi(boxed::Box::new(new_expr)));
-            statements.append(&mut stmts);
-            new_assign = Block(Normal, syn::Block { stmts: statements })
-        }
-        new_assign
-    }};
-}
+const VALUE_IDENT: &str = "__relm_value";
 
 pub struct Adder<'a> {
     map: &'a PropertyModelMap,
@@ -64,67 +48,623 @@ pub struct Adder<'a> {
 impl<'a> Adder<'a> {
     pub fn new(map: &'a PropertyModelMap) -> Self {
         Adder {
-            map: map,
+            map,
         }
     }
-}
 
-impl<'a> Folder for Adder<'a> {
-    fn fold_expr(&mut self, expr: Expr) -> Expr {
-        let lhs_clone =
+    /// Add the property setters to the body of a method.
+    pub fn add_updates(&mut self, block: Block) -> Block {
+        let all_mutations = mutations_in_block(&block);
+        let block = self.block_with_updates(block, Mutations::new());
+        let mut return_folder = ReturnFolder {
+            setters: self.setters(&all_mutations),
+        };
+        return_folder.fold_block(block)
+    }
+
+    fn block_with_updates(&self, mut block: Block, mut pending: Mutations) -> Block {
+        let last_is_branch = block.stmts.last().map(is_branch_stmt).unwrap_or(false);
+        if last_is_branch {
+            let last = block.stmts.pop().expect("last statement");
+            for stmt in &block.stmts {
+                pending.add_stmt(stmt);
+            }
+            let last =
+                match last {
+                    Stmt::Expr(expr) => Stmt::Expr(Box::new(self.branch_with_updates(*expr, pending))),
+                    Stmt::Semi(expr) => Stmt::Semi(Box::new(self.branch_with_updates(*expr, pending))),
+                    _ => unreachable!(),
+                };
+            block.stmts.push(last);
+            block
+        }
+        else {
+            for stmt in &block.stmts {
+                pending.add_stmt(stmt);
+            }
+            let setters = self.setters(&pending);
+            if setters.is_empty() {
+                return block;
+            }
+            match block.stmts.pop() {
+                Some(Stmt::Expr(ref expr)) if is_unit(expr) => {
+                    block.stmts.push(Stmt::Semi(expr.clone()));
+                    block.stmts.extend(setters);
+                },
+                Some(Stmt::Expr(expr)) => {
+                    // Keep the value of the block.
+                    let value = Ident::new(VALUE_IDENT);
+                    block.stmts.push(to_stmt(quote! { let #value = #expr; }));
+                    block.stmts.extend(setters);
+                    block.stmts.push(Stmt::Expr(Box::new(to_expr(quote! { #value }))));
+                },
+                Some(stmt) => {
+                    block.stmts.push(stmt);
+                    block.stmts.extend(setters);
+                },
+                None => block.stmts.extend(setters),
+            }
+            block
+        }
+    }
+
+    fn branch_with_updates(&self, expr: Expr, mut pending: Mutations) -> Expr {
+        let node =
             match expr.node {
-                Assign(ref lhs, _) | AssignOp(_, ref lhs, _) => lhs.clone(),
-                _ => return noop_fold_expr(self, expr),
+                ExprKind::Match(scrutinee, arms) => {
+                    pending.add_expr(&scrutinee);
+                    let arms = arms.into_iter()
+                        .map(|arm| Arm {
+                            body: Box::new(self.body_with_updates(*arm.body, pending.clone())),
+                            .. arm
+                        })
+                        .collect();
+                    ExprKind::Match(scrutinee, arms)
+                },
+                ExprKind::If(condition, then_block, else_expr) => {
+                    pending.add_expr(&condition);
+                    let (then_block, else_expr) = self.if_with_updates(then_block, else_expr, pending);
+                    ExprKind::If(condition, then_block, else_expr)
+                },
+                ExprKind::IfLet(pattern, scrutinee, then_block, else_expr) => {
+                    pending.add_expr(&scrutinee);
+                    let (then_block, else_expr) = self.if_with_updates(then_block, else_expr, pending);
+                    ExprKind::IfLet(pattern, scrutinee, then_block, else_expr)
+                },
+                ExprKind::Block(unsafety, block) => ExprKind::Block(unsafety, self.block_with_updates(block, pending)),
+                node => node,
             };
-        let new_expr = noop_fold_expr(self, expr);
-        let new_node = fold_assign!(self, lhs_clone, new_expr.node);
         Expr {
-            node: new_node,
-            attrs: new_expr.attrs.into_iter().map(|a| self.fold_attribute(a)).collect(),
+            node,
+            attrs: expr.attrs,
+        }
+    }
+
+    fn body_with_updates(&self, expr: Expr, mut pending: Mutations) -> Expr {
+        if is_branch(&expr) {
+            return self.branch_with_updates(expr, pending);
+        }
+        pending.add_expr(&expr);
+        let setters = self.setters(&pending);
+        if setters.is_empty() {
+            return expr;
+        }
+        if is_unit(&expr) {
+            to_expr(quote! {{
+                #expr;
+                #(#setters)*
+            }})
         }
+        else {
+            let value = Ident::new(VALUE_IDENT);
+            to_expr(quote! {{
+                let #value = #expr;
+                #(#setters)*
+                #value
+            }})
+        }
+    }
+
+    fn if_with_updates(&self, then_block: Block, else_expr: Option<Box<Expr>>, pending: Mutations)
+        -> (Block, Option<Box<Expr>>)
+    {
+        let then_block = self.block_with_updates(then_block, pending.clone());
+        let else_expr =
+            match else_expr {
+                Some(else_expr) => Some(Box::new(self.branch_with_updates(*else_expr, pending))),
+                None => {
+                    // The setters are also needed when the condition is false.
+                    let setters = self.setters(&pending);
+                    if setters.is_empty() {
+                        None
+                    }
+                    else {
+                        Some(Box::new(to_expr(quote! {{
+                            #(#setters)*
+                        }})))
+                    }
+                },
+            };
+        (then_block, else_expr)
+    }
+
+    fn setters(&self, mutations: &Mutations) -> Vec<Stmt> {
+        let mut stmts = vec![];
+        // The cached values of the computed functions are updated before the properties using them.
+        for &computed in &[true, false] {
+            // A widget depending on many fields is only updated once.
+            let mut add_stmts = |ident| {
+                for stmt in create_stmts(ident, self.map, computed) {
+                    if !stmts.contains(&stmt) {
+                        stmts.push(stmt);
+                    }
+                }
+            };
+            if mutations.whole_model {
+                // The properties depending on a computed function are set when its value changes.
+                for ident in self.map.keys().filter(|ident| !ident.as_ref().starts_with(COMPUTED_PREFIX)) {
+                    add_stmts(ident);
+                }
+            }
+            else {
+                for ident in &mutations.fields {
+                    add_stmts(ident);
+                }
+            }
+        }
+        stmts
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PropertyKind {
+    // The cached value of a #[computed] function, named by `name`.
+    Computed,
+    // The children created by an `if`, a `match` or a `for`, rebuilt when the model changes.
+    Flow,
+    Gtk,
+    Relm,
+}
+
 #[derive(Debug, Eq, Hash, PartialEq)]
 pub struct Property {
     pub expr: String,
-    pub is_relm_widget: bool,
+    pub kind: PropertyKind,
     pub name: String,
     pub widget_name: Ident,
 }
 
-fn create_stmts(ident: &Ident, map: &PropertyModelMap) -> Vec<Stmt> {
+#[derive(Clone)]
+struct Mutations {
+    fields: Vec<Ident>,
+    whole_model: bool,
+}
+
+impl Mutations {
+    fn new() -> Self {
+        Mutations {
+            fields: vec![],
+            whole_model: false,
+        }
+    }
+
+    fn add(&mut self, target: Target) {
+        match target {
+            Target::Field(ident) => {
+                if !self.fields.contains(&ident) {
+                    self.fields.push(ident);
+                }
+            },
+            Target::Model => self.whole_model = true,
+            Target::Other => (),
+        }
+    }
+
+    fn add_expr(&mut self, expr: &Expr) {
+        MutationVisitor { mutations: self }.visit_expr(expr);
+    }
+
+    fn add_stmt(&mut self, stmt: &Stmt) {
+        MutationVisitor { mutations: self }.visit_stmt(stmt);
+    }
+}
+
+struct MutationVisitor<'a> {
+    mutations: &'a mut Mutations,
+}
+
+impl<'a> Visitor for MutationVisitor<'a> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr.node {
+            ExprKind::Assign(ref lhs, _) | ExprKind::AssignOp(_, ref lhs, _) =>
+                self.mutations.add(model_target(lhs)),
+            ExprKind::AddrOf(Mutability::Mutable, ref expr) => self.mutations.add(model_target(expr)),
+            ExprKind::MethodCall(ref method, _, ref args) => {
+                if let Some(receiver) = args.first() {
+                    if is_mutating_method(method) {
+                        self.mutations.add(model_target(receiver));
+                    }
+                }
+                for arg in args.iter().skip(1) {
+                    self.add_argument(arg);
+                }
+            },
+            ExprKind::Call(_, ref args) => {
+                for arg in args {
+                    self.add_argument(arg);
+                }
+            },
+            _ => (),
+        }
+        walk_expr(self, expr);
+    }
+}
+
+impl<'a> MutationVisitor<'a> {
+    // The model given as an argument might be mutated by the function.
+    fn add_argument(&mut self, arg: &Expr) {
+        if let Target::Model = model_target(arg) {
+            self.mutations.add(Target::Model);
+        }
+    }
+}
+
+struct ReturnFolder {
+    setters: Vec<Stmt>,
+}
+
+impl Folder for ReturnFolder {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        match expr.node {
+            // A return in a closure does not return from the method.
+            ExprKind::Closure(..) => expr,
+            ExprKind::Ret(_) if !self.setters.is_empty() => {
+                let setters = &self.setters;
+                to_expr(quote! {{
+                    #(#setters)*
+                    #expr
+                }})
+            },
+            _ => noop_fold_expr(self, expr),
+        }
+    }
+}
+
+// Methods of the standard library taking &mut self, which are not known to be mutating otherwise.
+const MUTATING_METHODS: &[&str] = &[
+    "append", "clear", "dedup", "drain", "entry", "extend", "insert", "pop", "pop_back", "pop_front", "push",
+    "push_back", "push_front", "push_str", "remove", "replace", "resize", "retain", "reverse", "set", "sort",
+    "sort_by", "sort_by_key", "sort_unstable", "split_off", "swap", "swap_remove", "take", "truncate",
+];
+
+/*
+ * Other methods can be called on a mutable borrow to be considered mutating, like
+ * (&mut model.list).add(item).
+ */
+fn is_mutating_method(method: &Ident) -> bool {
+    let method = method.as_ref();
+    method.ends_with("_mut") || MUTATING_METHODS.contains(&method)
+}
+
+enum Target {
+    Field(Ident),
+    Model,
+    Other,
+}
+
+/*
+ * Get the field of the model at the root of a place expression like model.a.b[0].
+ */
+fn model_target(expr: &Expr) -> Target {
+    match expr.node {
+        ExprKind::Field(ref object, ref ident) => {
+            if is_model_path(object) {
+                Target::Field(ident.clone())
+            }
+            else {
+                model_target(object)
+            }
+        },
+        ExprKind::Index(ref object, _) | ExprKind::TupField(ref object, _) | ExprKind::Paren(ref object) |
+            ExprKind::Unary(UnOp::Deref, ref object) => model_target(object),
+        ExprKind::Path(..) if is_model_path(expr) => Target::Model,
+        _ => Target::Other,
+    }
+}
+
+fn mutations_in_block(block: &Block) -> Mutations {
+    let mut mutations = Mutations::new();
+    for stmt in &block.stmts {
+        mutations.add_stmt(stmt);
+    }
+    mutations
+}
+
+fn create_stmts(ident: &Ident, map: &PropertyModelMap, computed: bool) -> Vec<Stmt> {
     let mut stmts = vec![];
     if let Some(properties) = map.get(ident) {
+        let properties = properties.iter()
+            .filter(|property| (property.kind == PropertyKind::Computed) == computed);
         for property in properties {
             let widget_name = &property.widget_name;
             let prop_name = Ident::new(format!("set_{}", property.name));
             let mut tokens = Tokens::new();
             tokens.append(&property.expr);
             let stmt =
-                if property.is_relm_widget {
-                    quote! {
-                        { self.#widget_name.widget().#prop_name(#tokens); }
-                    }
-                }
-                else {
-                    quote! {
-                        { self.#widget_name.#prop_name(#tokens); }
-                    }
+                match property.kind {
+                    PropertyKind::Computed => {
+                        let function = Ident::new(property.name.as_str());
+                        let setters = create_stmts(widget_name, map, false);
+                        quote! {
+                            if self.#widget_name.update(Self::#function(model)) {
+                                #(#setters)*
+                            }
+                        }
+                    },
+                    PropertyKind::Flow => quote! {
+                        self.#widget_name.rebuild(model);
+                    },
+                    PropertyKind::Gtk => quote! {
+                        self.#widget_name.#prop_name(#tokens);
+                    },
+                    PropertyKind::Relm => quote! {
+                        self.#widget_name.widget().#prop_name(#tokens);
+                    },
                 };
-            let expr = parse_expr(&stmt.parse::<String>().expect("parse::<String>() in create_stmts"))
-                .expect("parse_expr() in create_stmts");
-            if let ExprKind::Block(_, ref block) = expr.node {
-                stmts.push(block.stmts[0].clone());
-            }
+            stmts.push(to_stmt(stmt));
         }
     }
     stmts
 }
 
-fn is_model_path(expr: &syn::Expr) -> bool {
+fn is_branch(expr: &Expr) -> bool {
+    match expr.node {
+        ExprKind::Match(..) | ExprKind::If(..) | ExprKind::IfLet(..) => true,
+        ExprKind::Block(unsafety, _) => unsafety == syn::Unsafety::Normal,
+        _ => false,
+    }
+}
+
+fn is_branch_stmt(stmt: &Stmt) -> bool {
+    match *stmt {
+        Stmt::Expr(ref expr) | Stmt::Semi(ref expr) => is_branch(expr),
+        _ => false,
+    }
+}
+
+// Check whether the expression is known to have the type ().
+fn is_unit(expr: &Expr) -> bool {
+    match expr.node {
+        ExprKind::Assign(..) | ExprKind::AssignOp(..) | ExprKind::ForLoop(..) | ExprKind::While(..) |
+            ExprKind::WhileLet(..) => true,
+        ExprKind::Paren(ref expr) => is_unit(expr),
+        _ => false,
+    }
+}
+
+fn is_model_path(expr: &Expr) -> bool {
     if let ExprKind::Path(_, ref path) = expr.node {
         return path.segments.len() == 1 && path.segments[0].ident == Ident::new("model");
     }
     false
 }
+
+fn to_expr(tokens: Tokens) -> Expr {
+    parse_expr(tokens.as_str()).expect("parse_expr() in to_expr()")
+}
+
+/*
+ * Parse a single statement by parsing it in a block.
+ */
+fn to_stmt(tokens: Tokens) -> Stmt {
+    let expr = to_expr(quote! {{
+        #tokens
+    }});
+    if let ExprKind::Block(_, mut block) = expr.node {
+        block.stmts.remove(0)
+    }
+    else {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use quote::Tokens;
+    use syn::{ExprKind, Ident};
+
+    use super::{Adder, Property, PropertyKind, to_expr};
+
+    // The label shows the counter and the entry shows the text.
+    fn add_updates(body: Tokens) -> String {
+        let mut map = HashMap::new();
+        for &(field, widget_name, expr) in &[("counter", "label", "&model.counter.to_string()"),
+            ("text", "entry", "&model.text")]
+        {
+            let mut properties = HashSet::new();
+            properties.insert(Property {
+                expr: expr.to_string(),
+                kind: PropertyKind::Gtk,
+                name: "text".to_string(),
+                widget_name: Ident::new(widget_name),
+            });
+            map.insert(Ident::new(field), properties);
+        }
+        let block = block(body);
+        let block = Adder::new(&map).add_updates(block);
+        quote!(#block).to_string()
+    }
+
+    // Parse the block and print it again, to compare it with a generated block.
+    fn normalize(body: Tokens) -> String {
+        let block = block(body);
+        quote!(#block).to_string()
+    }
+
+    fn block(body: Tokens) -> ::syn::Block {
+        match to_expr(body).node {
+            ExprKind::Block(_, block) => block,
+            _ => panic!("expected a block"),
+        }
+    }
+
+    #[test]
+    fn end_of_block() {
+        assert_eq!(add_updates(quote! {{
+            model.counter += 1;
+            model.counter *= 2;
+        }}), normalize(quote! {{
+            model.counter += 1;
+            model.counter *= 2;
+            self.label.set_text(&model.counter.to_string());
+        }}));
+    }
+
+    #[test]
+    fn match_arms() {
+        assert_eq!(add_updates(quote! {{
+            match event {
+                Increment => model.counter += 1,
+                Change(text) => model.text = text,
+                Quit => gtk::main_quit(),
+            }
+        }}), normalize(quote! {{
+            match event {
+                Increment => {
+                    model.counter += 1;
+                    self.label.set_text(&model.counter.to_string());
+                },
+                Change(text) => {
+                    model.text = text;
+                    self.entry.set_text(&model.text);
+                },
+                Quit => gtk::main_quit(),
+            }
+        }}));
+    }
+
+    #[test]
+    fn if_without_else() {
+        // The fields mutated before the branch are updated in both branches.
+        assert_eq!(add_updates(quote! {{
+            model.text.clear();
+            if model.counter > 0 {
+                model.counter -= 1;
+            }
+        }}), normalize(quote! {{
+            model.text.clear();
+            if model.counter > 0 {
+                model.counter -= 1;
+                self.entry.set_text(&model.text);
+                self.label.set_text(&model.counter.to_string());
+            }
+            else {
+                self.entry.set_text(&model.text);
+            }
+        }}));
+    }
+
+    #[test]
+    fn mutations() {
+        // Nested fields and mutable borrows mutate the field at their root.
+        assert_eq!(add_updates(quote! {{
+            model.counter.value = 1;
+            swap(&mut model.text, &mut text);
+        }}), normalize(quote! {{
+            model.counter.value = 1;
+            swap(&mut model.text, &mut text);
+            self.label.set_text(&model.counter.to_string());
+            self.entry.set_text(&model.text);
+        }}));
+        // Reading a field, even with a method, does not update its widgets.
+        assert_eq!(add_updates(quote! {{
+            println!("{}", model.counter);
+            let length = model.text.len();
+            let first = model.text.chars().next();
+        }}), normalize(quote! {{
+            println!("{}", model.counter);
+            let length = model.text.len();
+            let first = model.text.chars().next();
+        }}));
+    }
+
+    #[test]
+    fn mutating_methods() {
+        assert_eq!(add_updates(quote! {{
+            model.text.push_str("text");
+        }}), normalize(quote! {{
+            model.text.push_str("text");
+            self.entry.set_text(&model.text);
+        }}));
+        assert_eq!(add_updates(quote! {{
+            *model.counter.get_mut() += 1;
+        }}), normalize(quote! {{
+            *model.counter.get_mut() += 1;
+            self.label.set_text(&model.counter.to_string());
+        }}));
+        // Other methods are called on a mutable borrow.
+        assert_eq!(add_updates(quote! {{
+            (&mut model.text).make_ascii_uppercase();
+        }}), normalize(quote! {{
+            (&mut model.text).make_ascii_uppercase();
+            self.entry.set_text(&model.text);
+        }}));
+    }
+
+    #[test]
+    fn whole_model() {
+        // Every widget is updated, in the order of the map.
+        let code = add_updates(quote! {{
+            reset(model);
+        }});
+        let label_first = normalize(quote! {{
+            reset(model);
+            self.label.set_text(&model.counter.to_string());
+            self.entry.set_text(&model.text);
+        }});
+        let entry_first = normalize(quote! {{
+            reset(model);
+            self.entry.set_text(&model.text);
+            self.label.set_text(&model.counter.to_string());
+        }});
+        assert!(code == label_first || code == entry_first, "unexpected updates in {}", code);
+    }
+
+    #[test]
+    fn block_value() {
+        assert_eq!(add_updates(quote! {{
+            model.counter += 1;
+            model.counter
+        }}), normalize(quote! {{
+            model.counter += 1;
+            let __relm_value = model.counter;
+            self.label.set_text(&model.counter.to_string());
+            __relm_value
+        }}));
+    }
+
+    #[test]
+    fn early_return() {
+        assert_eq!(add_updates(quote! {{
+            model.counter += 1;
+            if model.counter > 10 {
+                return;
+            }
+            model.text.clear();
+        }}), normalize(quote! {{
+            model.counter += 1;
+            if model.counter > 10 {
+                {
+                    self.label.set_text(&model.counter.to_string());
+                    self.entry.set_text(&model.text);
+                    return
+                };
+            }
+            model.text.clear();
+            self.label.set_text(&model.counter.to_string());
+            self.entry.set_text(&model.text);
+        }}));
+    }
+}
@@ -0,0 +1,327 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+/*
+ * Functions annotated with #[computed], whose value is cached in the widget struct.
+ *
+ * They are used in the view as Self::name(model) and the properties using them are only set when
+ * their value changes, after a field they read is mutated.
+ */
+
+use syn::{Block, Expr, ExprKind, FnArg, Ident, Mac, MethodSig, Pat, TokenTree, Ty, parse_expr};
+use syn::FunctionRetTy;
+use syn::Token::{Dot, Ident as IdentToken};
+use syn::fold::{Folder, noop_fold_expr};
+use syn::visit::{Visitor, walk_expr};
+
+use error::{Error, Result};
+
+pub const COMPUTED_PREFIX: &str = "__relm_computed_";
+
+#[derive(Debug)]
+pub struct ComputedFn {
+    // Field of the model read by the function.
+    pub deps: Vec<Ident>,
+    pub name: Ident,
+    pub typ: Ty,
+}
+
+impl ComputedFn {
+    pub fn new(name: Ident, sig: &MethodSig, block: &Block) -> Result<Self> {
+        let typ =
+            match sig.decl.output {
+                FunctionRetTy::Ty(ref typ) => typ.clone(),
+                FunctionRetTy::Default =>
+                    return Err(Error::new(format!("the #[computed] function {} must return a value", name))),
+            };
+        match sig.decl.inputs.first() {
+            Some(&FnArg::Captured(Pat::Ident(_, ref ident, _), _)) if *ident == "model" && sig.decl.inputs.len() == 1 => (),
+            _ => return Err(Error::new(format!(
+                "the #[computed] function {} must only take the model, in a parameter named `model`", name))),
+        }
+        let mut visitor = DependencyVisitor {
+            deps: vec![],
+            parent: None,
+            whole_model_uses: vec![],
+        };
+        for stmt in &block.stmts {
+            visitor.visit_stmt(stmt);
+        }
+        if let Some(usage) = visitor.whole_model_uses.first() {
+            return Err(Error::new(format!(
+                "the #[computed] function {} can only read the fields of the model, like `model.field`, but `{}` uses the whole model",
+                name, usage)));
+        }
+        Ok(ComputedFn {
+            deps: visitor.deps,
+            name,
+            typ,
+        })
+    }
+
+    // Field of the widget struct caching the value.
+    pub fn cache_ident(&self) -> Ident {
+        Ident::new(format!("{}{}", COMPUTED_PREFIX, self.name))
+    }
+}
+
+/*
+ * Get the computed functions called in expr.
+ */
+pub fn computed_calls<'a>(expr: &Expr, computed: &'a [ComputedFn]) -> Vec<&'a ComputedFn> {
+    let mut visitor = CallVisitor {
+        calls: vec![],
+        computed,
+    };
+    visitor.visit_expr(expr);
+    visitor.calls
+}
+
+/*
+ * Replace the calls to the computed functions by their cached value, to be used in the setters.
+ */
+pub fn replace_computed_calls(expr: Expr, computed: &[ComputedFn]) -> Expr {
+    let mut folder = CallFolder {
+        computed,
+    };
+    folder.fold_expr(expr)
+}
+
+fn called_computed<'a>(expr: &Expr, computed: &'a [ComputedFn]) -> Option<&'a ComputedFn> {
+    if let ExprKind::Call(ref func, _) = expr.node {
+        if let ExprKind::Path(None, ref path) = func.node {
+            if path.segments.len() == 2 && path.segments[0].ident == "Self" {
+                return computed.iter().find(|function| function.name == path.segments[1].ident);
+            }
+        }
+    }
+    None
+}
+
+fn is_model(expr: &Expr) -> bool {
+    if let ExprKind::Path(None, ref path) = expr.node {
+        path.segments.len() == 1 && path.segments[0].ident == "model"
+    }
+    else {
+        false
+    }
+}
+
+struct CallFolder<'a> {
+    computed: &'a [ComputedFn],
+}
+
+impl<'a> Folder for CallFolder<'a> {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        if let Some(function) = called_computed(&expr, self.computed) {
+            let cache = function.cache_ident();
+            let value = quote! {
+                (*self.#cache.get())
+            };
+            return parse_expr(value.as_str()).expect("cached value should be an expression");
+        }
+        noop_fold_expr(self, expr)
+    }
+}
+
+struct CallVisitor<'a> {
+    calls: Vec<&'a ComputedFn>,
+    computed: &'a [ComputedFn],
+}
+
+impl<'a> Visitor for CallVisitor<'a> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Some(function) = called_computed(expr, self.computed) {
+            self.calls.push(function);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/*
+ * Collect the fields of the model read by a computed function.
+ * The other uses of the model, like model.method() or helper(&model), are collected too, since they
+ * might read any field: the function cannot be cached in this case.
+ */
+struct DependencyVisitor {
+    deps: Vec<Ident>,
+    // The source of the expression being visited.
+    parent: Option<String>,
+    whole_model_uses: Vec<String>,
+}
+
+impl DependencyVisitor {
+    fn visit_tokens(&mut self, tokens: &[TokenTree], parent: &str) {
+        for (index, token) in tokens.iter().enumerate() {
+            match *token {
+                TokenTree::Delimited(ref delimited) => self.visit_tokens(&delimited.tts, parent),
+                TokenTree::Token(IdentToken(ref ident)) if *ident == "model" => {
+                    // A field of another variable, like self.model.
+                    if index > 0 && tokens[index - 1] == TokenTree::Token(Dot) {
+                        continue;
+                    }
+                    match (tokens.get(index + 1), tokens.get(index + 2)) {
+                        (Some(&TokenTree::Token(Dot)), Some(&TokenTree::Token(IdentToken(ref field)))) =>
+                            self.deps.push(field.clone()),
+                        _ => self.whole_model_uses.push(parent.to_string()),
+                    }
+                },
+                TokenTree::Token(_) => (),
+            }
+        }
+    }
+}
+
+impl Visitor for DependencyVisitor {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let ExprKind::Field(ref obj, ref field) = expr.node {
+            if is_model(obj) {
+                self.deps.push(field.clone());
+                return;
+            }
+        }
+        if is_model(expr) {
+            let usage = self.parent.clone().unwrap_or_else(|| "model".to_string());
+            self.whole_model_uses.push(usage);
+            return;
+        }
+        let parent = self.parent.take();
+        self.parent = Some(quote! { #expr }.to_string());
+        walk_expr(self, expr);
+        self.parent = parent;
+    }
+
+    fn visit_mac(&mut self, mac: &Mac) {
+        let parent = quote! { #mac }.to_string();
+        self.visit_tokens(&mac.tts, &parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::Tokens;
+    use syn::{ImplItemKind, ItemKind, parse_item};
+
+    use gen_widget;
+    use super::ComputedFn;
+
+    fn computed_fn(function: Tokens) -> ComputedFn {
+        let item = parse_item(quote! { impl Win { #function } }.as_str()).expect("impl");
+        match item.node {
+            ItemKind::Impl(_, _, _, _, _, ref items) => {
+                match items[0].node {
+                    ImplItemKind::Method(ref sig, ref block) =>
+                        ComputedFn::new(items[0].ident.clone(), sig, block).expect("computed function"),
+                    _ => panic!("expected a method"),
+                }
+            },
+            _ => panic!("expected an impl"),
+        }
+    }
+
+    fn gen_computed(function: Tokens) -> String {
+        gen_widget(quote! {
+            impl Widget for Win {
+                fn model() -> Model {
+                    Model {
+                        items: vec![],
+                    }
+                }
+
+                fn update(&mut self, event: Msg, model: &mut Model) {
+                }
+
+                #[computed]
+                #function
+
+                view! {
+                    gtk::Label {
+                        text: &Self::count(model),
+                    }
+                }
+            }
+        }).to_string()
+    }
+
+    #[test]
+    fn field_dependencies() {
+        let function = computed_fn(quote! {
+            fn count(model: &Model) -> String {
+                let count = model.items.len() + model.other_items.len();
+                format!("{} {}", count, model.unit)
+            }
+        });
+        let deps: Vec<_> = function.deps.iter().map(|dep| dep.as_ref()).collect();
+        assert_eq!(deps, vec!["items", "other_items", "unit"]);
+    }
+
+    #[test]
+    fn whole_model_method_call() {
+        let code = gen_computed(quote! {
+            fn count(model: &Model) -> String {
+                model.count().to_string()
+            }
+        });
+        assert!(code.contains("compile_error"), "{}", code);
+        assert!(code.contains("but `model . count ( )` uses the whole model"), "{}", code);
+    }
+
+    #[test]
+    fn whole_model_argument() {
+        let code = gen_computed(quote! {
+            fn count(model: &Model) -> String {
+                helper(&model).to_string()
+            }
+        });
+        assert!(code.contains("but `& model` uses the whole model"), "{}", code);
+    }
+
+    #[test]
+    fn whole_model_in_macro() {
+        let code = gen_computed(quote! {
+            fn count(model: &Model) -> String {
+                format!("{:?}", model)
+            }
+        });
+        assert!(code.contains("uses the whole model"), "{}", code);
+    }
+
+    #[test]
+    fn whole_model_value() {
+        let code = gen_computed(quote! {
+            fn count(model: &Model) -> String {
+                let model = model;
+                model.items.len().to_string()
+            }
+        });
+        assert!(code.contains("uses the whole model"), "{}", code);
+    }
+
+    #[test]
+    fn field_only() {
+        let code = gen_computed(quote! {
+            fn count(model: &Model) -> String {
+                model.items.len().to_string()
+            }
+        });
+        assert!(!code.contains("compile_error"), "{}", code);
+    }
+}
@@ -0,0 +1,225 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::result;
+
+use quote::{Tokens, ToTokens};
+use syn::{Delimited, DelimToken, TokenTree};
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// Error in the code given to the `#[widget]` attribute or the `view!` macro.
+#[derive(Debug)]
+pub struct Error {
+    message: String,
+    // The address of the token where the error happened, used to find its location once the
+    // error reaches the parser entry point.
+    address: Option<usize>,
+    // The location of the token where the error happened, like `3:12`.
+    location: Option<String>,
+    // The source of the token where the error happened.
+    // TODO: use the span of the token when syn gives access to it.
+    token: Option<String>,
+}
+
+impl Error {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Error {
+            message: message.into(),
+            address: None,
+            location: None,
+            token: None,
+        }
+    }
+
+    /// Error for the `found` token when `expected` was expected.
+    pub fn unexpected(expected: &str, found: &TokenTree) -> Self {
+        let mut tokens = Tokens::new();
+        found.to_tokens(&mut tokens);
+        Error {
+            message: format!("expected {}", expected),
+            address: Some(address(found)),
+            location: None,
+            token: Some(tokens.to_string()),
+        }
+    }
+
+    /// Error for the end of `tokens` when `expected` was expected.
+    pub fn unexpected_end(expected: &str, tokens: &[TokenTree]) -> Self {
+        let mut error = Error::new(format!("expected {} but found the end of the block", expected));
+        error.address = Some(end_address(tokens));
+        error
+    }
+
+    /// Set the location of the error from the token where it happened, if any.
+    pub fn locate(mut self, locations: &Locations) -> Self {
+        if self.location.is_none() {
+            self.location = self.address
+                .and_then(|address| locations.locations.get(&address))
+                .map(|&(line, column)| {
+                    match locations.file {
+                        Some(ref file) => format!("{}:{}:{}", file, line, column),
+                        None => format!("{}:{} of the view! macro", line, column),
+                    }
+                });
+        }
+        self
+    }
+
+    /// Generate a `compile_error!()` with this error.
+    pub fn to_compile_error(&self) -> Tokens {
+        let message = self.to_string();
+        quote! {
+            compile_error!(#message);
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.message)?;
+        if let Some(ref token) = self.token {
+            write!(formatter, ", found `{}`", token)?;
+        }
+        if let Some(ref location) = self.location {
+            write!(formatter, " at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lines and columns of the tokens of a view, indexed by the address of the tokens.
+///
+/// syn does not give access to the spans of the tokens, so they are searched in the source of the
+/// view instead.
+/// The end of a block is indexed by the address following its last token.
+pub struct Locations {
+    file: Option<String>,
+    locations: HashMap<usize, (usize, usize)>,
+}
+
+impl Locations {
+    /// Find `tokens` in `source`, starting at the byte `start`.
+    ///
+    /// `file` is the name of the file containing `source`, if the view is not in the `view!` macro.
+    pub fn new(source: &str, start: usize, tokens: &[TokenTree], file: Option<&str>) -> Self {
+        let mut cursor = Cursor {
+            column: 1,
+            line: 1,
+            offset: 0,
+            source,
+        };
+        cursor.advance(start);
+        let mut locations = Locations {
+            file: file.map(str::to_string),
+            locations: HashMap::new(),
+        };
+        locations.add(&mut cursor, tokens);
+        locations.locations.insert(end_address(tokens), cursor.location());
+        locations
+    }
+
+    fn add(&mut self, cursor: &mut Cursor, tokens: &[TokenTree]) {
+        for token in tokens {
+            match *token {
+                TokenTree::Delimited(Delimited { ref delim, ref tts }) => {
+                    let (open, close) =
+                        match *delim {
+                            DelimToken::Brace => ("{", "}"),
+                            DelimToken::Bracket => ("[", "]"),
+                            DelimToken::Paren => ("(", ")"),
+                        };
+                    let location = cursor.find(open);
+                    self.locations.insert(address(token), location);
+                    self.add(cursor, tts);
+                    let location = cursor.find(close);
+                    self.locations.insert(end_address(tts), location);
+                },
+                TokenTree::Token(_) => {
+                    let mut text = Tokens::new();
+                    token.to_tokens(&mut text);
+                    let location = cursor.find(text.as_str().trim());
+                    self.locations.insert(address(token), location);
+                },
+            }
+        }
+    }
+}
+
+struct Cursor<'a> {
+    column: usize,
+    line: usize,
+    offset: usize,
+    source: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn advance(&mut self, offset: usize) {
+        for character in self.source[self.offset..offset].chars() {
+            if character == '\n' {
+                self.line += 1;
+                self.column = 1;
+            }
+            else {
+                self.column += 1;
+            }
+        }
+        self.offset = offset;
+    }
+
+    /*
+     * Move the cursor after the next occurence of text and return the location of this occurence.
+     * If the text is not found, because its source is written differently, the cursor is not moved.
+     */
+    fn find(&mut self, text: &str) -> (usize, usize) {
+        match self.source[self.offset..].find(text) {
+            Some(index) => {
+                let offset = self.offset + index;
+                self.advance(offset);
+                let location = self.location();
+                self.advance(offset + text.len());
+                location
+            },
+            None => self.location(),
+        }
+    }
+
+    fn location(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+}
+
+fn address(token: &TokenTree) -> usize {
+    token as *const TokenTree as usize
+}
+
+// The address following the last token, which is also the address of an empty slice at the end.
+fn end_address(tokens: &[TokenTree]) -> usize {
+    tokens.as_ptr() as usize + mem::size_of_val(tokens)
+}
+
+/// Get the token at `index` or an error mentioning what was `expected`.
+pub fn token_at<'a>(tokens: &'a [TokenTree], index: usize, expected: &str) -> Result<&'a TokenTree> {
+    tokens.get(index).ok_or_else(|| Error::unexpected_end(expected, tokens))
+}
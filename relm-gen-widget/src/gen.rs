@@ -24,11 +24,24 @@ use std::collections::HashMap;
 use quote::Tokens;
 use syn::{Generics, Ident, Path, Ty, parse_path};
 
-use parser::{GtkWidget, RelmWidget, Widget, RELM_WIDGET_CLONE_IDENT, RELM_WIDGET_SELF_IDENT};
+use computed::ComputedFn;
+use error::{Error, Result};
+use parser::{
+    FlowWidget,
+    GtkWidget,
+    RelmWidget,
+    Widget,
+    RELM_BUILDER_IDENT,
+    RELM_FLOW_IDENT,
+    RELM_WIDGET_CLONE_IDENT,
+    RELM_WIDGET_SELF_IDENT,
+    manifest_dir,
+};
 use parser::EventValue::{CurrentWidget, ForeignWidget};
 use parser::EventValueReturn::{CallReturn, Return, WithoutReturn};
-use parser::EitherWidget::{Gtk, Relm};
-use super::{Driver, get_generic_types};
+use parser::FlowWidget::{For, If, Match};
+use parser::EitherWidget::{Flow, Gtk, Relm};
+use super::{Driver, block_to_impl_item, get_generic_types};
 
 use self::WidgetType::*;
 
@@ -64,7 +77,7 @@ macro_rules! set_container {
                     else {
                         "#[container]".to_string()
                     };
-                panic!("Cannot use the {} attribute twice in the same widget", attribute);
+                return Err(Error::new(format!("cannot use the {} attribute twice in the same widget", attribute)));
             }
             $_self.relm_widgets.insert($widget_name.clone(), $widget_type.clone());
             $_self.container_names.insert(container_type.clone(), ($widget_name.clone(), $widget_type.clone()));
@@ -78,10 +91,11 @@ enum WidgetType {
     IsRelm,
 }
 
-pub fn gen(name: &Ident, typ: &Ty, widget: &Widget, driver: &mut Driver) -> (Tokens, HashMap<Ident, Path>, Tokens)
+pub fn gen(name: &Ident, typ: &Ty, widget: &Widget, driver: &mut Driver)
+    -> Result<(Tokens, HashMap<Ident, Path>, Tokens)>
 {
-    let mut generator = Generator::new(driver);
-    let widget_tokens = generator.widget(widget, None, IsGtk);
+    let mut generator = Generator::new(driver, typ);
+    let widget_tokens = generator.widget(widget, None, IsGtk)?;
     let driver = generator.driver.take().expect("driver");
     let idents: Vec<_> = driver.widgets.keys().collect();
     let root_widget_name = &driver.root_widget.as_ref().expect("root_widget is None");
@@ -94,12 +108,19 @@ pub fn gen(name: &Ident, typ: &Ty, widget: &Widget, driver: &mut Driver) -> (Tok
     let events = &generator.events;
     let phantom_field = gen_phantom_field(typ);
     let self_ident = Ident::new(RELM_WIDGET_SELF_IDENT);
+    let caches: Vec<_> = driver.computed.iter().map(ComputedFn::cache_ident).collect();
+    let caches1 = &caches;
+    let caches2 = &caches;
+    let computed_names: Vec<_> = driver.computed.iter().map(|function| &function.name).collect();
     let code = quote! {
+        #(let #caches1 = ::relm::Computed::new(Self::#computed_names(model));)*
+
         #widget_tokens
 
         let #self_ident: ::relm::ManuallyDrop<Self> = ::relm::ManuallyDrop::new(#name {
             #root_widget_name: #root_widget_name.clone(),
             #(#widget_names1: #widget_names2.clone(),)*
+            #(#caches1: #caches2.clone(),)*
             #phantom_field
         });
 
@@ -108,28 +129,50 @@ pub fn gen(name: &Ident, typ: &Ty, widget: &Widget, driver: &mut Driver) -> (Tok
         #name {
             #root_widget_name: #root_widget_name,
             #(#widget_names1: #widget_names2,)*
+            #(#caches1: #caches2,)*
             #phantom_field
         }
     };
-    let container_impl = gen_container_impl(&generator, widget, driver.generic_types.as_ref().expect("generic types"));
-    (code, generator.relm_widgets, container_impl)
+    let container_impl = gen_container_impl(&generator, widget, driver.generic_types.as_ref().expect("generic types"))?;
+    Ok((code, generator.relm_widgets, container_impl))
 }
 
 struct Generator<'a> {
     container_names: HashMap<Option<String>, (Ident, Path)>,
     driver: Option<&'a mut Driver>,
     events: Vec<Tokens>,
+    // Whether the widgets are generated in the builder of the children of an `if`, a `match` or a `for`.
+    in_flow: bool,
     relm_widgets: HashMap<Ident, Path>,
+    typ: Option<Ty>,
     widget_names: Vec<Ident>,
 }
 
 impl<'a> Generator<'a> {
-    fn new(driver: &'a mut Driver) -> Self {
+    fn new(driver: &'a mut Driver, typ: &Ty) -> Self {
         Generator {
             container_names: HashMap::new(),
             driver: Some(driver),
             events: vec![],
+            in_flow: false,
+            relm_widgets: HashMap::new(),
+            typ: Some(typ.clone()),
+            widget_names: vec![],
+        }
+    }
+
+    /*
+     * The widgets created in a flow are not saved in the struct: the events are connected in the
+     * builder and the components are kept by the FlowChildren.
+     */
+    fn new_in_flow() -> Self {
+        Generator {
+            container_names: HashMap::new(),
+            driver: None,
+            events: vec![],
+            in_flow: true,
             relm_widgets: HashMap::new(),
+            typ: None,
             widget_names: vec![],
         }
     }
@@ -204,7 +247,57 @@ impl<'a> Generator<'a> {
         }
     }
 
-    fn collect_events(&mut self, widget: &Widget, gtk_widget: &GtkWidget) {
+    /*
+     * A bound property sets the model field in a method generated in the helper impl, so that the
+     * widgets depending on this field are updated too.
+     * The model is already borrowed when the property is changed from update(): the value is then
+     * set by the event stream once the model is released. The field is only set when its value
+     * changed, to avoid a feedback loop with the property set from the model.
+     */
+    fn collect_bindings(&mut self, widget: &Widget, gtk_widget: &GtkWidget) -> Result<()> {
+        if gtk_widget.bindings.is_empty() {
+            return Ok(());
+        }
+        if self.in_flow {
+            return Err(Error::new("the properties of the widgets in an `if`, a `match` or a `for` cannot be bound with `<=>`"));
+        }
+        let widget_name = &widget.name;
+        let clone_ident = Ident::new(RELM_WIDGET_CLONE_IDENT);
+        let self_ident = Ident::new(RELM_WIDGET_SELF_IDENT);
+        for (property, target) in &gtk_widget.bindings {
+            let (signal, getter, value_type) = gen_binding_signal_getter(&widget.typ, property)?;
+            let method = Ident::new(format!("__relm_bind_{}_{}", widget_name, property));
+            let driver = self.driver.as_mut().expect("driver");
+            driver.binding_methods.push(block_to_impl_item(quote! {
+                fn #method(&self, model: &mut <Self as ::relm::Widget>::Model, value: #value_type) {
+                    if #target != value {
+                        #target = value;
+                    }
+                }
+            })?);
+            self.events.push(quote! {
+                {
+                    let #clone_ident = ::std::rc::Rc::new(::relm::ManuallyDrop::new(#self_ident.clone()));
+                    let model = relm.model().clone();
+                    let stream = relm.stream().clone();
+                    #self_ident.#widget_name.#signal(move |_| {
+                        let value = #clone_ident.#widget_name.#getter;
+                        match model.try_borrow_mut() {
+                            Ok(mut model) => #clone_ident.#method(&mut *model, value),
+                            Err(_) => {
+                                let #clone_ident = #clone_ident.clone();
+                                let model = model.clone();
+                                stream.exec(move || #clone_ident.#method(&mut *model.borrow_mut(), value.clone()));
+                            },
+                        }
+                    });
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn collect_events(&mut self, widget: &Widget, gtk_widget: &GtkWidget, save: bool) -> Result<()> {
         let widget_name = &widget.name;
         for (name, event) in &gtk_widget.events {
             let event_ident = Ident::new(format!("connect_{}", name));
@@ -222,7 +315,7 @@ impl<'a> Generator<'a> {
             let clone_ident = Ident::new(RELM_WIDGET_CLONE_IDENT);
             let self_ident = Ident::new(RELM_WIDGET_SELF_IDENT);
             let (self_ident, clone) =
-                if gtk_widget.save {
+                if save {
                     (quote! {
                         #self_ident .
                     }, quote! {
@@ -248,7 +341,9 @@ impl<'a> Generator<'a> {
                         #clone
                         connect!(relm, #self_ident #widget_name, #event_ident(#(#event_params),*) (#event_value, #return_value));
                     },
-                    ForeignWidget(_, Return(_, _)) | ForeignWidget(_, CallReturn(_)) => unreachable!(),
+                    ForeignWidget(_, Return(_, _)) | ForeignWidget(_, CallReturn(_)) =>
+                        return Err(Error::new(format!(
+                            "the event `{}` cannot return a value when sending a message to another widget", name))),
                     CurrentWidget(CallReturn(ref func)) => quote! {
                         #clone
                         connect!(relm, #self_ident #widget_name, #event_ident(#(#event_params),*) #event_model_ident #func);
@@ -257,9 +352,10 @@ impl<'a> Generator<'a> {
                 };
             self.events.push(connect);
         }
+        Ok(())
     }
 
-    fn collect_relm_events(&mut self, widget: &Widget, relm_widget: &RelmWidget) {
+    fn collect_relm_events(&mut self, widget: &Widget, relm_widget: &RelmWidget) -> Result<()> {
         let widget_name = &widget.name;
         for (name, widget_events) in &relm_widget.events {
             let event_ident = Ident::new(name.as_ref());
@@ -285,50 +381,129 @@ impl<'a> Generator<'a> {
                             connect!(#widget_name@#event_ident #params, #foreign_widget_name, #event_value);
                         },
                         CurrentWidget(Return(_, _)) | CurrentWidget(CallReturn(_)) | ForeignWidget(_, Return(_, _)) |
-                            ForeignWidget(_, CallReturn(_)) => unreachable!(),
+                            ForeignWidget(_, CallReturn(_)) =>
+                            return Err(Error::new(format!(
+                                "the event `{}` of the relm widget {} cannot return a value", name, widget_name))),
                     };
                 self.events.push(connect);
             }
         }
+        Ok(())
+    }
+
+    /*
+     * Generate the FlowChildren of an `if`, a `match` or a `for`, which is saved in the struct to be
+     * rebuilt when the model changes.
+     * In a flow, the nested flows are generated as normal control flow since they are rebuilt
+     * with their parent.
+     */
+    fn flow_widget(&mut self, widget: &Widget, flow: &FlowWidget, parent: &Ident, previous: Option<&Widget>)
+        -> Result<Tokens>
+    {
+        if self.in_flow {
+            return gen_flow_branches(flow, parent, false);
+        }
+        let flow_name = &widget.name;
+        self.widget_names.push(flow_name.clone());
+        let typ = self.typ.as_ref().expect("widget type");
+        let flow_type = quote! {
+            ::relm::FlowChildren< <#typ as ::relm::Widget>::Model >
+        };
+        self.relm_widgets.insert(flow_name.clone(),
+            parse_path(flow_type.as_str()).expect("flow type should be a valid path"));
+        let set_previous =
+            match previous {
+                Some(&Widget { name: ref previous_name, widget: Flow(_), .. }) => quote! {
+                    #flow_name.set_previous_flow(&#previous_name);
+                },
+                Some(&Widget { name: ref previous_name, widget: Gtk(_), .. }) => quote! {
+                    #flow_name.set_previous_widget(&#previous_name);
+                },
+                Some(&Widget { name: ref previous_name, widget: Relm(_), .. }) => quote! {
+                    #flow_name.set_previous_widget(#previous_name.widget().root());
+                },
+                None => quote! {
+                },
+            };
+        let body = gen_flow_branches(flow, parent, true)?;
+        let flow_ident = Ident::new(RELM_FLOW_IDENT);
+        Ok(quote! {
+            let #flow_name: ::relm::FlowChildren<Self::Model> = ::relm::FlowChildren::new(&#parent);
+            #set_previous
+            {
+                let relm = relm.clone();
+                let #parent = #parent.clone();
+                #flow_name.set_builder(move |#flow_ident, model| {
+                    #body
+                });
+            }
+            #flow_name.rebuild(model);
+        })
     }
 
     fn gtk_widget(&mut self, widget: &Widget, gtk_widget: &GtkWidget, parent: Option<&Ident>,
-        parent_widget_type: WidgetType) -> Tokens
+        parent_widget_type: WidgetType) -> Result<Tokens>
     {
         let struct_name = &widget.typ;
         let widget_name = &widget.name;
         set_container!(self, widget, widget_name, struct_name);
         self.widget_names.push(widget_name.clone());
 
-        if gtk_widget.save {
+        let save = gtk_widget.save && !self.in_flow;
+        if save {
             self.relm_widgets.insert(widget_name.clone(), struct_name.clone());
         }
 
-        let construct_widget = gen_construct_widget(widget);
-        self.collect_events(widget, gtk_widget);
-
-        let children: Vec<_> = widget.children.iter()
-            .map(|child| self.widget(child, Some(widget_name), IsGtk))
-            .collect();
+        let create_builder = gen_create_builder(gtk_widget);
+        let construct_widget = gen_construct_widget(widget, gtk_widget);
+        self.collect_events(widget, gtk_widget, save)?;
+        self.collect_bindings(widget, gtk_widget)?;
+
+        let mut children = vec![];
+        let mut previous = None;
+        for child in &widget.children {
+            let child_tokens =
+                match child.widget {
+                    Flow(ref flow) => {
+                        check_flow_container(&widget.typ)?;
+                        self.flow_widget(child, flow, widget_name, previous)?
+                    },
+                    Gtk(_) | Relm(_) => self.widget(child, Some(widget_name), IsGtk)?,
+                };
+            children.push(child_tokens);
+            previous = Some(child);
+        }
 
-        let add_child_or_show_all = self.add_child_or_show_all(widget, parent, parent_widget_type);
+        let mut add_child_or_show_all = self.add_child_or_show_all(widget, parent, parent_widget_type);
+        let mut show = quote! {
+            #widget_name.show();
+        };
+        // The objects of a glade file are already in their container and their visibility is
+        // specified in the file: only the root is shown.
+        if gtk_widget.builder_id.is_some() && parent.is_some() {
+            add_child_or_show_all = quote! {
+            };
+            show = quote! {
+            };
+        }
         let ident = quote! { #widget_name };
         let (properties, visible_properties) = gen_set_prop_calls!(widget, ident);
         let child_properties = gen_set_child_prop_calls(widget, parent, parent_widget_type, IsGtk);
 
-        quote! {
+        Ok(quote! {
+            #create_builder
             let #widget_name: #struct_name = #construct_widget;
             #(#properties)*
             #(#children)*
             #add_child_or_show_all
-            #widget_name.show();
+            #show
             #(#visible_properties)*
             #(#child_properties)*
-        }
+        })
     }
 
     fn relm_widget(&mut self, widget: &Widget, relm_widget: &RelmWidget, parent: Option<&Ident>,
-        parent_widget_type: WidgetType) -> Tokens
+        parent_widget_type: WidgetType) -> Result<Tokens>
     {
         self.widget_names.push(widget.name.clone());
         let widget_name = &widget.name;
@@ -337,11 +512,11 @@ impl<'a> Generator<'a> {
         let relm_component_type = gen_relm_component_type(widget_type_ident);
         self.relm_widgets.insert(widget.name.clone(), relm_component_type);
 
-        self.collect_relm_events(widget, relm_widget);
+        self.collect_relm_events(widget, relm_widget)?;
 
-        let children: Vec<_> = widget.children.iter()
+        let children = widget.children.iter()
             .map(|child| self.widget(child, Some(widget_name), IsRelm))
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
         let ident = quote! { #widget_name.widget() };
         let (properties, visible_properties) = gen_set_prop_calls!(widget, ident);
 
@@ -349,29 +524,159 @@ impl<'a> Generator<'a> {
             parent, parent_widget_type, widget_name, widget_type_ident, &widget.init_parameters);
         let child_properties = gen_set_child_prop_calls(widget, parent, parent_widget_type, IsRelm);
 
-        quote! {
+        Ok(quote! {
             #add_or_create_widget
             #(#properties)*
             #(#visible_properties)*
             #(#children)*
             #(#child_properties)*
-        }
+        })
     }
 
-    fn widget(&mut self, widget: &Widget, parent: Option<&Ident>, parent_widget_type: WidgetType) -> Tokens {
+    fn widget(&mut self, widget: &Widget, parent: Option<&Ident>, parent_widget_type: WidgetType) -> Result<Tokens> {
         match widget.widget {
+            Flow(ref flow) => {
+                let parent = parent.ok_or_else(|| Error::new("the root of the view cannot be an `if`, a `match` or a `for`"))?;
+                self.flow_widget(widget, flow, parent, None)
+            },
             Gtk(ref gtk_widget) => self.gtk_widget(widget, gtk_widget, parent, parent_widget_type),
             Relm(ref relm_widget) => self.relm_widget(widget, relm_widget, parent, parent_widget_type),
         }
     }
 }
 
-fn gen_construct_widget(widget: &Widget) -> Tokens {
+/*
+ * The children of a flow are created again when it is rebuilt and placed after their previous sibling
+ * with gtk::Box::reorder_child(), so a flow cannot be used in another container.
+ */
+fn check_flow_container(container_type: &Path) -> Result<()> {
+    let is_box = container_type.segments.last().map(|segment| segment.ident == "Box").unwrap_or(false);
+    if is_box {
+        Ok(())
+    }
+    else {
+        let name: Vec<_> = container_type.segments.iter()
+            .map(|segment| segment.ident.as_ref())
+            .collect();
+        Err(Error::new(format!("an `if`, a `match` or a `for` can only be used in a gtk::Box, not in a {}",
+            name.join("::"))))
+    }
+}
+
+/*
+ * Generate the control flow creating the children of a flow.
+ * The children added to the container of the flow are registered when `register` is true.
+ */
+fn gen_flow_branches(flow: &FlowWidget, parent: &Ident, register: bool) -> Result<Tokens> {
+    match *flow {
+        For(ref pattern, ref iterator, ref children) => {
+            let block = gen_flow_block(children, parent, register)?;
+            Ok(quote! {
+                for #pattern in #iterator {
+                    #block
+                }
+            })
+        },
+        If(ref condition, ref then, ref otherwise) => {
+            let then = gen_flow_block(then, parent, register)?;
+            let otherwise = gen_flow_block(otherwise, parent, register)?;
+            Ok(quote! {
+                if #condition {
+                    #then
+                }
+                else {
+                    #otherwise
+                }
+            })
+        },
+        Match(ref expr, ref arms) => {
+            let mut patterns = vec![];
+            let mut blocks = vec![];
+            for (pattern, children) in arms {
+                patterns.push(pattern);
+                blocks.push(gen_flow_block(children, parent, register)?);
+            }
+            Ok(quote! {
+                match #expr {
+                    #(#patterns => { #blocks })*
+                }
+            })
+        },
+    }
+}
+
+fn gen_flow_block(children: &[Widget], parent: &Ident, register: bool) -> Result<Tokens> {
+    let flow_ident = Ident::new(RELM_FLOW_IDENT);
+    let mut generator = Generator::new_in_flow();
+    let mut stmts = vec![];
+    for child in children {
+        match child.widget {
+            Flow(ref flow) => stmts.push(gen_flow_branches(flow, parent, register)?),
+            Gtk(_) | Relm(_) => {
+                stmts.push(generator.widget(child, Some(parent), IsGtk)?);
+                if register {
+                    let child_name = &child.name;
+                    let root =
+                        if let Relm(_) = child.widget {
+                            quote! {
+                                #child_name.widget().root()
+                            }
+                        }
+                        else {
+                            quote! {
+                                &#child_name
+                            }
+                        };
+                    stmts.push(quote! {
+                        #flow_ident.add_widget(#root);
+                    });
+                }
+            },
+        }
+    }
+    let events = &generator.events;
+    let keep_components: Vec<_> = generator.relm_widgets.keys()
+        .map(|component| quote! {
+            #flow_ident.keep(#component);
+        })
+        .collect();
+    Ok(quote! {
+        #(#stmts)*
+        #(#events)*
+        #(#keep_components)*
+    })
+}
+
+/*
+ * Get the signal emitted when the bound property changes, the getter of this property and the
+ * type of its value.
+ */
+fn gen_binding_signal_getter(widget_type: &Path, property: &str) -> Result<(Ident, Tokens, Tokens)> {
+    let is_switch = widget_type.segments.last().map(|segment| segment.ident == "Switch").unwrap_or(false);
+    match property {
+        "active" if is_switch =>
+            Ok((Ident::new("connect_property_active_notify"), quote! { get_active() }, quote! { bool })),
+        "active" => Ok((Ident::new("connect_toggled"), quote! { get_active() }, quote! { bool })),
+        "text" => Ok((Ident::new("connect_changed"), quote! { get_text().unwrap_or_else(String::new) },
+            quote! { String })),
+        "value" => Ok((Ident::new("connect_value_changed"), quote! { get_value() }, quote! { f64 })),
+        _ => Err(Error::new(format!("the property `{}` cannot be bound with `<=>`", property))),
+    }
+}
+
+fn gen_construct_widget(widget: &Widget, gtk_widget: &GtkWidget) -> Tokens {
     let struct_name = &widget.typ;
 
     let params = &widget.init_parameters;
 
-    if widget.init_parameters.is_empty() {
+    if let Some(ref id) = gtk_widget.builder_id {
+        let builder_ident = Ident::new(RELM_BUILDER_IDENT);
+        let error_message = format!("the object `{}` of the glade file should have the type of its field", id);
+        quote! {
+            ::gtk::Builder::get_object(&#builder_ident, #id).expect(#error_message)
+        }
+    }
+    else if widget.init_parameters.is_empty() {
         quote! {
             unsafe {
                 use gtk::StaticType;
@@ -389,19 +694,46 @@ fn gen_construct_widget(widget: &Widget) -> Tokens {
     }
 }
 
-fn gen_widget_type(widget: &Widget) -> Tokens {
+fn gen_create_builder(gtk_widget: &GtkWidget) -> Tokens {
+    if let Some(ref glade_file) = gtk_widget.glade_file {
+        let builder_ident = Ident::new(RELM_BUILDER_IDENT);
+        // The file is included, so that the crate is rebuilt when it changes.
+        let interface =
+            if manifest_dir(glade_file).is_some() {
+                let glade_file = format!("/{}", glade_file);
+                quote! {
+                    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), #glade_file))
+                }
+            }
+            else {
+                quote! {
+                    include_str!(#glade_file)
+                }
+            };
+        quote! {
+            let #builder_ident = ::gtk::Builder::new_from_string(#interface);
+        }
+    }
+    else {
+        quote! {
+        }
+    }
+}
+
+fn gen_widget_type(widget: &Widget) -> Result<Tokens> {
     match widget.widget {
+        Flow(_) => Err(Error::new("the root of the view cannot be an `if`, a `match` or a `for`")),
         Gtk(ref gtk_widget) => {
             let ident = gtk_widget.relm_name.as_ref().unwrap();
-            quote! {
+            Ok(quote! {
                 #ident
-            }
+            })
         },
         Relm(_) => {
             let path = &widget.typ;
-            quote! {
+            Ok(quote! {
                 #path
-            }
+            })
         },
     }
 }
@@ -473,14 +805,14 @@ fn gen_add_widget_method(container_names: &HashMap<Option<String>, (Ident, Path)
     }
 }
 
-fn gen_container_impl(generator: &Generator, widget: &Widget, generic_types: &Generics) -> Tokens {
-    let widget_type = gen_widget_type(widget);
+fn gen_container_impl(generator: &Generator, widget: &Widget, generic_types: &Generics) -> Result<Tokens> {
+    let widget_type = gen_widget_type(widget)?;
     if generator.container_names.is_empty() {
-        quote! {
-        }
+        Ok(quote! {
+        })
     }
     else if !generator.container_names.contains_key(&None) {
-        panic!("Use of #[container=\"name\"] attribute without the default #[container].");
+        Err(Error::new("use of #[container=\"name\"] attribute without the default #[container]"))
     }
     else {
         let mut container_type = None;
@@ -493,7 +825,7 @@ fn gen_container_impl(generator: &Generator, widget: &Widget, generic_types: &Ge
         let &(ref name, _) = generator.container_names.get(&None).expect("default container");
         let add_widget_method = gen_add_widget_method(&generator.container_names);
 
-        quote! {
+        Ok(quote! {
             impl #generic_types ::relm::Container for #widget_type {
                 type Container = #typ;
 
@@ -503,7 +835,7 @@ fn gen_container_impl(generator: &Generator, widget: &Widget, generic_types: &Ge
 
                 #add_widget_method
             }
-        }
+        })
     }
 }
 
@@ -513,7 +845,7 @@ fn gen_model_param(init_parameters: &[Tokens]) -> Tokens {
     }
 }
 
-fn gen_phantom_field(typ: &Ty) -> Tokens {
+pub fn gen_phantom_field(typ: &Ty) -> Tokens {
     if let Some(types) = get_generic_types(typ) {
         let fields = types.iter().map(|typ| {
             let name = Ident::new(format!("__relm_phantom_marker_{}", typ.as_ref().to_lowercase()));
@@ -573,3 +905,100 @@ fn gen_set_child_prop_calls(widget: &Widget, parent: Option<&Ident>, parent_widg
     }
     child_properties
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    use quote::Tokens;
+
+    use gen_widget;
+
+    fn gen_view(view: Tokens) -> String {
+        gen_widget(quote! {
+            impl Widget for Win {
+                fn model() -> Model {
+                    Model {
+                        visible: true,
+                    }
+                }
+
+                fn update(&mut self, event: Msg, model: &mut Model) {
+                }
+
+                view! {
+                    #view
+                }
+            }
+        }).to_string()
+    }
+
+    #[test]
+    fn flow_in_box() {
+        let code = gen_view(quote! {
+            gtk::Box {
+                if model.visible {
+                    gtk::Label {
+                    }
+                }
+            }
+        });
+        assert!(!code.contains("compile_error"), "{}", code);
+        assert!(code.contains("FlowChildren"), "{}", code);
+    }
+
+    #[test]
+    fn flow_in_other_container() {
+        let code = gen_view(quote! {
+            gtk::Window {
+                if model.visible {
+                    gtk::Label {
+                    }
+                }
+            }
+        });
+        assert!(code.contains("an `if`, a `match` or a `for` can only be used in a gtk::Box, not in a gtk::Window"),
+            "{}", code);
+    }
+
+    #[test]
+    fn binding_while_model_borrowed() {
+        let code = gen_view(quote! {
+            gtk::Box {
+                gtk::Entry {
+                    text: <=> model.query,
+                },
+            }
+        });
+        assert!(code.contains("if model . query != value { model . query = value ;"), "{}", code);
+        // The value is not dropped when the model is borrowed.
+        assert!(code.contains("Err ( _ ) =>"), "{}", code);
+        assert!(code.contains("stream . exec ( move | |"), "{}", code);
+    }
+
+    #[test]
+    fn included_glade_file() {
+        let dir = env::temp_dir();
+        let mut file = File::create(dir.join("relm-gen-widget-included.glade")).expect("create glade file");
+        file.write_all(br#"<interface><object class="GtkWindow" id="window"/></interface>"#)
+            .expect("write glade file");
+
+        // The relative files are in the directory of the crate.
+        env::set_var("CARGO_MANIFEST_DIR", &dir);
+        let code = gen_view(quote! {
+            glade "relm-gen-widget-included.glade"
+        });
+        assert!(code.contains(r#"include_str ! ( concat ! ( env ! ( "CARGO_MANIFEST_DIR" ) , "/relm-gen-widget-included.glade" ) )"#),
+            "{}", code);
+        assert!(!code.contains("GtkWindow"), "{}", code);
+
+        let path = dir.join("relm-gen-widget-included.glade");
+        let path = path.to_str().expect("glade file path");
+        let code = gen_view(quote! {
+            glade #path
+        });
+        assert!(code.contains(&format!("include_str ! ( {:?} )", path)), "{}", code);
+    }
+}
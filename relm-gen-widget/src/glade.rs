@@ -0,0 +1,107 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Extraction of the objects of a GtkBuilder (glade) file.
+
+use xml::reader::{EventReader, XmlEvent};
+
+use error::{Error, Result};
+
+pub struct GladeObject {
+    pub class: String,
+    pub id: String,
+    pub toplevel: bool,
+}
+
+/*
+ * Get the objects having an id, in the order of the file.
+ */
+pub fn parse_objects(content: &str, file_name: &str) -> Result<Vec<GladeObject>> {
+    let mut objects = vec![];
+    let mut depth = 0;
+    for event in EventReader::from_str(content) {
+        let event = event.map_err(|error| Error::new(format!("cannot parse {}: {}", file_name, error)))?;
+        match event {
+            XmlEvent::StartElement { ref name, ref attributes, .. } if name.local_name == "object" => {
+                let attribute = |attribute_name| attributes.iter()
+                    .find(|attribute| attribute.name.local_name == attribute_name)
+                    .map(|attribute| attribute.value.clone());
+                if let Some(id) = attribute("id") {
+                    let class = attribute("class")
+                        .ok_or_else(|| Error::new(format!("the object `{}` has no class in {}", id, file_name)))?;
+                    objects.push(GladeObject {
+                        class,
+                        id,
+                        toplevel: depth == 0,
+                    });
+                }
+                depth += 1;
+            },
+            XmlEvent::EndElement { ref name } if name.local_name == "object" => depth -= 1,
+            _ => (),
+        }
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_objects;
+
+    fn parse_error(content: &str) -> String {
+        parse_objects(content, "test.glade").err().expect("the glade file should not parse").to_string()
+    }
+
+    #[test]
+    fn objects() {
+        let objects = parse_objects(r#"<interface>
+            <object class="GtkWindow" id="window">
+                <child>
+                    <object class="GtkBox">
+                        <child>
+                            <object class="GtkLabel" id="label"/>
+                        </child>
+                    </object>
+                </child>
+            </object>
+            <object class="GtkAdjustment" id="adjustment"/>
+        </interface>"#, "test.glade").expect("glade objects");
+        let objects: Vec<_> = objects.iter()
+            .map(|object| (object.id.as_str(), object.class.as_str(), object.toplevel))
+            .collect();
+        assert_eq!(objects, vec![
+            ("window", "GtkWindow", true),
+            ("label", "GtkLabel", false),
+            ("adjustment", "GtkAdjustment", true),
+        ]);
+    }
+
+    #[test]
+    fn missing_class() {
+        assert_eq!(parse_error(r#"<interface><object id="window"/></interface>"#),
+            "the object `window` has no class in test.glade");
+    }
+
+    #[test]
+    fn invalid_xml() {
+        assert!(parse_error("<interface><object></interface>").starts_with("cannot parse test.glade: "));
+    }
+}
@@ -21,36 +21,45 @@
 
 /*
  * TODO: automatically add the model() method with a () return type when it is not found?
- * FIXME: Doing model.text.push_str() will not cause a set_text() to be added.
- * TODO: think about conditions and loops (widget-list).
  */
 
+#![recursion_limit="128"]
+
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate quote;
 extern crate syn;
+extern crate xml;
 
 mod adder;
+mod computed;
+mod error;
 mod gen;
+mod glade;
 mod parser;
+mod vdom;
 mod walker;
 
 use std::collections::{HashMap, HashSet};
 
-use adder::{Adder, Property};
-use gen::gen;
-use parser::EitherWidget::{Gtk, Relm};
-use parser::{Widget, parse};
+use adder::{Adder, Property, PropertyKind};
+use computed::{ComputedFn, computed_calls, replace_computed_calls};
+use error::{Error, Result};
+use gen::{gen, gen_phantom_field};
+use parser::EitherWidget::{Flow, Gtk, Relm};
+use parser::{FlowWidget, Widget, parse};
 use quote::Tokens;
 use syn::{
     AngleBracketedParameterData,
+    Attribute,
     Delimited,
     FunctionRetTy,
     Generics,
     Ident,
     ImplItem,
     Mac,
+    MetaItem,
     MethodSig,
     Path,
     PathSegment,
@@ -58,22 +67,27 @@ use syn::{
     parse_expr,
     parse_item,
 };
-use syn::FnArg::Captured;
-use syn::fold::Folder;
+use syn::FnArg::{Captured, SelfRef, SelfValue};
+use syn::Lit::Str;
 use syn::ImplItemKind::{Const, Macro, Method, Type};
 use syn::ItemKind::Impl;
-use syn::Pat::Wild;
+use syn::Pat::{self, Wild};
 use syn::PathParameters::AngleBracketed;
 use syn::Ty::{self, Tup};
 use syn::visit::Visitor;
+use vdom::gen_description;
 use walker::ModelVariableVisitor;
 
 type PropertyModelMap = HashMap<Ident, HashSet<Property>>;
 
 #[derive(Debug)]
 pub struct Driver {
+    binding_methods: Vec<ImplItem>,
+    computed: Vec<ComputedFn>,
     data_method: Option<ImplItem>,
+    describe_method: Option<ImplItem>,
     generic_types: Option<Generics>,
+    handlers: Vec<Handler>,
     model_type: Option<ImplItem>,
     model_param_type: Option<ImplItem>,
     msg_type: Option<ImplItem>,
@@ -84,6 +98,7 @@ pub struct Driver {
     root_widget_expr: Option<Tokens>,
     root_widget_type: Option<Tokens>,
     update_method: Option<ImplItem>,
+    vdom: bool,
     view_macro: Option<Mac>,
     widget_model_type: Option<Ty>,
     widget_msg_type: Option<Ty>,
@@ -91,6 +106,36 @@ pub struct Driver {
     widgets: HashMap<Ident, Tokens>, // Map widget ident to widget type.
 }
 
+// Method handling the messages matching a pattern, specified with #[update(Pattern)].
+#[derive(Debug)]
+struct Handler {
+    args: Vec<Tokens>,
+    name: Ident,
+    pattern: Tokens,
+}
+
+/// Options given to the `#[widget]` attribute.
+#[derive(Default)]
+pub struct WidgetOptions {
+    /// Re-evaluate the view after every update and patch the widgets which changed, with `#[widget(vdom)]`.
+    pub vdom: bool,
+}
+
+impl WidgetOptions {
+    /// Parse the options from the attribute arguments, like `(vdom)`.
+    pub fn parse(attributes: &str) -> Result<Self> {
+        let mut options = WidgetOptions::default();
+        let attributes = attributes.trim().trim_start_matches('(').trim_end_matches(')');
+        for option in attributes.split(',').map(str::trim).filter(|option| !option.is_empty()) {
+            match option {
+                "vdom" => options.vdom = true,
+                _ => return Err(Error::new(format!("unknown option `{}` in #[widget] attribute", option))),
+            }
+        }
+        Ok(options)
+    }
+}
+
 struct View {
     container_impl: Tokens,
     item: ImplItem,
@@ -100,10 +145,14 @@ struct View {
 }
 
 impl Driver {
-    fn new() -> Self {
+    fn new(options: WidgetOptions) -> Self {
         Driver {
+            binding_methods: vec![],
+            computed: vec![],
             data_method: None,
+            describe_method: None,
             generic_types: None,
+            handlers: vec![],
             root_method: None,
             root_type: None,
             model_type: None,
@@ -114,6 +163,7 @@ impl Driver {
             root_widget_expr: None,
             root_widget_type: None,
             update_method: None,
+            vdom: options.vdom,
             view_macro: None,
             widget_model_type: None,
             widget_msg_type: None,
@@ -145,10 +195,15 @@ impl Driver {
     }
 
     fn create_struct(&self, typ: &Ty, relm_widgets: &HashMap<Ident, Path>) -> Tokens {
+        if self.vdom {
+            return self.create_vdom_struct(typ);
+        }
         let widgets = self.widgets.iter().filter(|&(ident, _)| !relm_widgets.contains_key(ident));
         let (idents, types): (Vec<_>, Vec<_>) = widgets.unzip();
         let relm_idents = relm_widgets.keys();
         let relm_types = relm_widgets.values();
+        let caches: Vec<_> = self.computed.iter().map(ComputedFn::cache_ident).collect();
+        let computed_types: Vec<_> = self.computed.iter().map(|function| &function.typ).collect();
         let phantom_field = get_phantom_field(typ);
         quote! {
             #[allow(dead_code)]
@@ -156,38 +211,91 @@ impl Driver {
             pub struct #typ {
                 #(#idents: #types,)*
                 #(#relm_idents: #relm_types,)*
+                #(#caches: ::relm::Computed<#computed_types>,)*
+                #phantom_field
+            }
+        }
+    }
+
+    /*
+     * In vdom mode, the widgets can be recreated at every update: only the root is saved.
+     */
+    fn create_vdom_struct(&self, typ: &Ty) -> Tokens {
+        let root_widget = self.root_widget.as_ref().expect("root widget");
+        let root_widget_type = &self.widgets[root_widget];
+        let phantom_field = get_phantom_field(typ);
+        quote! {
+            #[allow(dead_code)]
+            #[derive(ManualClone)]
+            pub struct #typ {
+                #root_widget: #root_widget_type,
+                __relm_relm: ::relm::RemoteRelm<#typ>,
+                __relm_vdom: ::relm::VDom,
                 #phantom_field
             }
         }
     }
 
-    fn gen_widget(&mut self, input: Tokens) -> Tokens {
+    fn gen_widget(&mut self, input: Tokens) -> Result<Tokens> {
         let source = input.to_string();
-        let mut ast = parse_item(&source).expect("parse_item() in gen_widget()");
+        let mut ast = parse_item(&source).map_err(Error::new)?;
         if let Impl(unsafety, polarity, generics, path, typ, items) = ast.node {
             self.generic_types = Some(generics.clone());
-            let name = get_name(&typ);
+            let name = get_name(&typ)?;
             let mut new_items = vec![];
+            // Items which are not part of the Widget trait, moved to an inherent impl.
+            let mut helper_items = vec![];
+            let mut has_on_add = false;
+            let mut has_type_name = false;
             for item in items {
                 let mut i = item.clone();
                 match item.node {
-                    Const(_, _) => panic!("Unexpected const item"),
+                    Const(_, _) => helper_items.push(i),
                     Macro(mac) => self.view_macro = Some(mac),
-                    Method(sig, _) => {
+                    Method(sig, block) => {
+                        if take_computed_attribute(&mut i.attrs) {
+                            self.computed.push(ComputedFn::new(i.ident.clone(), &sig, &block)?);
+                            helper_items.push(i);
+                            continue;
+                        }
+                        if let Some(pattern) = take_update_attribute(&mut i.attrs)? {
+                            self.handlers.push(Handler {
+                                args: get_handler_args(&sig)?,
+                                name: i.ident.clone(),
+                                pattern,
+                            });
+                            helper_items.push(i);
+                            continue;
+                        }
                         match item.ident.to_string().as_ref() {
                             "parent_id" => self.data_method = Some(i),
                             "root" => self.root_method = Some(i),
                             "model" => {
-                                self.widget_model_type = Some(get_return_type(sig));
-                                add_model_param(&mut i, &mut self.model_param_type);
+                                self.widget_model_type = Some(get_return_type(sig)?);
+                                add_model_param(&mut i, &mut self.model_param_type)?;
+                                new_items.push(i);
+                            },
+                            "on_add" => {
+                                has_on_add = true;
+                                new_items.push(i);
+                            },
+                            "type_name" => {
+                                has_type_name = true;
                                 new_items.push(i);
                             },
-                            "init_view" | "subscriptions" | "update_command" => new_items.push(i),
-                            "update" => {
-                                self.widget_msg_type = Some(get_second_param_type(&sig));
+                            "init_view" | "on_destroy" | "on_remove" | "run" | "subscriptions" | "update_command" =>
+                                new_items.push(i),
+                            "update" | "update_with_commands" => {
+                                self.widget_msg_type = Some(get_second_param_type(&sig)?);
+                                // An update() method taking the commands is the update_with_commands()
+                                // method of the Widget trait.
+                                if sig.decl.inputs.len() == 4 {
+                                    i.ident = Ident::new("update_with_commands");
+                                }
                                 self.update_method = Some(i)
                             },
-                            method_name => panic!("Unexpected method {}", method_name),
+                            "view" => return Err(Error::new("use the view! macro instead of the view method")),
+                            _ => helper_items.push(i),
                         }
                     },
                     Type(_) => {
@@ -196,166 +304,329 @@ impl Driver {
                             "Model" => self.model_type = Some(i),
                             "ModelParam" => self.model_param_type = Some(i),
                             "Msg" => self.msg_type = Some(i),
-                            _ => panic!("Unexpected type item {:?}", item.ident),
+                            _ => return Err(Error::new(format!("unexpected type item {}", item.ident))),
                         }
                     },
                 }
             }
-            let view = self.get_view(&name, &typ);
-            if let Some(on_add) = gen_set_child_prop_calls(&view.widget) {
+            self.gen_handlers_update()?;
+            let view = self.get_view(&name, &typ)?;
+            if let Some(describe_method) = self.describe_method.take() {
+                helper_items.push(describe_method);
+            }
+            helper_items.append(&mut self.binding_methods);
+            if let Some(on_add) = gen_set_child_prop_calls(&view.widget)? {
+                if has_on_add {
+                    return Err(Error::new("the on_add method cannot be implemented when the root widget has child properties"));
+                }
                 new_items.push(on_add);
             }
             self.properties_model_map = Some(view.properties_model_map);
             new_items.push(view.item);
             self.widgets.insert(self.root_widget.clone().expect("root widget"),
             self.root_widget_type.clone().expect("root widget type"));
-            new_items.push(self.get_msg_type());
-            new_items.push(self.get_model_type());
-            new_items.push(self.get_model_param_type());
-            new_items.push(self.get_root_type());
-            if let Some(data_method) = self.get_data_method() {
+            new_items.push(self.get_msg_type()?);
+            new_items.push(self.get_model_type()?);
+            new_items.push(self.get_model_param_type()?);
+            new_items.push(self.get_root_type()?);
+            if let Some(data_method) = self.get_data_method()? {
                 new_items.push(data_method);
             }
-            new_items.push(self.get_update());
-            new_items.push(self.get_root());
+            new_items.push(self.get_update(&mut helper_items)?);
+            new_items.push(self.get_root()?);
+            if !has_type_name {
+                let type_name = name.to_string();
+                new_items.push(block_to_impl_item(quote! {
+                    fn type_name() -> &'static str {
+                        #type_name
+                    }
+                })?);
+            }
+            let helper_impl = self.get_helper_impl(&generics, &typ, helper_items);
             let widget_struct = self.create_struct(&typ, &view.relm_widgets);
             let item = Impl(unsafety, polarity, generics, path, typ, new_items);
             ast.node = item;
             let container_impl = view.container_impl;
-            quote! {
+            Ok(quote! {
                 #widget_struct
                 #ast
+                #helper_impl
                 #container_impl
+            })
+        }
+        else {
+            Err(Error::new("the #[widget] attribute must be used on an impl"))
+        }
+    }
+
+    /*
+     * Generate the update() method dispatching the messages to the #[update(Pattern)] handlers.
+     */
+    fn gen_handlers_update(&mut self) -> Result<()> {
+        if self.handlers.is_empty() {
+            return Ok(());
+        }
+        if self.update_method.is_some() {
+            return Err(Error::new("the update method cannot be implemented when using #[update] handlers"));
+        }
+        if self.msg_type.is_none() {
+            return Err(Error::new("the Msg type must be specified (type Msg = …;) when using #[update] handlers"));
+        }
+        let arms: Vec<_> = self.handlers.iter()
+            .map(|handler| {
+                let Handler { ref args, ref name, ref pattern } = *handler;
+                quote! {
+                    #pattern => self.#name(#(#args),*),
+                }
+            })
+            .collect();
+        // The messages without a handler are ignored.
+        self.update_method = Some(block_to_impl_item(quote! {
+            #[allow(unreachable_patterns, unused_variables)]
+            fn update(&mut self, event: Self::Msg, model: &mut Self::Model) {
+                match event {
+                    #(#arms)*
+                    _ => (),
+                }
             }
+        })?);
+        Ok(())
+    }
+
+    fn get_data_method(&mut self) -> Result<Option<ImplItem>> {
+        if let Some(data_method) = self.data_method.take() {
+            return Ok(Some(data_method));
+        }
+        if let Some(ref parent_id) = self.widget_parent_id {
+            Ok(Some(block_to_impl_item(quote! {
+                fn parent_id() -> Option<&'static str> {
+                    Some(#parent_id)
+                }
+            })?))
         }
         else {
-            panic!("Expected impl");
+            Ok(None)
         }
     }
 
-    fn get_data_method(&mut self) -> Option<ImplItem> {
-        self.data_method.take().or_else(|| {
-            if let Some(ref parent_id) = self.widget_parent_id {
-                Some(block_to_impl_item(quote! {
-                    fn parent_id() -> Option<&'static str> {
-                        Some(#parent_id)
+    /*
+     * The helper methods taking self get the same property updates as the update() method.
+     */
+    fn get_helper_impl(&self, generics: &Generics, typ: &Ty, helper_items: Vec<ImplItem>) -> Tokens {
+        if helper_items.is_empty() {
+            return quote! {
+            };
+        }
+        let mut adder = Adder::new(self.properties_model_map.as_ref().expect("properties model map"));
+        let helper_items: Vec<_> = helper_items.into_iter()
+            .map(|mut item| {
+                if let Method(ref sig, ref mut block) = item.node {
+                    if let Some(&SelfRef(..)) = sig.decl.inputs.first() {
+                        *block = adder.add_updates(block.clone());
                     }
-                }))
-            }
-            else {
-                None
+                }
+                item
+            })
+            .collect();
+        let where_clause = &generics.where_clause;
+        quote! {
+            impl #generics #typ #where_clause {
+                #(#helper_items)*
             }
-        })
+        }
     }
 
-    fn get_model_param_type(&mut self) -> ImplItem {
-        self.model_param_type.take().unwrap_or_else(|| {
-            block_to_impl_item(quote! {
-                type ModelParam = ();
-            })
+    fn get_model_param_type(&mut self) -> Result<ImplItem> {
+        if let Some(model_param_type) = self.model_param_type.take() {
+            return Ok(model_param_type);
+        }
+        block_to_impl_item(quote! {
+            type ModelParam = ();
         })
     }
 
-    fn get_model_type(&mut self) -> ImplItem {
-        self.model_type.take().unwrap_or_else(|| {
-            let widget_model_type = self.widget_model_type.take().expect("missing model method");
-            block_to_impl_item(quote! {
-                type Model = #widget_model_type;
-            })
+    fn get_model_type(&mut self) -> Result<ImplItem> {
+        if let Some(model_type) = self.model_type.take() {
+            return Ok(model_type);
+        }
+        let widget_model_type = self.widget_model_type.take()
+            .ok_or_else(|| Error::new("missing model method"))?;
+        block_to_impl_item(quote! {
+            type Model = #widget_model_type;
         })
     }
 
-    fn get_msg_type(&mut self) -> ImplItem {
-        self.msg_type.take().unwrap_or_else(|| {
-            let widget_msg_type = self.widget_msg_type.take().expect("missing update method");
-            block_to_impl_item(quote! {
-                type Msg = #widget_msg_type;
-            })
+    fn get_msg_type(&mut self) -> Result<ImplItem> {
+        if let Some(msg_type) = self.msg_type.take() {
+            return Ok(msg_type);
+        }
+        let widget_msg_type = self.widget_msg_type.take()
+            .ok_or_else(|| Error::new("missing update method"))?;
+        block_to_impl_item(quote! {
+            type Msg = #widget_msg_type;
         })
     }
 
-    fn get_root(&mut self) -> ImplItem {
-        self.root_method.take().unwrap_or_else(|| {
-            let root_widget_expr = self.root_widget_expr.take().expect("root widget expr");
-            block_to_impl_item(quote! {
-                fn root(&self) -> &Self::Root {
-                    &self.#root_widget_expr
-                }
-            })
+    fn get_root(&mut self) -> Result<ImplItem> {
+        if let Some(root_method) = self.root_method.take() {
+            return Ok(root_method);
+        }
+        let root_widget_expr = self.root_widget_expr.take().expect("root widget expr");
+        block_to_impl_item(quote! {
+            fn root(&self) -> &Self::Root {
+                &self.#root_widget_expr
+            }
         })
     }
 
-    fn get_root_type(&mut self) -> ImplItem {
-        self.root_type.take().unwrap_or_else(|| {
-            let root_widget_type = self.root_widget_type.take().expect("root widget type");
-            block_to_impl_item(quote! {
-                type Root = #root_widget_type;
-            })
+    fn get_root_type(&mut self) -> Result<ImplItem> {
+        if let Some(root_type) = self.root_type.take() {
+            return Ok(root_type);
+        }
+        let root_widget_type = self.root_widget_type.take().expect("root widget type");
+        block_to_impl_item(quote! {
+            type Root = #root_widget_type;
         })
     }
 
-    /*
-     * TODO: Create a control flow graph for each variable of the model.
-     * Add the set_property() calls in every leaf of every graphs.
-     */
-    fn get_update(&mut self) -> ImplItem {
-        let mut func = self.update_method.take().expect("update method");
+    fn get_update(&mut self, helper_items: &mut Vec<ImplItem>) -> Result<ImplItem> {
+        let mut func = self.update_method.take().ok_or_else(|| Error::new("missing update method"))?;
+        if self.vdom {
+            // The update method of the user is called before patching the widgets.
+            let update = gen_vdom_update(&func.ident)?;
+            func.ident = Ident::new("__relm_update");
+            helper_items.push(func);
+            return Ok(update);
+        }
         if let Method(_, ref mut block) = func.node {
             let mut adder = Adder::new(self.properties_model_map.as_ref().expect("update method"));
-            *block = adder.fold_block(block.clone());
+            *block = adder.add_updates(block.clone());
         }
         // TODO: consider gtk::main_quit() as return.
-        func
+        Ok(func)
     }
 
-    fn get_view(&mut self, name: &Ident, typ: &Ty) -> View {
+    fn get_view(&mut self, name: &Ident, typ: &Ty) -> Result<View> {
         {
-            let segments = &self.view_macro.as_ref().expect("view! macro missing").path.segments;
+            let view_macro = self.view_macro.as_ref().ok_or_else(|| Error::new("missing view! macro"))?;
+            let segments = &view_macro.path.segments;
             if segments.len() != 1 || segments[0].ident != "view" {
-                panic!("Unexpected macro item")
+                return Err(Error::new(format!("unexpected macro item {}!", segments.iter()
+                    .map(|segment| segment.ident.as_ref())
+                    .collect::<Vec<_>>()
+                    .join("::"))));
             }
         }
         self.impl_view(name, typ)
     }
 
-    fn impl_view(&mut self, name: &Ident, typ: &Ty) -> View {
+    fn impl_view(&mut self, name: &Ident, typ: &Ty) -> Result<View> {
         let tokens = &self.view_macro.take().expect("view_macro in impl_view()").tts;
-        if let TokenTree::Delimited(Delimited { ref tts, .. }) = tokens[0] {
-            let mut widget = parse(tts);
+        if let Some(&TokenTree::Delimited(Delimited { ref tts, .. })) = tokens.first() {
+            let mut widget = parse(tts)?;
             if let Gtk(ref mut widget) = widget.widget {
                 widget.relm_name = Some(typ.clone());
             }
             self.widget_parent_id = widget.parent_id.clone();
+            if self.vdom {
+                return self.impl_vdom_view(name, typ, widget);
+            }
             let mut properties_model_map = HashMap::new();
-            get_properties_model_map(&widget, &mut properties_model_map);
+            get_properties_model_map(&widget, &mut properties_model_map, &self.computed)?;
             self.add_widgets(&widget, &properties_model_map);
-            let (view, relm_widgets, container_impl) = gen(name, typ, &widget, self);
+            let (view, relm_widgets, container_impl) = gen(name, typ, &widget, self)?;
             let item = block_to_impl_item(quote! {
                 #[allow(unused_variables)] // Necessary to avoid warnings in case the parameters are unused.
                 fn view(relm: &::relm::RemoteRelm<Self>, model: &Self::Model) -> Self {
                     #view
                 }
-            });
-            View {
-                container_impl: container_impl,
-                item: item,
-                properties_model_map: properties_model_map,
-                relm_widgets: relm_widgets,
-                widget: widget,
-            }
+            })?;
+            Ok(View {
+                container_impl,
+                item,
+                properties_model_map,
+                relm_widgets,
+                widget,
+            })
         }
         else {
-            panic!("Expected `{{` but found `{:?}` in view! macro", tokens[0]);
+            Err(Error::unexpected_end("`{`", tokens))
         }
     }
+
+    /*
+     * In vdom mode, the view is described by __relm_describe(), which is evaluated to create the
+     * widgets and after every update to patch them.
+     */
+    fn impl_vdom_view(&mut self, name: &Ident, typ: &Ty, widget: Widget) -> Result<View> {
+        let description = gen_description(&widget)?;
+        let root_widget = widget.name.clone();
+        let root_widget_type = widget.typ.clone();
+        self.root_widget = Some(root_widget.clone());
+        self.root_widget_expr = Some(quote! {
+            #root_widget
+        });
+        self.root_widget_type = Some(quote! {
+            #root_widget_type
+        });
+        self.describe_method = Some(block_to_impl_item(quote! {
+            #[allow(unused_variables)]
+            fn __relm_describe(relm: &::relm::RemoteRelm<Self>, model: &<Self as ::relm::Widget>::Model)
+                -> ::relm::VWidget
+            {
+                #description
+            }
+        })?);
+        let phantom_field = gen_phantom_field(typ);
+        let item = block_to_impl_item(quote! {
+            #[allow(unused_variables)] // Necessary to avoid warnings in case the parameters are unused.
+            fn view(relm: &::relm::RemoteRelm<Self>, model: &Self::Model) -> Self {
+                let __relm_vdom = ::relm::VDom::new(Self::__relm_describe(relm, model));
+                let #root_widget: #root_widget_type = ::relm::Downcast::downcast(__relm_vdom.root())
+                    .unwrap_or_else(|_| panic!("the root of the view should have the type of the root widget"));
+                #name {
+                    #root_widget: #root_widget,
+                    __relm_relm: relm.clone(),
+                    __relm_vdom: __relm_vdom,
+                    #phantom_field
+                }
+            }
+        })?;
+        Ok(View {
+            container_impl: Tokens::new(),
+            item,
+            properties_model_map: HashMap::new(),
+            relm_widgets: HashMap::new(),
+            widget,
+        })
+    }
 }
 
+/// Generate the widget struct and the `Widget` implementation from the `impl` given as input.
+///
+/// The errors are reported with a `compile_error!()`.
 pub fn gen_widget(input: Tokens) -> Tokens {
-    let mut driver = Driver::new();
-    driver.gen_widget(input)
+    gen_widget_with_options(input, WidgetOptions::default())
 }
 
-fn add_model_param(model_fn: &mut ImplItem, model_param_type: &mut Option<ImplItem>) {
+/// Same as `gen_widget()`, with the options of the attribute parsed from `attributes`.
+pub fn gen_widget_with_attributes(attributes: &str, input: Tokens) -> Tokens {
+    match WidgetOptions::parse(attributes) {
+        Ok(options) => gen_widget_with_options(input, options),
+        Err(error) => error.to_compile_error(),
+    }
+}
+
+/// Same as `gen_widget()`, with the specified options.
+pub fn gen_widget_with_options(input: Tokens, options: WidgetOptions) -> Tokens {
+    let mut driver = Driver::new(options);
+    match driver.gen_widget(input) {
+        Ok(tokens) => tokens,
+        Err(error) => error.to_compile_error(),
+    }
+}
+
+fn add_model_param(model_fn: &mut ImplItem, model_param_type: &mut Option<ImplItem>) -> Result<()> {
     if let Method(ref mut method_sig, _) = model_fn.node {
         if method_sig.decl.inputs.is_empty() {
             method_sig.decl.inputs.push(Captured(Wild, Tup(vec![])));
@@ -364,35 +635,75 @@ fn add_model_param(model_fn: &mut ImplItem, model_param_type: &mut Option<ImplIt
             if let Captured(_, ref path) = method_sig.decl.inputs[0] {
                 *model_param_type = Some(block_to_impl_item(quote! {
                     type ModelParam = #path;
-                }));
+                })?);
             }
         }
     }
+    Ok(())
 }
 
-fn block_to_impl_item(tokens: Tokens) -> ImplItem {
+fn block_to_impl_item(tokens: Tokens) -> Result<ImplItem> {
     let implementation = quote! {
         impl Test {
             #tokens
         }
     };
-    let implementation = parse_item(implementation.as_str()).expect("parse_item in block_to_impl_item");
-    match implementation.node {
-        Impl(_, _, _, _, _, items) => items[0].clone(),
-        _ => unreachable!(),
+    let implementation = parse_item(implementation.as_str())
+        .map_err(|error| Error::new(format!("cannot parse the generated item `{}`: {}", tokens, error)))?;
+    if let Impl(_, _, _, _, _, mut items) = implementation.node {
+        if items.len() == 1 {
+            return Ok(items.remove(0));
+        }
+    }
+    Err(Error::new(format!("the generated item `{}` is not a single impl item", tokens)))
+}
+
+fn gen_vdom_update(update_ident: &Ident) -> Result<ImplItem> {
+    if *update_ident == "update_with_commands" {
+        block_to_impl_item(quote! {
+            fn update_with_commands(&mut self, event: Self::Msg, model: &mut Self::Model,
+                commands: &mut ::relm::Commands<Self::Msg>)
+            {
+                self.__relm_update(event, model, commands);
+                self.__relm_vdom.patch(Self::__relm_describe(&self.__relm_relm, model));
+            }
+        })
+    }
+    else {
+        block_to_impl_item(quote! {
+            fn update(&mut self, event: Self::Msg, model: &mut Self::Model) {
+                self.__relm_update(event, model);
+                self.__relm_vdom.patch(Self::__relm_describe(&self.__relm_relm, model));
+            }
+        })
     }
 }
 
-fn get_name(typ: &Ty) -> Ident {
+/*
+ * The arguments given to a handler are the variables of the same name bound by the pattern, or the model.
+ */
+fn get_handler_args(sig: &MethodSig) -> Result<Vec<Tokens>> {
+    let mut args = vec![];
+    for input in &sig.decl.inputs {
+        match *input {
+            SelfRef(..) | SelfValue(_) => (),
+            Captured(Pat::Ident(_, ref ident, _), _) => args.push(quote! { #ident }),
+            _ => return Err(Error::new("the parameters of an #[update] handler must be identifiers")),
+        }
+    }
+    Ok(args)
+}
+
+fn get_name(typ: &Ty) -> Result<Ident> {
     if let Ty::Path(_, ref path) = *typ {
         let mut parts = vec![];
         for segment in &path.segments {
             parts.push(segment.ident.as_ref());
         }
-        Ident::new(parts.join("::"))
+        Ok(Ident::new(parts.join("::")))
     }
     else {
-        panic!("Expected Path")
+        Err(Error::new("expected a type path in the #[widget] impl"))
     }
 }
 
@@ -440,58 +751,173 @@ fn get_phantom_field(typ: &Ty) -> Tokens {
 }
 
 macro_rules! get_map {
-    ($widget:expr, $map:expr, $is_relm:expr) => {{
+    ($widget:expr, $map:expr, $computed:expr, $kind:expr) => {{
         for (name, value) in &$widget.properties {
-            let string: String = value.parse().expect("parse::<String>() in get_map!");
-            let expr = parse_expr(&string).expect("parse_expr in get_map!");
+            let mut string: String = value.parse().expect("parse::<String>() in get_map!");
+            let expr = parse_expr(&string)
+                .map_err(|error| Error::new(format!("invalid value for the property `{}`: {}", name, error)))?;
             let mut visitor = ModelVariableVisitor::new();
             visitor.visit_expr(&expr);
-            let model_variables = visitor.idents;
+            let mut model_variables = visitor.idents;
+            let calls = computed_calls(&expr, $computed);
+            if !calls.is_empty() {
+                // The setters use the cached value, updated when a field read by the function changes.
+                let expr = replace_computed_calls(expr.clone(), $computed);
+                string = quote!(#expr).to_string();
+                for function in calls {
+                    let cache = function.cache_ident();
+                    for dep in &function.deps {
+                        let set = $map.entry(dep.clone()).or_insert_with(HashSet::new);
+                        set.insert(Property {
+                            expr: String::new(),
+                            kind: PropertyKind::Computed,
+                            name: function.name.to_string(),
+                            widget_name: cache.clone(),
+                        });
+                    }
+                    model_variables.push(cache);
+                }
+            }
             for var in model_variables {
                 let set = $map.entry(var).or_insert_with(HashSet::new);
                 set.insert(Property {
                     expr: string.clone(),
-                    is_relm_widget: $is_relm,
+                    kind: $kind,
                     name: name.clone(),
                     widget_name: $widget.name.clone(),
                 });
             }
         }
         for child in &$widget.children {
-            get_properties_model_map(child, $map);
+            get_properties_model_map(child, $map, $computed)?;
         }
+        Ok(())
     }};
 }
 
 /*
  * The map maps model variable name to a vector of tuples (widget name, property name).
  */
-fn get_properties_model_map(widget: &Widget, map: &mut PropertyModelMap) {
+fn get_properties_model_map(widget: &Widget, map: &mut PropertyModelMap, computed: &[ComputedFn]) -> Result<()> {
     match widget.widget {
-        Gtk(_) => get_map!(widget, map, false),
-        Relm(_) => get_map!(widget, map, true),
+        Flow(ref flow) => {
+            // The children are rebuilt when a model variable used in the flow or its children changes.
+            let mut visitor = ModelVariableVisitor::new();
+            visit_flow(flow, &mut visitor, computed)?;
+            for var in visitor.idents {
+                let set = map.entry(var).or_default();
+                set.insert(Property {
+                    expr: String::new(),
+                    kind: PropertyKind::Flow,
+                    name: String::new(),
+                    widget_name: widget.name.clone(),
+                });
+            }
+            Ok(())
+        },
+        Gtk(_) => get_map!(widget, map, computed, PropertyKind::Gtk),
+        Relm(_) => get_map!(widget, map, computed, PropertyKind::Relm),
+    }
+}
+
+fn visit_code(code: &str, visitor: &mut ModelVariableVisitor, computed: &[ComputedFn]) -> Result<()> {
+    let expr = parse_expr(code).map_err(|error| Error::new(format!("invalid expression `{}`: {}", code, error)))?;
+    visitor.visit_expr(&expr);
+    for function in computed_calls(&expr, computed) {
+        visitor.idents.extend(function.deps.iter().cloned());
+    }
+    Ok(())
+}
+
+fn visit_flow(flow: &FlowWidget, visitor: &mut ModelVariableVisitor, computed: &[ComputedFn]) -> Result<()> {
+    match *flow {
+        FlowWidget::For(ref pattern, ref iterator, ref children) => {
+            visit_code(&format!("for {} in {} {{}}", pattern.as_str(), iterator.as_str()), visitor, computed)?;
+            visit_widgets(children, visitor, computed)
+        },
+        FlowWidget::If(ref condition, ref then, ref otherwise) => {
+            visit_code(&format!("if {} {{}}", condition.as_str()), visitor, computed)?;
+            visit_widgets(then, visitor, computed)?;
+            visit_widgets(otherwise, visitor, computed)
+        },
+        FlowWidget::Match(ref expr, ref arms) => {
+            visit_code(&format!("match {} {{}}", expr.as_str()), visitor, computed)?;
+            for (_, children) in arms {
+                visit_widgets(children, visitor, computed)?;
+            }
+            Ok(())
+        },
+    }
+}
+
+fn visit_widgets(widgets: &[Widget], visitor: &mut ModelVariableVisitor, computed: &[ComputedFn]) -> Result<()> {
+    for widget in widgets {
+        if let Flow(ref flow) = widget.widget {
+            visit_flow(flow, visitor, computed)?;
+        }
+        let values = widget.properties.values()
+            .chain(widget.child_properties.values())
+            .chain(&widget.init_parameters)
+            .filter(|value| !value.as_str().is_empty());
+        for value in values {
+            visit_code(value.as_str(), visitor, computed)?;
+        }
+        visit_widgets(&widget.children, visitor, computed)?;
+    }
+    Ok(())
+}
+
+/*
+ * Remove the #[computed] attribute and return whether it was present.
+ */
+fn take_computed_attribute(attrs: &mut Vec<Attribute>) -> bool {
+    match attrs.iter().position(|attr| attr.name() == "computed") {
+        Some(index) => {
+            let _ = attrs.remove(index);
+            true
+        },
+        None => false,
+    }
+}
+
+/*
+ * Remove the #[update(Pattern)] or #[update = "Pattern"] attribute and return its pattern.
+ */
+fn take_update_attribute(attrs: &mut Vec<Attribute>) -> Result<Option<Tokens>> {
+    let index =
+        match attrs.iter().position(|attr| attr.name() == "update") {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+    let attr = attrs.remove(index);
+    let mut pattern = Tokens::new();
+    match attr.value {
+        MetaItem::List(_, ref items) if items.len() == 1 => pattern.append_all(items),
+        MetaItem::NameValue(_, Str(ref string, _)) => pattern.append(string),
+        _ => return Err(Error::new("expected #[update(Pattern)] or #[update = \"Pattern\"]")),
     }
+    Ok(Some(pattern))
 }
 
-fn get_return_type(sig: MethodSig) -> Ty {
+fn get_return_type(sig: MethodSig) -> Result<Ty> {
     if let FunctionRetTy::Ty(ty) = sig.decl.output {
-        ty
+        Ok(ty)
     }
     else {
-        panic!("Unexpected default, expecting Ty");
+        Err(Error::new("the model method must return the model"))
     }
 }
 
-fn get_second_param_type(sig: &MethodSig) -> Ty {
-    if let Captured(_, ref path) = sig.decl.inputs[1] {
-        path.clone()
+fn get_second_param_type(sig: &MethodSig) -> Result<Ty> {
+    if let Some(Captured(_, path)) = sig.decl.inputs.get(1) {
+        Ok(path.clone())
     }
     else {
-        panic!("Unexpected `{:?}`, expecting Captured Ty", sig.decl.inputs[1]);
+        Err(Error::new("the second parameter of the update method must be the message"))
     }
 }
 
-fn gen_set_child_prop_calls(widget: &Widget) -> Option<ImplItem> {
+fn gen_set_child_prop_calls(widget: &Widget) -> Result<Option<ImplItem>> {
     let mut tokens = Tokens::new();
     let widget_name = &widget.name;
     for (key, value) in &widget.child_properties {
@@ -501,15 +927,151 @@ fn gen_set_child_prop_calls(widget: &Widget) -> Option<ImplItem> {
         });
     }
     if !widget.child_properties.is_empty() {
-        Some(block_to_impl_item(quote! {
+        Ok(Some(block_to_impl_item(quote! {
             fn on_add<W: ::gtk::IsA<::gtk::Widget> + ::gtk::IsA<::gtk::Object>>(&self, parent: W) {
                 let parent: gtk::Box = ::gtk::Cast::downcast(::gtk::Cast::upcast::<::gtk::Widget>(parent))
                     .expect("the parent of a widget with child properties must be a gtk::Box");
                 #tokens
             }
-        }))
+        })?))
     }
     else {
-        None
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::Tokens;
+
+    use gen_widget;
+
+    fn gen_win(items: Tokens) -> String {
+        gen_widget(quote! {
+            impl Widget for Win {
+                fn model() -> Model {
+                    Model {
+                        text: String::new(),
+                    }
+                }
+
+                #items
+
+                view! {
+                    gtk::Label {
+                        text: &model.text,
+                    }
+                }
+            }
+        }).to_string()
+    }
+
+    // Get the code of the generated Widget impl and of the generated inherent impl, if any.
+    fn split_impls(code: &str) -> (&str, &str) {
+        let widget_impl = code.find("impl Widget for Win {").expect("Widget impl");
+        match code.find("impl Win {") {
+            Some(helper_impl) => {
+                assert!(widget_impl < helper_impl, "{}", code);
+                (&code[widget_impl..helper_impl], &code[helper_impl..])
+            },
+            None => (&code[widget_impl..], ""),
+        }
+    }
+
+    #[test]
+    fn helper_items() {
+        let code = gen_win(quote! {
+            const GREETING: &'static str = "Hello";
+
+            fn update(&mut self, event: Msg, model: &mut Model) {
+                self.greet(model);
+            }
+
+            fn greet(&mut self, model: &mut Model) {
+                model.text = Self::GREETING.to_string();
+            }
+
+            fn format(text: &str) -> String {
+                text.to_uppercase()
+            }
+        });
+        assert!(!code.contains("compile_error"), "{}", code);
+        let (widget_impl, helper_impl) = split_impls(&code);
+        assert!(widget_impl.contains("fn update ("), "{}", code);
+        for item in &["const GREETING", "fn greet (", "fn format ("] {
+            assert!(!widget_impl.contains(item), "{} in {}", item, code);
+            assert!(helper_impl.contains(item), "{} not in {}", item, code);
+        }
+        // The helper methods taking self update the properties using the fields they mutate.
+        assert!(helper_impl.contains(". set_text ( & model . text ) ; }"), "{}", code);
+    }
+
+    #[test]
+    fn update_handlers() {
+        let code = gen_win(quote! {
+            type Msg = Msg;
+
+            #[update(Clear)]
+            fn clear(&mut self, model: &mut Model) {
+                model.text.clear();
+            }
+
+            #[update(Set(text))]
+            fn set(&mut self, text: String, model: &mut Model) {
+                model.text = text;
+            }
+
+            // Patterns which are not valid in an attribute list are given as a string.
+            #[update = "Move { x, .. }"]
+            fn move_x(&self, x: i32) {
+                println!("{}", x);
+            }
+        });
+        assert!(!code.contains("compile_error"), "{}", code);
+        let (widget_impl, helper_impl) = split_impls(&code);
+        // The handlers given the model are followed by the updates of the properties.
+        let arms = [
+            "match event { Clear => { let __relm_value = self . clear ( model ) ;",
+            "Set ( text ) => { let __relm_value = self . set ( text , model ) ;",
+            "Move { x , .. } => self . move_x ( x ) ,",
+            // The messages without a handler are ignored.
+            "_ => ( ) , }",
+        ];
+        for arm in &arms {
+            assert!(widget_impl.contains(arm), "{} not in {}", arm, code);
+        }
+        for handler in &["fn clear (", "fn set (", "fn move_x ("] {
+            assert!(helper_impl.contains(handler), "{} not in {}", handler, code);
+        }
+    }
+
+    #[test]
+    fn update_handlers_with_update() {
+        let code = gen_win(quote! {
+            type Msg = Msg;
+
+            fn update(&mut self, event: Msg, model: &mut Model) {
+            }
+
+            #[update(Clear)]
+            fn clear(&mut self, model: &mut Model) {
+                model.text.clear();
+            }
+        });
+        assert!(code.contains("the update method cannot be implemented when using #[update] handlers"), "{}", code);
+    }
+
+    #[test]
+    fn update_with_commands() {
+        let code = gen_win(quote! {
+            fn update(&mut self, event: Msg, model: &mut Model, commands: &mut Commands<Msg>) {
+                model.text = event.to_string();
+            }
+        });
+        assert!(!code.contains("compile_error"), "{}", code);
+        let (widget_impl, _) = split_impls(&code);
+        assert!(widget_impl.contains("fn update_with_commands ( & mut self , event : Msg , model : & mut Model , \
+            commands : & mut Commands < Msg > )"), "{}", code);
+        assert!(!widget_impl.contains("fn update ("), "{}", code);
     }
 }
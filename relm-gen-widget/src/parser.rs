@@ -20,8 +20,10 @@
  */
 
 use std::collections::HashMap;
+use std::env;
 use std::fs::File;
 use std::io::Read;
+use std::path;
 use std::sync::Mutex;
 
 use quote::{Tokens, ToTokens};
@@ -32,13 +34,18 @@ use syn::ItemKind::Mac;
 use syn::Lit::Str;
 use syn::StrStyle::Cooked;
 use syn::TokenTree::{self, Token};
-use syn::Token::{At, Colon, Comma, Eq, FatArrow, Gt, Ident, Literal, Lt, ModSep, Pound};
+use syn::Token::{At, Colon, Comma, Eq, FatArrow, Gt, Ident, Le, Literal, Lt, ModSep, Pound};
 
+use error::{Error, Locations, Result, token_at};
+use glade::parse_objects;
 use self::DefaultParam::*;
 use self::EventValue::*;
 use self::EventValueReturn::*;
 use self::EitherWidget::*;
+use self::FlowWidget::*;
 
+pub const RELM_BUILDER_IDENT: &str = "__relm_builder";
+pub const RELM_FLOW_IDENT: &str = "__relm_flow";
 pub const RELM_WIDGET_CLONE_IDENT: &str = "__relm_widget_self_clone";
 pub const RELM_WIDGET_SELF_IDENT: &str = "__relm_widget_self";
 
@@ -82,6 +89,7 @@ impl Event {
     }
 }
 
+#[derive(Debug)]
 pub struct Widget {
     pub child_properties: HashMap<String, Tokens>,
     pub children: Vec<Widget>,
@@ -95,6 +103,22 @@ pub struct Widget {
 }
 
 impl Widget {
+    fn new_flow(flow: FlowWidget, keyword: &str) -> Self {
+        let typ = Path::from(keyword);
+        let name = format!("__relm_{}", gen_widget_name(&typ));
+        Widget {
+            child_properties: HashMap::new(),
+            children: vec![],
+            container_type: None,
+            init_parameters: vec![],
+            name: syn::Ident::new(name),
+            parent_id: None,
+            properties: HashMap::new(),
+            typ,
+            widget: Flow(flow),
+        }
+    }
+
     fn new_gtk(widget: GtkWidget, typ: Path, init_parameters: Vec<Tokens>, children: Vec<Widget>,
         properties: HashMap<String, Tokens>, child_properties: HashMap<String, Tokens>) -> Self
     {
@@ -136,13 +160,30 @@ impl Widget {
 
 #[derive(Debug)]
 pub enum EitherWidget {
+    Flow(FlowWidget),
     Gtk(GtkWidget),
     Relm(RelmWidget),
 }
 
+// Children which depend on the model, specified with an `if`, a `match` or a `for` in the view.
+#[derive(Debug)]
+pub enum FlowWidget {
+    // Pattern, iterator and children of the loop.
+    For(Tokens, Tokens, Vec<Widget>),
+    // The condition contains the pattern of an `if let`.
+    If(Tokens, Vec<Widget>, Vec<Widget>),
+    Match(Tokens, Vec<(Tokens, Vec<Widget>)>),
+}
+
 #[derive(Debug)]
 pub struct GtkWidget {
+    // Maps the property name to the model field bound with `property: <=> model.field`.
+    pub bindings: HashMap<String, Tokens>,
+    // Id of the object in the glade file, when the widget is created by a gtk::Builder.
+    pub builder_id: Option<String>,
     pub events: HashMap<String, Event>,
+    // Glade file, as written in the view! macro, only set on the root widget.
+    pub glade_file: Option<String>,
     pub relm_name: Option<Ty>,
     pub save: bool,
 }
@@ -150,7 +191,10 @@ pub struct GtkWidget {
 impl GtkWidget {
     fn new() -> Self {
         GtkWidget {
+            bindings: HashMap::new(),
+            builder_id: None,
             events: HashMap::new(),
+            glade_file: None,
             relm_name: None,
             save: false,
         }
@@ -182,70 +226,113 @@ fn last_segment_lowercase(path: &Path) -> bool {
     }
 }
 
-pub fn parse(tokens: &[TokenTree]) -> Widget {
-    let tokens =
-        if let Token(Literal(Str(ref relm_view_file, _))) = tokens[0] {
-            // TODO: also support glade file.
-            let mut file = File::open(relm_view_file).expect("File::open() in parse()");
-            let mut file_content = String::new();
-            file.read_to_string(&mut file_content).expect("read_to_string() in parse()");
-            let item = parse_item(&file_content).expect("parse_item() in parse()");
-            if let Mac(syn::Mac { tts, .. }) = item.node {
-                if let TokenTree::Delimited(Delimited { ref tts, .. }) = tts[0] {
-                    tts.clone()
-                }
-                else {
-                    panic!("Expected delimited macro")
-                }
-            }
-            else {
-                panic!("Expecting a macro")
+pub fn parse(tokens: &[TokenTree]) -> Result<Widget> {
+    if let Some(&Token(Literal(Str(ref relm_view_file, _)))) = tokens.first() {
+        let file_content = read_file(relm_view_file)?;
+        let item = parse_item(&file_content)
+            .map_err(|error| Error::new(format!("cannot parse {}: {}", relm_view_file, error)))?;
+        if let Mac(syn::Mac { tts, .. }) = item.node {
+            match tts.first() {
+                Some(&TokenTree::Delimited(Delimited { ref tts, .. })) => {
+                    // The tokens of the view start after the delimiter following view!.
+                    let start = file_content.find("view!")
+                        .and_then(|index| file_content[index..].find(&['{', '(', '['][..])
+                            .map(|delimiter| index + delimiter + 1))
+                        .unwrap_or(0);
+                    let locations = Locations::new(&file_content, start, tts, Some(relm_view_file));
+                    parse_view(tts).map_err(|error| error.locate(&locations))
+                },
+                _ => Err(Error::new(format!("expected a delimited view! macro in {}", relm_view_file))),
             }
         }
         else {
-            tokens.to_vec()
-        };
-    let (mut widget, _, parent_id) = parse_child(&tokens, true);
+            Err(Error::new(format!("expected a view! macro in {}", relm_view_file)))
+        }
+    }
+    else {
+        let mut source = Tokens::new();
+        source.append_all(tokens);
+        let locations = Locations::new(source.as_str(), 0, tokens, None);
+        parse_view(tokens).map_err(|error| error.locate(&locations))
+    }
+}
+
+fn parse_view(tokens: &[TokenTree]) -> Result<Widget> {
+    if token_at(tokens, 0, "a widget")? == &Token(Ident(syn::Ident::new("glade"))) {
+        return parse_glade(&tokens[1..]);
+    }
+    let (mut widget, _, parent_id) = parse_child(tokens, true)?;
     widget.parent_id = parent_id;
-    widget
+    Ok(widget)
 }
 
-fn parse_widget(tokens: &[TokenTree], save: bool) -> (Widget, &[TokenTree]) {
-    let (gtk_type, mut tokens) = parse_qualified_name(tokens);
-    let mut gtk_widget = GtkWidget::new();
-    let mut init_parameters = vec![];
-    let mut children = vec![];
-    let mut properties = HashMap::new();
-    let mut child_properties = HashMap::new();
-    gtk_widget.save = save;
-    if let TokenTree::Delimited(Delimited { delim: Paren, ref tts }) = tokens[0] {
-        let parameters = parse_comma_list(tts);
-        init_parameters = parameters;
-        tokens = &tokens[1..];
+/*
+ * Parse view!(glade "file.glade" { id { property: value, event => Msg } }).
+ * Every object with an id is saved in the widget struct. The first toplevel object is the root.
+ */
+fn parse_glade(tokens: &[TokenTree]) -> Result<Widget> {
+    let glade_file =
+        match *token_at(tokens, 0, "a glade file name")? {
+            Token(Literal(Str(ref glade_file, _))) => glade_file,
+            ref token => return Err(Error::unexpected("a glade file name", token)),
+        };
+    let path =
+        match manifest_dir(glade_file) {
+            Some(dir) => format!("{}/{}", dir, glade_file),
+            None => glade_file.clone(),
+        };
+    let interface = read_file(&path)?;
+    let mut objects = HashMap::new();
+    let mut root_id = None;
+    let mut ids = vec![];
+    for object in parse_objects(&interface, glade_file)? {
+        if root_id.is_none() && object.toplevel {
+            root_id = Some(object.id.clone());
+        }
+        ids.push(object.id.clone());
+        objects.insert(object.id, object.class);
     }
-    if let TokenTree::Delimited(Delimited { delim: Brace, ref tts }) = tokens[0] {
+    let root_id = root_id.ok_or_else(|| Error::new(format!("no toplevel object with an id in {}", glade_file)))?;
+
+    let mut bindings = HashMap::new();
+    if let Some(&TokenTree::Delimited(Delimited { delim: Brace, ref tts })) = tokens.get(1) {
         let mut tts = &tts[..];
         while !tts.is_empty() {
-            if tts[0] == Token(Pound) || try_parse_name(tts).is_some() {
-                let (child, new_tts, _) = parse_child(tts, false);
-                tts = new_tts;
-                children.push(child);
+            let (id, new_tts) = parse_ident(tts)?;
+            tts = new_tts;
+            if !objects.contains_key(&id) {
+                return Err(Error::new(format!("no object with id `{}` in {}", id, glade_file)));
             }
-            else {
-                // Property or event.
-                let (ident, _) = parse_ident(tts);
-                tts = &tts[1..];
-                match tts[0] {
-                    Token(Colon) => {
-                        tts = parse_value_or_child_properties(tts, ident, &mut child_properties, &mut properties);
-                    },
-                    TokenTree::Delimited(Delimited { delim: Paren, .. }) | Token(FatArrow) => {
-                        let (event, new_tts) = parse_event(tts, DefaultOneParam);
-                        gtk_widget.events.insert(ident, event);
-                        tts = new_tts;
-                    },
-                    _ => panic!("Expected `:` or `(` but found `{:?}` in view! macro", tts[0]),
+            let typ =
+                if token_at(tts, 0, "`{` or `:`")? == &Token(Colon) {
+                    let (typ, new_tts) = parse_qualified_name(&tts[1..])?;
+                    tts = new_tts;
+                    Some(typ)
                 }
+                else {
+                    None
+                };
+            let widget =
+                match *token_at(tts, 0, "`{`")? {
+                    TokenTree::Delimited(Delimited { delim: Brace, ref tts }) => {
+                        let mut gtk_widget = GtkWidget::new();
+                        let mut children = vec![];
+                        let mut properties = HashMap::new();
+                        let mut child_properties = HashMap::new();
+                        parse_gtk_widget_items(tts, &mut gtk_widget, &mut children, &mut properties,
+                            &mut child_properties)?;
+                        if !child_properties.is_empty() {
+                            return Err(Error::new(format!(
+                                "child properties cannot be set on the glade object `{}`: use <packing> in the glade file",
+                                id)));
+                        }
+                        (typ, gtk_widget, children, properties)
+                    },
+                    ref token => return Err(Error::unexpected("`{`", token)),
+                };
+            tts = &tts[1..];
+            if bindings.insert(id.clone(), widget).is_some() {
+                return Err(Error::new(format!("the glade object `{}` is bound twice in view! macro", id)));
             }
 
             if tts.first() == Some(&Token(Comma)) {
@@ -253,46 +340,289 @@ fn parse_widget(tokens: &[TokenTree], save: bool) -> (Widget, &[TokenTree]) {
             }
         }
     }
+
+    let mut glade_widget = |id: &str| -> Result<Widget> {
+        let (typ, mut gtk_widget, children, properties) = bindings.remove(id)
+            .unwrap_or_else(|| (None, GtkWidget::new(), vec![], HashMap::new()));
+        let typ =
+            match typ {
+                Some(typ) => typ,
+                None => glade_class_to_path(&objects[id])?,
+            };
+        gtk_widget.builder_id = Some(id.to_string());
+        gtk_widget.save = true;
+        let mut widget = Widget::new_gtk(gtk_widget, typ, vec![], children, properties, HashMap::new());
+        widget.name = syn::Ident::new(id);
+        Ok(widget)
+    };
+    let mut root = glade_widget(&root_id)?;
+    for id in ids.iter().filter(|id| **id != root_id) {
+        let widget = glade_widget(id)?;
+        root.children.push(widget);
+    }
+    if let Gtk(ref mut gtk_widget) = root.widget {
+        gtk_widget.glade_file = Some(glade_file.clone());
+    }
+    Ok(root)
+}
+
+/*
+ * A relative glade file is in the directory of the crate being compiled, given by cargo, instead of
+ * the working directory of rustc.
+ */
+pub fn manifest_dir(file: &str) -> Option<String> {
+    if path::Path::new(file).is_relative() {
+        env::var("CARGO_MANIFEST_DIR").ok()
+    }
     else {
-        panic!("Expected {{ but found `{:?}` in view! macro", tokens[0]);
+        None
+    }
+}
+
+fn glade_class_to_path(class: &str) -> Result<Path> {
+    if class.starts_with("Gtk") {
+        Ok(parse_path(&format!("gtk::{}", class.trim_start_matches("Gtk"))).expect("glade class should be a valid path"))
+    }
+    else {
+        Err(Error::new(format!(
+            "cannot infer the type of the glade class {}: specify it with `id: Type {{ }}` in view! macro", class)))
+    }
+}
+
+fn parse_widget(tokens: &[TokenTree], save: bool) -> Result<(Widget, &[TokenTree])> {
+    let (gtk_type, mut tokens) = parse_qualified_name(tokens)?;
+    let mut gtk_widget = GtkWidget::new();
+    let mut init_parameters = vec![];
+    let mut children = vec![];
+    let mut properties = HashMap::new();
+    let mut child_properties = HashMap::new();
+    gtk_widget.save = save;
+    if let TokenTree::Delimited(Delimited { delim: Paren, ref tts }) = *token_at(tokens, 0, "`(` or `{`")? {
+        let parameters = parse_comma_list(tts);
+        init_parameters = parameters;
+        tokens = &tokens[1..];
+    }
+    match *token_at(tokens, 0, "`{`")? {
+        TokenTree::Delimited(Delimited { delim: Brace, ref tts }) =>
+            parse_gtk_widget_items(tts, &mut gtk_widget, &mut children, &mut properties, &mut child_properties)?,
+        ref token => return Err(Error::unexpected("`{`", token)),
     }
     let widget = Widget::new_gtk(gtk_widget, gtk_type, init_parameters, children, properties, child_properties);
-    (widget, &tokens[1..])
+    Ok((widget, &tokens[1..]))
 }
 
-fn parse_child(mut tokens: &[TokenTree], root: bool) -> (Widget, &[TokenTree], Option<String>) {
-    let (mut attributes, new_tokens) = parse_attributes(tokens);
+fn parse_gtk_widget_items(mut tts: &[TokenTree], gtk_widget: &mut GtkWidget, children: &mut Vec<Widget>,
+    properties: &mut HashMap<String, Tokens>, child_properties: &mut HashMap<String, Tokens>) -> Result<()>
+{
+    while !tts.is_empty() {
+        if is_flow(tts) || tts[0] == Token(Pound) || try_parse_name(tts).is_some() {
+            let (child, new_tts) = parse_child_or_flow(tts)?;
+            tts = new_tts;
+            children.push(child);
+        }
+        else {
+            // Property or event.
+            let (ident, new_tts) = parse_ident(tts)?;
+            tts = new_tts;
+            match *token_at(tts, 0, "`:`, `=>` or `(`")? {
+                Token(Colon) if tts.get(1) == Some(&Token(Le)) && tts.get(2) == Some(&Token(Gt)) => {
+                    tts = parse_binding(&tts[3..], ident, gtk_widget, properties)?;
+                },
+                Token(Colon) => {
+                    tts = parse_value_or_child_properties(tts, ident, child_properties, properties)?;
+                },
+                TokenTree::Delimited(Delimited { delim: Paren, .. }) | Token(FatArrow) => {
+                    let (event, new_tts) = parse_event(tts, DefaultOneParam)?;
+                    if gtk_widget.events.insert(ident.clone(), event).is_some() {
+                        return Err(Error::new(format!("the event `{}` is connected twice in view! macro", ident)));
+                    }
+                    tts = new_tts;
+                },
+                ref token => return Err(Error::unexpected("`:`, `=>` or `(`", token)),
+            }
+        }
+
+        if tts.first() == Some(&Token(Comma)) {
+            tts = &tts[1..];
+        }
+    }
+    Ok(())
+}
+
+fn parse_child(mut tokens: &[TokenTree], root: bool) -> Result<(Widget, &[TokenTree], Option<String>)> {
+    let (mut attributes, new_tokens) = parse_attributes(tokens)?;
     let container_type = attributes.remove("container")
         .map(|typ| typ.map(str::to_string));
     tokens = new_tokens;
     let name = attributes.get("name").and_then(|name| *name);
     let (mut widget, new_tokens) =
         if tokens.get(1) == Some(&Token(ModSep)) {
-            parse_widget(tokens, name.is_some() || root)
+            parse_widget(tokens, name.is_some() || root)?
         }
         else {
-            parse_relm_widget(tokens)
+            parse_relm_widget(tokens)?
         };
     if let Some(name) = name {
         widget.name = syn::Ident::new(name);
     }
     widget.container_type = container_type;
     let parent_id = attributes.get("parent").and_then(|opt_str| opt_str.map(str::to_string));
-    (widget, new_tokens, parent_id)
+    Ok((widget, new_tokens, parent_id))
+}
+
+fn is_flow(tokens: &[TokenTree]) -> bool {
+    match tokens.first() {
+        Some(&Token(Ident(ref ident))) => *ident == "for" || *ident == "if" || *ident == "match",
+        _ => false,
+    }
 }
 
-fn parse_ident(tokens: &[TokenTree]) -> (String, &[TokenTree]) {
+fn parse_child_or_flow(tokens: &[TokenTree]) -> Result<(Widget, &[TokenTree])> {
     match tokens[0] {
+        Token(Ident(ref ident)) if *ident == "for" => parse_for(&tokens[1..]),
+        Token(Ident(ref ident)) if *ident == "if" => parse_if(&tokens[1..]),
+        Token(Ident(ref ident)) if *ident == "match" => parse_match(&tokens[1..]),
+        _ => {
+            let (child, tokens, _) = parse_child(tokens, false)?;
+            Ok((child, tokens))
+        },
+    }
+}
+
+/*
+ * Parse for pattern in iterator { widgets }.
+ */
+fn parse_for(mut tokens: &[TokenTree]) -> Result<(Widget, &[TokenTree])> {
+    let mut pattern = Tokens::new();
+    while *token_at(tokens, 0, "`in`")? != Token(Ident(syn::Ident::new("in"))) {
+        tokens[0].to_tokens(&mut pattern);
+        tokens = &tokens[1..];
+    }
+    if pattern.as_str().is_empty() {
+        return Err(Error::unexpected("a pattern", &tokens[0]));
+    }
+    let (iterator, tokens) = parse_until_brace(&tokens[1..], "an iterator")?;
+    let (children, tokens) = parse_flow_block(tokens)?;
+    Ok((Widget::new_flow(For(pattern, iterator, children), "for"), tokens))
+}
+
+/*
+ * Parse if condition { widgets } else if condition { widgets } else { widgets }.
+ */
+fn parse_if(tokens: &[TokenTree]) -> Result<(Widget, &[TokenTree])> {
+    let (condition, tokens) = parse_until_brace(tokens, "a condition")?;
+    let (then, tokens) = parse_flow_block(tokens)?;
+    let (otherwise, tokens) =
+        if tokens.first() == Some(&Token(Ident(syn::Ident::new("else")))) {
+            match *token_at(tokens, 1, "`if` or `{`")? {
+                Token(Ident(ref ident)) if *ident == "if" => {
+                    let (widget, tokens) = parse_if(&tokens[2..])?;
+                    (vec![widget], tokens)
+                },
+                _ => parse_flow_block(&tokens[1..])?,
+            }
+        }
+        else {
+            (vec![], tokens)
+        };
+    Ok((Widget::new_flow(If(condition, then, otherwise), "if"), tokens))
+}
+
+/*
+ * Parse match expr { pattern => { widgets }, pattern => widget }.
+ */
+fn parse_match(tokens: &[TokenTree]) -> Result<(Widget, &[TokenTree])> {
+    let (expr, tokens) = parse_until_brace(tokens, "an expression")?;
+    let mut arms = vec![];
+    if let TokenTree::Delimited(Delimited { delim: Brace, ref tts }) = tokens[0] {
+        let mut tts = &tts[..];
+        while !tts.is_empty() {
+            let mut pattern = Tokens::new();
+            while *token_at(tts, 0, "`=>`")? != Token(FatArrow) {
+                tts[0].to_tokens(&mut pattern);
+                tts = &tts[1..];
+            }
+            tts = &tts[1..];
+            let (children, new_tts) =
+                if let TokenTree::Delimited(Delimited { delim: Brace, .. }) = *token_at(tts, 0, "a widget")? {
+                    parse_flow_block(tts)?
+                }
+                else {
+                    let (child, new_tts) = parse_child_or_flow(tts)?;
+                    (vec![child], new_tts)
+                };
+            tts = new_tts;
+            arms.push((pattern, children));
+
+            if tts.first() == Some(&Token(Comma)) {
+                tts = &tts[1..];
+            }
+        }
+    }
+    Ok((Widget::new_flow(Match(expr, arms), "match"), &tokens[1..]))
+}
+
+/*
+ * Parse a block containing only widgets, in a branch of an `if` or a `match` or in a `for`.
+ */
+fn parse_flow_block(tokens: &[TokenTree]) -> Result<(Vec<Widget>, &[TokenTree])> {
+    match *token_at(tokens, 0, "`{`")? {
+        TokenTree::Delimited(Delimited { delim: Brace, ref tts }) => {
+            let mut gtk_widget = GtkWidget::new();
+            let mut children = vec![];
+            let mut properties = HashMap::new();
+            let mut child_properties = HashMap::new();
+            parse_gtk_widget_items(tts, &mut gtk_widget, &mut children, &mut properties, &mut child_properties)?;
+            if !gtk_widget.events.is_empty() || !properties.is_empty() || !child_properties.is_empty() {
+                return Err(Error::new("only widgets are allowed in the blocks of `if`, `match` and `for` in view! macro"));
+            }
+            Ok((children, &tokens[1..]))
+        },
+        ref token => Err(Error::unexpected("`{`", token)),
+    }
+}
+
+/*
+ * Collect the tokens of the condition of an `if`, the expression of a `match` or the iterator of a
+ * `for`, up to the block.
+ */
+fn parse_until_brace<'a>(mut tokens: &'a [TokenTree], expected: &str) -> Result<(Tokens, &'a [TokenTree])> {
+    let mut expr = Tokens::new();
+    loop {
+        match *token_at(tokens, 0, "`{`")? {
+            TokenTree::Delimited(Delimited { delim: Brace, .. }) => break,
+            ref token => token.to_tokens(&mut expr),
+        }
+        tokens = &tokens[1..];
+    }
+    if expr.as_str().is_empty() {
+        return Err(Error::unexpected(expected, &tokens[0]));
+    }
+    Ok((expr, tokens))
+}
+
+fn read_file(path: &str) -> Result<String> {
+    let mut file = File::open(path).map_err(|error| Error::new(format!("cannot open {}: {}", path, error)))?;
+    let mut file_content = String::new();
+    file.read_to_string(&mut file_content)
+        .map_err(|error| Error::new(format!("cannot read {}: {}", path, error)))?;
+    Ok(file_content)
+}
+
+fn parse_ident(tokens: &[TokenTree]) -> Result<(String, &[TokenTree])> {
+    match *token_at(tokens, 0, "an identifier")? {
         Token(Ident(ref ident)) => {
-            (ident.to_string(), &tokens[1..])
+            Ok((ident.to_string(), &tokens[1..]))
         },
-        _ => panic!("Expected ident but found `{:?}` in view! macro", tokens[0]),
+        ref token => Err(Error::unexpected("an identifier", token)),
     }
 }
 
-fn parse_qualified_name(tokens: &[TokenTree]) -> (Path, &[TokenTree]) {
-    try_parse_name(tokens)
-        .unwrap_or_else(|| panic!("Expected qualified name but found `{:?}` in view! macro", tokens[0]))
+fn parse_qualified_name(tokens: &[TokenTree]) -> Result<(Path, &[TokenTree])> {
+    match try_parse_name(tokens) {
+        Some(name) => Ok(name),
+        None => Err(Error::unexpected("a widget type", token_at(tokens, 0, "a widget type")?)),
+    }
 }
 
 fn try_parse_name(mut tokens: &[TokenTree]) -> Option<(Path, &[TokenTree])> {
@@ -311,8 +641,8 @@ fn try_parse_name(mut tokens: &[TokenTree]) -> Option<(Path, &[TokenTree])> {
         path_string.push_str(&toks.to_string());
         tokens = &tokens[1..];
     }
-    match tokens[0] {
-        TokenTree::Delimited(_) | Token(Comma) => {
+    match tokens.first() {
+        Some(&TokenTree::Delimited(_)) | Some(&Token(Comma)) => {
             if let Ok(path) = parse_path(&path_string) {
                 if !last_segment_lowercase(&path) {
                     return Some((path, tokens));
@@ -324,7 +654,7 @@ fn try_parse_name(mut tokens: &[TokenTree]) -> Option<(Path, &[TokenTree])> {
     None
 }
 
-fn parse_comma_ident_list(tokens: &[TokenTree]) -> Vec<syn::Ident> {
+fn parse_comma_ident_list(tokens: &[TokenTree]) -> Result<Vec<syn::Ident>> {
     let mut params = vec![];
     for token in tokens {
         if *token != Token(Comma) {
@@ -334,11 +664,11 @@ fn parse_comma_ident_list(tokens: &[TokenTree]) -> Vec<syn::Ident> {
                 params.push(syn::Ident::new(tokens.as_str()));
             }
             else {
-                panic!("Expecting Token, but found: `{:?}`", token);
+                return Err(Error::unexpected("a parameter name", token));
             }
         }
     }
-    params
+    Ok(params)
 }
 
 fn parse_comma_list(tokens: &[TokenTree]) -> Vec<Tokens> {
@@ -357,37 +687,38 @@ fn parse_comma_list(tokens: &[TokenTree]) -> Vec<Tokens> {
     params
 }
 
-fn parse_event(mut tokens: &[TokenTree], default_param: DefaultParam) -> (Event, &[TokenTree]) {
+fn parse_event(mut tokens: &[TokenTree], default_param: DefaultParam) -> Result<(Event, &[TokenTree])> {
     let mut event = Event::new();
     if default_param == DefaultNoParam {
         event.params.clear();
     }
-    if let TokenTree::Delimited(Delimited { delim: Paren, ref tts }) = tokens[0] {
-        event.params = parse_comma_ident_list(tts);
+    if let TokenTree::Delimited(Delimited { delim: Paren, ref tts }) = *token_at(tokens, 0, "`(` or `=>`")? {
+        event.params = parse_comma_ident_list(tts)?;
         tokens = &tokens[1..];
     }
     event.model_ident =
-        if tokens[0] == Token(Ident(syn::Ident::new("with"))) {
-            if let Token(Ident(ref ident)) = tokens[1] {
-                tokens = &tokens[2..];
-                Some(ident.clone())
-            }
-            else {
-                panic!("Expecting ident after `with`, but found `{:?}` in view! macro", tokens[1]);
+        if *token_at(tokens, 0, "`=>`")? == Token(Ident(syn::Ident::new("with"))) {
+            match *token_at(tokens, 1, "an identifier after `with`")? {
+                Token(Ident(ref ident)) => {
+                    tokens = &tokens[2..];
+                    Some(ident.clone())
+                },
+                ref token => return Err(Error::unexpected("an identifier after `with`", token)),
             }
         }
         else {
             None
         };
-    if tokens[0] != Token(FatArrow) {
-        panic!("Expected `=>` but found `{:?}` in view! macro", tokens[0]);
+    let token = token_at(tokens, 0, "`=>`")?;
+    if *token != Token(FatArrow) {
+        return Err(Error::unexpected("`=>`", token));
     }
     tokens = &tokens[1..];
     event.value =
         // Message sent to another widget.
         if tokens.len() >= 2 && tokens[1] == Token(At) {
-            let (event_value, new_tokens) = parse_event_value(&tokens[2..]);
-            let (ident, _) = parse_ident(tokens);
+            let (event_value, new_tokens) = parse_event_value(&tokens[2..])?;
+            let (ident, _) = parse_ident(tokens)?;
             tokens = new_tokens;
             let mut ident_tokens = Tokens::new();
             ident_tokens.append(ident);
@@ -395,47 +726,86 @@ fn parse_event(mut tokens: &[TokenTree], default_param: DefaultParam) -> (Event,
         }
         // Message sent to the same widget.
         else {
-            let (event_value, new_tokens) = parse_event_value(tokens);
+            let (event_value, new_tokens) = parse_event_value(tokens)?;
             tokens = new_tokens;
             CurrentWidget(event_value)
         };
-    (event, tokens)
+    Ok((event, tokens))
 }
 
-fn parse_event_value(tokens: &[TokenTree]) -> (EventValueReturn, &[TokenTree]) {
-    if Token(Ident(syn::Ident::new("return"))) == tokens[0] {
-        let (value, tokens) = parse_value(&tokens[1..]);
-        (CallReturn(value), tokens)
+fn parse_event_value(tokens: &[TokenTree]) -> Result<(EventValueReturn, &[TokenTree])> {
+    match *token_at(tokens, 0, "a message")? {
+        Token(Ident(ref ident)) if *ident == syn::Ident::new("return") => {
+            let (value, tokens) = parse_value(&tokens[1..]);
+            Ok((CallReturn(value), tokens))
+        },
+        TokenTree::Delimited(Delimited { delim: Paren, ref tts }) => {
+            let (value1, new_tts) = parse_value(tts);
+            let token = token_at(new_tts, 0, "`,`")?;
+            if *token != Token(Comma) {
+                return Err(Error::unexpected("`,`", token));
+            }
+            let (value2, _) = parse_value(&new_tts[1..]);
+            Ok((Return(value1, value2), &tokens[1..]))
+        },
+        _ => {
+            let (value, tokens) = parse_value(tokens);
+            Ok((WithoutReturn(value), tokens))
+        },
     }
-    else if let TokenTree::Delimited(Delimited { delim: Paren, ref tts }) = tokens[0] {
-        let (value1, new_tts) = parse_value(tts);
-        if new_tts[0] != Token(Comma) {
-            panic!("Expected `,` but found `{:?}` in view! macro", new_tts[0]);
-        }
-        let (value2, _) = parse_value(&new_tts[1..]);
-        (Return(value1, value2), &tokens[1..])
+}
+
+/*
+ * Parse the model field of `property: <=> model.field`.
+ * The property is also set from the field, like a normal property, so that it is updated when
+ * the field changes.
+ */
+fn parse_binding<'a>(tokens: &'a [TokenTree], property: String, gtk_widget: &mut GtkWidget,
+    properties: &mut HashMap<String, Tokens>) -> Result<&'a [TokenTree]>
+{
+    let (target, tokens) = parse_value(tokens);
+    if target.as_str().is_empty() {
+        return Err(match tokens.first() {
+            Some(token) => Error::unexpected("a model field after `<=>`", token),
+            None => Error::unexpected_end("a model field after `<=>`", tokens),
+        });
     }
-    else {
-        let (value, tokens) = parse_value(tokens);
-        (WithoutReturn(value), tokens)
+    let value =
+        match property.as_str() {
+            "text" => quote! { &#target },
+            "active" | "value" => quote! { #target },
+            _ => return Err(Error::new(format!(
+                "the property `{}` cannot be bound with `<=>`: only text, active and value are supported", property))),
+        };
+    if properties.insert(property.clone(), value).is_some() {
+        return Err(Error::new(format!("the property `{}` is set twice in view! macro", property)));
     }
+    gtk_widget.bindings.insert(property, target);
+    // The widget is saved in the struct to be read when it changes.
+    gtk_widget.save = true;
+    Ok(tokens)
 }
 
 fn parse_value_or_child_properties<'a>(tokens: &'a [TokenTree], ident: String,
-    child_properties: &mut HashMap<String, Tokens>, properties: &mut HashMap<String, Tokens>) -> &'a [TokenTree]
+    child_properties: &mut HashMap<String, Tokens>, properties: &mut HashMap<String, Tokens>)
+    -> Result<&'a [TokenTree]>
 {
-    match tokens[1] {
+    match *token_at(tokens, 1, "a value")? {
         TokenTree::Delimited(Delimited { delim: Brace, tts: ref child_tokens }) => {
-            let props = parse_child_properties(child_tokens);
+            let props = parse_child_properties(child_tokens)?;
             for (key, value) in props {
-                child_properties.insert(key, value);
+                if child_properties.insert(key.clone(), value).is_some() {
+                    return Err(Error::new(format!("the child property `{}` is set twice in view! macro", key)));
+                }
             }
-            &tokens[2..]
+            Ok(&tokens[2..])
         },
         _ => {
             let (value, tts) = parse_value(&tokens[1..]);
-            properties.insert(ident, value);
-            tts
+            if properties.insert(ident.clone(), value).is_some() {
+                return Err(Error::new(format!("the property `{}` is set twice in view! macro", ident)));
+            }
+            Ok(tts)
         },
     }
 }
@@ -478,17 +848,19 @@ fn path_to_string(path: &Path) -> String {
     string
 }
 
-fn parse_attributes(mut tokens: &[TokenTree]) -> (HashMap<&str, Option<&str>>, &[TokenTree]) {
+type Attributes<'a> = HashMap<&'a str, Option<&'a str>>;
+
+fn parse_attributes<'a>(mut tokens: &'a [TokenTree]) -> Result<(Attributes<'a>, &'a [TokenTree])> {
     let mut attributes = HashMap::new();
-    while tokens[0] == Token(Pound) {
+    while *token_at(tokens, 0, "a widget")? == Token(Pound) {
         tokens = &tokens[1..];
-        if let TokenTree::Delimited(Delimited { delim: Bracket, ref tts }) = tokens[0] {
+        if let TokenTree::Delimited(Delimited { delim: Bracket, ref tts }) = *token_at(tokens, 0, "`[`")? {
             tokens = &tokens[1..];
-            if let Token(Ident(ref ident)) = tts[0] {
+            if let Some(&Token(Ident(ref ident))) = tts.first() {
                 let name = ident.as_ref();
                 let value =
                     if let Some(&Token(Eq)) = tts.get(1) {
-                        if let Token(Literal(Str(ref name, Cooked))) = tts[2] {
+                        if let Some(&Token(Literal(Str(ref name, Cooked)))) = tts.get(2) {
                             Some(name.as_str())
                         }
                         else {
@@ -498,81 +870,78 @@ fn parse_attributes(mut tokens: &[TokenTree]) -> (HashMap<&str, Option<&str>>, &
                     else {
                         None
                     };
-                attributes.insert(name, value);
+                if attributes.insert(name, value).is_some() {
+                    return Err(Error::new(format!("the attribute #[{}] is used twice on the same widget", name)));
+                }
             }
         }
     }
-    (attributes, tokens)
+    Ok((attributes, tokens))
 }
 
-fn parse_child_properties(mut tokens: &[TokenTree]) -> HashMap<String, Tokens> {
-    // TODO: panic if the same child properties is set twice.
-    // TODO: same for normal properties?
+fn parse_child_properties(mut tokens: &[TokenTree]) -> Result<HashMap<String, Tokens>> {
     let mut properties = HashMap::new();
     while !tokens.is_empty() {
-        let (ident, _) = parse_ident(tokens);
+        let (ident, new_tokens) = parse_ident(tokens)?;
+        tokens = new_tokens;
+        let token = token_at(tokens, 0, "`:`")?;
+        if *token != Token(Colon) {
+            return Err(Error::unexpected("`:`", token));
+        }
         tokens = &tokens[1..];
-        if let Token(Colon) = tokens[0] {
-            tokens = &tokens[1..];
-            let (value, new_tokens) = parse_value(tokens);
-            tokens = new_tokens;
-            properties.insert(ident, value);
+        let (value, new_tokens) = parse_value(tokens);
+        tokens = new_tokens;
+        if properties.insert(ident.clone(), value).is_some() {
+            return Err(Error::new(format!("the child property `{}` is set twice in view! macro", ident)));
         }
 
         if tokens.first() == Some(&Token(Comma)) {
             tokens = &tokens[1..];
         }
     }
-    properties
+    Ok(properties)
 }
 
-fn parse_relm_widget(tokens: &[TokenTree]) -> (Widget, &[TokenTree]) {
-    let (relm_type, mut tokens) = parse_qualified_name(tokens);
+fn parse_relm_widget(tokens: &[TokenTree]) -> Result<(Widget, &[TokenTree])> {
+    let (relm_type, mut tokens) = parse_qualified_name(tokens)?;
     let mut relm_widget = RelmWidget::new();
     let mut init_parameters = vec![];
     let mut children = vec![];
     let mut properties = HashMap::new();
     let mut child_properties = HashMap::new();
-    if let TokenTree::Delimited(Delimited { delim: Paren, ref tts }) = tokens[0] {
+    if let TokenTree::Delimited(Delimited { delim: Paren, ref tts }) = *token_at(tokens, 0, "`(` or `{`")? {
         let parameters = parse_comma_list(tts);
         init_parameters = parameters;
         tokens = &tokens[1..];
     }
-    if let TokenTree::Delimited(Delimited { delim: Brace, ref tts }) = tokens[0] {
+    if let TokenTree::Delimited(Delimited { delim: Brace, ref tts }) = *token_at(tokens, 0, "`{`")? {
         let mut tts = &tts[..];
         while !tts.is_empty() {
             let is_child =
-                if let Some((_, next_tokens)) = try_parse_name(tts) {
-                    if let TokenTree::Delimited(Delimited { delim: Brace, .. }) = next_tokens[0] {
-                        true
-                    }
-                    else {
-                        false
-                    }
-                }
-                else {
-                    false
+                match try_parse_name(tts).and_then(|(_, next_tokens)| next_tokens.first()) {
+                    Some(TokenTree::Delimited(delimited)) => delimited.delim == Brace,
+                    _ => false,
                 };
             if tts[0] == Token(Pound) || is_child {
-                let (child, new_tts, _) = parse_child(tts, false);
+                let (child, new_tts, _) = parse_child(tts, false)?;
                 tts = new_tts;
                 children.push(child);
             }
             else {
                 // Property or event.
-                let (ident, _) = parse_ident(tts);
-                tts = &tts[1..];
-                match tts[0] {
+                let (ident, new_tts) = parse_ident(tts)?;
+                tts = new_tts;
+                match *token_at(tts, 0, "`:`, `=>` or `(`")? {
                     Token(Colon) => {
-                        tts = parse_value_or_child_properties(tts, ident, &mut child_properties, &mut properties);
+                        tts = parse_value_or_child_properties(tts, ident, &mut child_properties, &mut properties)?;
                     },
                     TokenTree::Delimited(Delimited { delim: Paren, .. }) | Token(FatArrow) => {
-                        let (event, new_tts) = parse_event(&tts[0..], DefaultNoParam);
+                        let (event, new_tts) = parse_event(&tts[0..], DefaultNoParam)?;
                         let mut entry = relm_widget.events.entry(ident).or_insert_with(Vec::new);
                         entry.push(event);
                         tts = new_tts;
                     },
-                    _ => panic!("Expected `:`, `=>` or `(` but found `{:?}` in view! macro", tts[0]),
+                    ref token => return Err(Error::unexpected("`:`, `=>` or `(`", token)),
                 }
             }
 
@@ -582,5 +951,118 @@ fn parse_relm_widget(tokens: &[TokenTree]) -> (Widget, &[TokenTree]) {
         }
     }
     let widget = Widget::new_relm(relm_widget, relm_type, init_parameters, children, properties, child_properties);
-    (widget, &tokens[1..])
+    Ok((widget, &tokens[1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    use syn::parse_token_trees;
+
+    use super::{Widget, parse};
+
+    const GLADE_WINDOW: &str = r#"<object class="GtkWindow" id="window">
+        <child>
+            <object class="GtkButton" id="button"/>
+        </child>
+    </object>"#;
+
+    // Write a glade file with these objects and return its path.
+    fn glade_file(name: &str, objects: &str) -> String {
+        let path = env::temp_dir().join(format!("relm-gen-widget-{}.glade", name));
+        let mut file = File::create(&path).expect("create glade file");
+        write!(file, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<interface>\n{}\n</interface>\n", objects)
+            .expect("write glade file");
+        path.to_str().expect("glade file path").to_string()
+    }
+
+    fn parse_ok(source: &str) -> Widget {
+        let tokens = parse_token_trees(source).expect("token trees");
+        match parse(&tokens) {
+            Ok(widget) => widget,
+            Err(error) => panic!("{} should parse: {}", source, error),
+        }
+    }
+
+    fn parse_error(source: &str) -> String {
+        let tokens = parse_token_trees(source).expect("token trees");
+        match parse(&tokens) {
+            Ok(_) => panic!("{} should not parse", source),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn unexpected_token_location() {
+        assert_eq!(parse_error("gtk::Window { gtk::Label , }"),
+            "expected `{`, found `,` at 1:30 of the view! macro");
+    }
+
+    #[test]
+    fn unexpected_end_location() {
+        assert_eq!(parse_error("gtk::Window { gtk::Label { text: } }"),
+            "expected a value but found the end of the block at 1:39 of the view! macro");
+    }
+
+    #[test]
+    fn view_file_location() {
+        let path = env::temp_dir().join("relm-gen-widget-view-file-location.rs");
+        let mut file = File::create(&path).expect("create view file");
+        file.write_all(b"view! {\n    gtk::Window {\n        gtk::Label ,\n    }\n}\n").expect("write view file");
+        let path = path.to_str().expect("view file path");
+        assert_eq!(parse_error(&format!("{:?}", path)), format!("expected `{{`, found `,` at {}:3:20", path));
+    }
+
+    #[test]
+    fn glade_objects() {
+        let path = glade_file("objects", GLADE_WINDOW);
+        let widget = parse_ok(&format!("glade {:?} {{ button {{ clicked => Increment }} }}", path));
+        assert_eq!(widget.name, "window");
+        let children: Vec<_> = widget.children.iter()
+            .map(|child| {
+                let typ = &child.typ;
+                (child.name.to_string(), quote! { #typ }.to_string())
+            })
+            .collect();
+        assert_eq!(children, vec![("button".to_string(), quote! { gtk::Button }.to_string())]);
+    }
+
+    #[test]
+    fn glade_bindings_errors() {
+        let path = glade_file("bindings-errors", GLADE_WINDOW);
+        let glade_error = |bindings: &str| parse_error(&format!("glade {:?} {{ {} }}", path, bindings));
+        assert_eq!(glade_error("label { }"), format!("no object with id `label` in {}", path));
+        assert_eq!(glade_error("button { }, button { }"), "the glade object `button` is bound twice in view! macro");
+        assert_eq!(glade_error("button { packing: { expand: true } }"),
+            "child properties cannot be set on the glade object `button`: use <packing> in the glade file");
+    }
+
+    #[test]
+    fn glade_class_without_type() {
+        let path = glade_file("class-without-type", r#"<object class="WebKitWebView" id="web_view"/>"#);
+        assert_eq!(parse_error(&format!("glade {:?}", path)),
+            "cannot infer the type of the glade class WebKitWebView: specify it with `id: Type { }` in view! macro");
+        let widget = parse_ok(&format!("glade {:?} {{ web_view: webkit2gtk::WebView {{ }} }}", path));
+        let typ = &widget.typ;
+        assert_eq!(quote! { #typ }.to_string(), quote! { webkit2gtk::WebView }.to_string());
+    }
+
+    #[test]
+    fn glade_without_toplevel_id() {
+        let path = glade_file("without-toplevel-id", r#"<object class="GtkWindow">
+            <child>
+                <object class="GtkButton" id="button"/>
+            </child>
+        </object>"#);
+        assert_eq!(parse_error(&format!("glade {:?}", path)), format!("no toplevel object with an id in {}", path));
+    }
+
+    #[test]
+    fn glade_file_name() {
+        assert_eq!(parse_error("glade window"), "expected a glade file name, found `window` at 1:7 of the view! macro");
+        assert!(parse_error("glade \"/nonexistent/relm.glade\"").starts_with("cannot open /nonexistent/relm.glade: "));
+    }
 }
@@ -0,0 +1,203 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+/*
+ * Generation of the view description used by #[widget(vdom)].
+ *
+ * The view! macro is converted to an expression building a ::relm::VWidget tree, which is evaluated
+ * in view() and after every update() to patch the widgets.
+ */
+
+use quote::Tokens;
+use syn::Ident;
+
+use error::{Error, Result};
+use parser::{GtkWidget, Widget};
+use parser::EitherWidget::{Flow, Gtk, Relm};
+use parser::EventValue::{CurrentWidget, ForeignWidget};
+use parser::EventValueReturn::{CallReturn, Return, WithoutReturn};
+use parser::FlowWidget::{For, If, Match};
+
+const NODE_IDENT: &str = "__relm_node";
+
+pub fn gen_description(widget: &Widget) -> Result<Tokens> {
+    match widget.widget {
+        Gtk(ref gtk_widget) => gen_node(widget, gtk_widget, true),
+        Flow(_) | Relm(_) => Err(Error::new("the root of the view must be a GTK+ widget in a #[widget(vdom)]")),
+    }
+}
+
+fn gen_children(children: &[Widget]) -> Result<Vec<Tokens>> {
+    children.iter()
+        .map(gen_child)
+        .collect()
+}
+
+fn gen_child(widget: &Widget) -> Result<Tokens> {
+    let node_ident = Ident::new(NODE_IDENT);
+    match widget.widget {
+        Flow(For(ref pattern, ref iterator, ref children)) => {
+            let children = gen_children(children)?;
+            Ok(quote! {
+                for #pattern in #iterator {
+                    #(#children)*
+                }
+            })
+        },
+        Flow(If(ref condition, ref then, ref otherwise)) => {
+            let then = gen_children(then)?;
+            let otherwise = gen_children(otherwise)?;
+            Ok(quote! {
+                if #condition {
+                    #(#then)*
+                }
+                else {
+                    #(#otherwise)*
+                }
+            })
+        },
+        Flow(Match(ref expr, ref arms)) => {
+            let mut patterns = vec![];
+            let mut bodies = vec![];
+            for (pattern, children) in arms {
+                let children = gen_children(children)?;
+                patterns.push(pattern);
+                bodies.push(quote! {
+                    #(#children)*
+                });
+            }
+            Ok(quote! {
+                match #expr {
+                    #(#patterns => { #bodies })*
+                }
+            })
+        },
+        Gtk(ref gtk_widget) => {
+            let node = gen_node(widget, gtk_widget, false)?;
+            Ok(quote! {
+                #node_ident.add_child(#node);
+            })
+        },
+        Relm(_) => Err(Error::new(format!(
+            "the relm widget {} cannot be used in a #[widget(vdom)]: only GTK+ widgets are supported", widget.name))),
+    }
+}
+
+fn gen_events(widget: &Widget, gtk_widget: &GtkWidget) -> Result<Vec<Tokens>> {
+    let node_ident = Ident::new(NODE_IDENT);
+    let typ = &widget.typ;
+    let mut events = vec![];
+    for (name, event) in &gtk_widget.events {
+        if event.model_ident.is_some() {
+            return Err(Error::new(format!("the event `{}` cannot use `with` in a #[widget(vdom)]", name)));
+        }
+        let event_ident = Ident::new(format!("connect_{}", name));
+        let event_params: Vec<_> = event.params.iter().map(|ident| Ident::new(ident.as_ref())).collect();
+        let handler =
+            match event.value {
+                CurrentWidget(WithoutReturn(ref event_value)) => quote! {
+                    __relm_stream.emit(#event_value);
+                },
+                CurrentWidget(Return(ref event_value, ref return_value)) =>
+                    gen_emit_return(quote! { (#event_value, #return_value) }),
+                CurrentWidget(CallReturn(ref func)) => gen_emit_return(func.clone()),
+                ForeignWidget(_, _) => return Err(Error::new(format!(
+                    "the event `{}` cannot send a message to another widget in a #[widget(vdom)]", name))),
+            };
+        // The events are connected again after every update, but the messages are created when the
+        // event is emitted, so they cannot borrow the model.
+        events.push(quote! {
+            {
+                let relm = relm.clone();
+                #node_ident.connect(move |widget: &#typ| {
+                    let __relm_stream = relm.stream().clone();
+                    widget.#event_ident(move |#(#event_params),*| {
+                        #handler
+                    })
+                });
+            }
+        });
+    }
+    Ok(events)
+}
+
+/*
+ * Emit the message of a (Option<MSG>, ReturnValue) and return the value to the GTK+ callback, like
+ * connect!().
+ */
+fn gen_emit_return(msg: Tokens) -> Tokens {
+    quote! {
+        let (msg, return_value) = #msg;
+        let msg: Option<_> = msg.into();
+        if let Some(msg) = msg {
+            __relm_stream.emit(msg);
+        }
+        return_value
+    }
+}
+
+fn gen_node(widget: &Widget, gtk_widget: &GtkWidget, root: bool) -> Result<Tokens> {
+    if gtk_widget.builder_id.is_some() {
+        return Err(Error::new("a glade file cannot be used in a #[widget(vdom)]"));
+    }
+    if !widget.init_parameters.is_empty() {
+        return Err(Error::new(format!(
+            "the widget {} cannot have constructor parameters in a #[widget(vdom)]: use properties instead",
+            widget.name)));
+    }
+    if !gtk_widget.bindings.is_empty() {
+        return Err(Error::new("the properties cannot be bound with `<=>` in a #[widget(vdom)]"));
+    }
+    if widget.container_type.is_some() {
+        return Err(Error::new("the #[container] attribute cannot be used in a #[widget(vdom)]"));
+    }
+    let node_ident = Ident::new(NODE_IDENT);
+    let typ = &widget.typ;
+    let properties = widget.properties.iter()
+        .map(|(name, value)| quote! {
+            #node_ident.property(#name, (#value).to_owned());
+        });
+    // The child properties of the root are set in on_add().
+    let child_properties: Vec<_> =
+        if root {
+            vec![]
+        }
+        else {
+            widget.child_properties.iter()
+                .map(|(name, value)| quote! {
+                    #node_ident.child_property(#name, (#value).to_owned());
+                })
+                .collect()
+        };
+    let events = gen_events(widget, gtk_widget)?;
+    let children = gen_children(&widget.children)?;
+    Ok(quote! {
+        {
+            #[allow(unused_mut)]
+            let mut #node_ident = ::relm::VWidget::new::<#typ>();
+            #(#properties)*
+            #(#child_properties)*
+            #(#events)*
+            #(#children)*
+            #node_ident
+        }
+    })
+}
@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Search of the widgets in a widget tree.
+
+use glib::translate::FromGlibPtrNone;
+use gtk::{
+    self,
+    ButtonExt,
+    Cast,
+    ContainerExt,
+    IsA,
+    WidgetExt,
+};
+use gtk_sys;
+
+/// Get the first widget of type `WIDGET` in the tree of `parent`, including `parent`.
+pub fn find_by_type<WIDGET, PARENT>(parent: &PARENT) -> Option<WIDGET>
+    where WIDGET: IsA<gtk::Widget>,
+          PARENT: IsA<gtk::Widget>,
+{
+    find_all_by_type(parent).into_iter().next()
+}
+
+/// Get all the widgets of type `WIDGET` in the tree of `parent`, including `parent`, in depth-first
+/// order.
+pub fn find_all_by_type<WIDGET, PARENT>(parent: &PARENT) -> Vec<WIDGET>
+    where WIDGET: IsA<gtk::Widget>,
+          PARENT: IsA<gtk::Widget>,
+{
+    widgets(parent).into_iter()
+        .filter_map(|widget| widget.downcast::<WIDGET>().ok())
+        .collect()
+}
+
+/// Get the first widget whose name, set with `WidgetExt::set_name()`, is `name`.
+pub fn find_by_name<PARENT: IsA<gtk::Widget>>(parent: &PARENT, name: &str) -> Option<gtk::Widget> {
+    widgets(parent).into_iter()
+        .find(|widget| widget.get_name().as_ref().map(String::as_str) == Some(name))
+}
+
+/// Get the first `gtk::Label` whose text is `label` or button whose label is `label`.
+pub fn find_by_label<PARENT: IsA<gtk::Widget>>(parent: &PARENT, label: &str) -> Option<gtk::Widget> {
+    widgets(parent).into_iter()
+        .find(|widget| {
+            let text =
+                if let Some(widget_label) = widget.clone().downcast::<gtk::Label>().ok() {
+                    widget_label.get_text()
+                }
+                else if let Some(button) = widget.clone().downcast::<gtk::Button>().ok() {
+                    button.get_label()
+                }
+                else {
+                    None
+                };
+            text.as_ref().map(String::as_str) == Some(label)
+        })
+}
+
+/// Get all the widgets in the tree of `parent`, including `parent`, in depth-first order.
+pub fn widgets<PARENT: IsA<gtk::Widget>>(parent: &PARENT) -> Vec<gtk::Widget> {
+    let mut result = vec![];
+    let parent: *mut gtk_sys::GtkWidget = parent.to_glib_none().0;
+    let parent = unsafe { gtk::Widget::from_glib_none(parent) };
+    add_widgets(parent, &mut result);
+    result
+}
+
+fn add_widgets(widget: gtk::Widget, result: &mut Vec<gtk::Widget>) {
+    result.push(widget.clone());
+    if let Ok(container) = widget.downcast::<gtk::Container>() {
+        for child in container.get_children() {
+            add_widgets(child, result);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Property-based testing of the `update()` method with random sequences of messages.
+
+use std::env;
+
+use relm::{DisplayVariant, TestRuntime, Widget};
+
+/// Environment variable to set the seed of the `Fuzzer`, to reproduce a failure or to try other
+/// sequences.
+pub const FUZZ_SEED: &'static str = "RELM_FUZZ_SEED";
+
+/// Seed used when `RELM_FUZZ_SEED` is not set, so that the tests are deterministic.
+const DEFAULT_SEED: u64 = 0x5EED;
+
+/// Pseudo-random number generator (xorshift64*) given to the strategies.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from `seed`.
+    pub fn new(seed: u64) -> Self {
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Rng {
+            // The state must not be zero.
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    /// Choose an element of `items`, which must not be empty.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.gen_range(0, items.len())]
+    }
+
+    /// Generate a `bool` which is `true` with the probability `probability`.
+    pub fn gen_bool(&mut self, probability: f64) -> bool {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 <= probability
+    }
+
+    /// Generate a number in the range [`low`, `high`[.
+    pub fn gen_range(&mut self, low: usize, high: usize) -> usize {
+        assert!(low < high, "Rng::gen_range() called with an empty range");
+        low + (self.next_u64() % (high - low) as u64) as usize
+    }
+
+    /// Generate a random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+/// Generator of messages.
+///
+/// It is implemented for the closures taking the `Rng`:
+/// ```ignore
+/// |rng: &mut Rng| rng.choose(&[Increment, Decrement]).clone()
+/// ```
+pub trait Strategy<MSG> {
+    /// Generate a message.
+    fn generate(&self, rng: &mut Rng) -> MSG;
+}
+
+impl<MSG, F: Fn(&mut Rng) -> MSG> Strategy<MSG> for F {
+    fn generate(&self, rng: &mut Rng) -> MSG {
+        self(rng)
+    }
+}
+
+struct Invariant<MODEL> {
+    check: Box<Fn(&MODEL) -> bool>,
+    name: &'static str,
+}
+
+// The invariant broken by a sequence of messages, and the number of messages sent before.
+struct Failure {
+    invariant: &'static str,
+    step: usize,
+}
+
+/// Run random sequences of messages through a component, with a [`TestRuntime`](../relm/struct.TestRuntime.html),
+/// checking the invariants of its model after each message:
+/// ```ignore
+/// Fuzzer::<Win>::new(())
+///     .invariant("counter in bounds", |model| model.counter >= 0 && model.counter <= 10)
+///     .run(|rng: &mut Rng| rng.choose(&[Increment, Decrement]).clone());
+/// ```
+///
+/// When an invariant is broken, the sequence is shrunk to a minimal sequence of messages breaking it,
+/// which is printed in the panic message with the seed to reproduce it.
+pub struct Fuzzer<WIDGET: Widget> {
+    cases: usize,
+    invariants: Vec<Invariant<WIDGET::Model>>,
+    max_length: usize,
+    model_param: WIDGET::ModelParam,
+    seed: u64,
+}
+
+impl<WIDGET> Fuzzer<WIDGET>
+    where WIDGET: Widget + Clone + 'static,
+          WIDGET::ModelParam: Clone,
+          WIDGET::Msg: Clone + DisplayVariant + 'static,
+{
+    /// Create a fuzzer for components created with `model_param`.
+    ///
+    /// The seed is read from the `RELM_FUZZ_SEED` environment variable, or is a fixed seed when it
+    /// is not set.
+    pub fn new(model_param: WIDGET::ModelParam) -> Self {
+        let seed =
+            match env::var(FUZZ_SEED) {
+                Ok(seed) => seed.parse()
+                    .unwrap_or_else(|_| panic!("{} must be an integer, but is \"{}\"", FUZZ_SEED, seed)),
+                Err(_) => DEFAULT_SEED,
+            };
+        Fuzzer {
+            cases: 100,
+            invariants: vec![],
+            max_length: 50,
+            model_param: model_param,
+            seed: seed,
+        }
+    }
+
+    /// Set the number of sequences to run (100 by default).
+    pub fn cases(mut self, cases: usize) -> Self {
+        self.cases = cases;
+        self
+    }
+
+    /// Add an invariant, which must return `true` for the initial model and after every message.
+    pub fn invariant<CHECK: Fn(&WIDGET::Model) -> bool + 'static>(mut self, name: &'static str, check: CHECK) -> Self {
+        self.invariants.push(Invariant {
+            check: Box::new(check),
+            name: name,
+        });
+        self
+    }
+
+    /// Set the maximum length of the sequences (50 by default).
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Set the seed of the random number generator.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Run the sequences generated by `strategy`.
+    ///
+    /// Panics if an invariant is broken.
+    pub fn run<STRATEGY: Strategy<WIDGET::Msg>>(&self, strategy: STRATEGY) {
+        let mut rng = Rng::new(self.seed);
+        for _ in 0..self.cases {
+            let length = rng.gen_range(1, self.max_length + 1);
+            let messages: Vec<_> = (0..length).map(|_| strategy.generate(&mut rng)).collect();
+            if let Some(failure) = self.execute(&messages) {
+                let (messages, failure) = self.shrink(messages, failure);
+                let names: Vec<_> = messages[..failure.step].iter()
+                    .map(|message| message.display_variant())
+                    .collect();
+                panic!("invariant \"{}\" broken after the messages [{}] (seed {})", failure.invariant,
+                    names.join(", "), self.seed);
+            }
+        }
+    }
+
+    // Send the messages to a new component until an invariant is broken.
+    fn execute(&self, messages: &[WIDGET::Msg]) -> Option<Failure> {
+        let runtime = TestRuntime::new().expect("cannot create the test runtime");
+        let component = runtime.component::<WIDGET>(self.model_param.clone());
+        for step in 0..messages.len() + 1 {
+            if step > 0 {
+                component.send(messages[step - 1].clone());
+                runtime.run_until_stalled();
+            }
+            let model = component.model();
+            if let Some(invariant) = self.invariants.iter().find(|invariant| !(invariant.check)(&*model)) {
+                return Some(Failure {
+                    invariant: invariant.name,
+                    step: step,
+                });
+            }
+        }
+        None
+    }
+
+    // Remove chunks of messages, from the largest to single messages, as long as the sequence still
+    // breaks an invariant.
+    fn shrink(&self, mut messages: Vec<WIDGET::Msg>, mut failure: Failure) -> (Vec<WIDGET::Msg>, Failure) {
+        messages.truncate(failure.step);
+        let mut chunk_size = messages.len() / 2;
+        while chunk_size > 0 {
+            let mut start = 0;
+            while start < messages.len() {
+                let end = (start + chunk_size).min(messages.len());
+                let mut candidate = messages[..start].to_vec();
+                candidate.extend_from_slice(&messages[end..]);
+                match self.execute(&candidate) {
+                    Some(new_failure) => {
+                        candidate.truncate(new_failure.step);
+                        messages = candidate;
+                        failure = new_failure;
+                    },
+                    None => start += chunk_size,
+                }
+            }
+            chunk_size /= 2;
+        }
+        (messages, failure)
+    }
+}
@@ -0,0 +1,192 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Simulation of the user input.
+
+use gdk_sys::{
+    self,
+    GdkEvent,
+    GdkEventAny,
+    GdkEventButton,
+    GdkEventKey,
+    GdkEventMotion,
+    GdkEventType,
+    GdkWindow,
+};
+use gobject_sys;
+use gtk::{
+    self,
+    ComboBoxExt,
+    EditableExt,
+    IsA,
+    ToggleButtonExt,
+};
+use gtk_sys;
+
+use super::run_loop;
+
+/// Type `text` in an editable widget like a `gtk::Entry`, one character at a time, at the
+/// position of the cursor.
+pub fn enter_text<E: IsA<gtk::Editable> + EditableExt>(entry: &E, text: &str) {
+    for character in text.chars() {
+        let mut position = entry.get_position();
+        let text = character.to_string();
+        entry.insert_text(&text, text.len() as i32, &mut position);
+        entry.set_position(position);
+        run_loop();
+    }
+}
+
+/// Insert `text` at the cursor of a `gtk::TextView`.
+pub fn enter_text_view(text_view: &gtk::TextView, text: &str) {
+    if let Some(buffer) = text_view.get_buffer() {
+        buffer.insert_at_cursor(text);
+    }
+    run_loop();
+}
+
+/// Send a key press event for the key `keyval` (for instance, `gdk::enums::key::Return`) to `widget`.
+pub fn key_press<W: IsA<gtk::Widget>>(widget: &W, keyval: u32) {
+    send_key_event(widget, gdk_sys::GDK_KEY_PRESS, keyval);
+}
+
+/// Send a key release event for the key `keyval` to `widget`.
+pub fn key_release<W: IsA<gtk::Widget>>(widget: &W, keyval: u32) {
+    send_key_event(widget, gdk_sys::GDK_KEY_RELEASE, keyval);
+}
+
+/// Press and release the key `keyval` on `widget`.
+pub fn press_key<W: IsA<gtk::Widget>>(widget: &W, keyval: u32) {
+    key_press(widget, keyval);
+    key_release(widget, keyval);
+}
+
+/// Send a press event of the mouse `button` (1 is the left button) at the position (`x`, `y`)
+/// relative to `widget`.
+pub fn mouse_press<W: IsA<gtk::Widget>>(widget: &W, button: u32, x: f64, y: f64) {
+    send_button_event(widget, gdk_sys::GDK_BUTTON_PRESS, button, x, y);
+}
+
+/// Send a release event of the mouse `button` at the position (`x`, `y`) relative to `widget`.
+pub fn mouse_release<W: IsA<gtk::Widget>>(widget: &W, button: u32, x: f64, y: f64) {
+    send_button_event(widget, gdk_sys::GDK_BUTTON_RELEASE, button, x, y);
+}
+
+/// Press and release the mouse `button` at the position (`x`, `y`) relative to `widget`.
+pub fn mouse_click<W: IsA<gtk::Widget>>(widget: &W, button: u32, x: f64, y: f64) {
+    mouse_press(widget, button, x, y);
+    mouse_release(widget, button, x, y);
+}
+
+/// Send a motion event of the mouse to the position (`x`, `y`) relative to `widget`.
+pub fn mouse_move<W: IsA<gtk::Widget>>(widget: &W, x: f64, y: f64) {
+    unsafe {
+        let event = new_event(widget, gdk_sys::GDK_MOTION_NOTIFY);
+        {
+            let motion = &mut *(event as *mut GdkEventMotion);
+            motion.x = x;
+            motion.y = y;
+        }
+        send_event(widget, event);
+    }
+}
+
+/// Select the item at `index` in a `gtk::ComboBox`.
+pub fn select_combo<C: ComboBoxExt>(combo: &C, index: i32) {
+    combo.set_active(index);
+    run_loop();
+}
+
+/// Select the row at `path`, the indices of the row and its parents, in a `gtk::TreeView`.
+pub fn select_row(tree_view: &gtk::TreeView, path: &[i32]) {
+    let mut tree_path = gtk::TreePath::new();
+    for &index in path {
+        tree_path.append_index(index);
+    }
+    tree_view.set_cursor(&tree_path, None, false);
+    run_loop();
+}
+
+/// Toggle a `gtk::ToggleButton` or a `gtk::CheckButton`.
+pub fn toggle<T: ToggleButtonExt>(button: &T) {
+    button.set_active(!button.get_active());
+    run_loop();
+}
+
+/*
+ * Create an event for the window of the widget.
+ * The event must be freed by send_event().
+ */
+unsafe fn new_event<W: IsA<gtk::Widget>>(widget: &W, typ: GdkEventType) -> *mut GdkEvent {
+    let event = gdk_sys::gdk_event_new(typ);
+    let window = gtk_sys::gtk_widget_get_window(widget.to_glib_none().0);
+    if !window.is_null() {
+        // The window is unreferenced by gdk_event_free().
+        let any = &mut *(event as *mut GdkEventAny);
+        any.window = gobject_sys::g_object_ref(window as *mut _) as *mut GdkWindow;
+        any.send_event = 1;
+    }
+    // The event handlers of GTK+ ignore the events without a device.
+    let display = gdk_sys::gdk_display_get_default();
+    if !display.is_null() {
+        let device_manager = gdk_sys::gdk_display_get_device_manager(display);
+        let pointer = gdk_sys::gdk_device_manager_get_client_pointer(device_manager);
+        let device =
+            match typ {
+                gdk_sys::GDK_KEY_PRESS | gdk_sys::GDK_KEY_RELEASE => gdk_sys::gdk_device_get_associated_device(pointer),
+                _ => pointer,
+            };
+        if !device.is_null() {
+            gdk_sys::gdk_event_set_device(event, device);
+        }
+    }
+    event
+}
+
+unsafe fn send_event<W: IsA<gtk::Widget>>(widget: &W, event: *mut GdkEvent) {
+    let _ = gtk_sys::gtk_widget_event(widget.to_glib_none().0, event);
+    gdk_sys::gdk_event_free(event);
+    run_loop();
+}
+
+fn send_button_event<W: IsA<gtk::Widget>>(widget: &W, typ: GdkEventType, button: u32, x: f64, y: f64) {
+    unsafe {
+        let event = new_event(widget, typ);
+        {
+            let button_event = &mut *(event as *mut GdkEventButton);
+            button_event.button = button;
+            button_event.x = x;
+            button_event.y = y;
+        }
+        send_event(widget, event);
+    }
+}
+
+fn send_key_event<W: IsA<gtk::Widget>>(widget: &W, typ: GdkEventType, keyval: u32) {
+    unsafe {
+        let event = new_event(widget, typ);
+        {
+            let key_event = &mut *(event as *mut GdkEventKey);
+            key_event.keyval = keyval;
+        }
+        send_event(widget, event);
+    }
+}
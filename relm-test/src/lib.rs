@@ -1,8 +1,41 @@
+extern crate gdk_sys;
+extern crate glib;
 extern crate glib_sys;
+extern crate gobject_sys;
 extern crate gtk;
+extern crate gtk_sys;
+extern crate relm;
 
+mod find;
+mod fuzz;
+mod input;
+mod snapshot;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use glib::Continue;
 use gtk::ButtonExt;
 
+pub use find::{find_all_by_type, find_by_label, find_by_name, find_by_type, widgets};
+pub use fuzz::{FUZZ_SEED, Fuzzer, Rng, Strategy};
+pub use input::{
+    enter_text,
+    enter_text_view,
+    key_press,
+    key_release,
+    mouse_click,
+    mouse_move,
+    mouse_press,
+    mouse_release,
+    press_key,
+    select_combo,
+    select_row,
+    toggle,
+};
+pub use snapshot::{UPDATE_SNAPSHOTS, assert_snapshot, snapshot};
+
 #[macro_export]
 macro_rules! assert_text {
     ($widget:expr, $string:expr) => {
@@ -10,6 +43,17 @@ macro_rules! assert_text {
     };
 }
 
+/// Compare the snapshot of a widget tree to the file `tests/snapshots/$name.snap` of the crate
+/// being tested.
+///
+/// See [`assert_snapshot()`](fn.assert_snapshot.html) to update the snapshot.
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($widget:expr, $name:expr) => {
+        $crate::assert_snapshot($widget, concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/", $name, ".snap"));
+    };
+}
+
 /// Simulate a click on a button.
 pub fn click<B: ButtonExt>(button: &B) {
     // TODO: look at how this is implemented to support other widgets.
@@ -17,9 +61,48 @@ pub fn click<B: ButtonExt>(button: &B) {
     run_loop();
 }
 
+/// Process the pending events of the main loop, without waiting for new ones.
+///
+/// To wait for an event which is not pending yet, like a timer or the result of a future, use
+/// [`wait_until()`](fn.wait_until.html).
 pub fn run_loop() {
-    unsafe { glib_sys::g_usleep(1000) };
     while gtk::events_pending() {
         gtk::main_iteration();
     }
 }
+
+/// Run the main loop until `condition` returns `true`.
+///
+/// Between two checks of the condition, the main loop waits for the next event, like a message or
+/// a timer, instead of sleeping.
+/// Returns `false` if the condition is still not met after `timeout`.
+pub fn wait_until<CONDITION: FnMut() -> bool>(mut condition: CONDITION, timeout: Duration) -> bool {
+    let start = Instant::now();
+    loop {
+        while gtk::events_pending() {
+            gtk::main_iteration();
+        }
+        if condition() {
+            return true;
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return false;
+        }
+        // Wake up the main loop at the timeout if no other event comes.
+        let remaining = timeout - elapsed;
+        let millis = remaining.as_secs() as u32 * 1000 + remaining.subsec_nanos() / 1_000_000;
+        let timed_out = Rc::new(Cell::new(false));
+        let source = {
+            let timed_out = timed_out.clone();
+            gtk::timeout_add(millis.max(1), move || {
+                timed_out.set(true);
+                Continue(false)
+            })
+        };
+        gtk::main_iteration_do(true);
+        if !timed_out.get() {
+            glib::source_remove(source);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Snapshots of widget trees.
+
+use std::env;
+use std::ffi::{CStr, CString};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::mem;
+use std::os::raw::{c_char, c_uint};
+use std::path::Path;
+
+use glib::translate::{FromGlibPtrNone, ToGlibPtr};
+use glib_sys;
+use gobject_sys::{self, GObject, GObjectClass, GParamSpec, GTypeInstance, GValue};
+use gtk::{self, Cast, ContainerExt, IsA};
+use gtk_sys;
+
+// Missing from gtk-sys.
+extern "C" {
+    fn gtk_container_class_list_child_properties(cclass: *mut GObjectClass, n_properties: *mut c_uint)
+        -> *mut *mut GParamSpec;
+}
+
+/// Environment variable which, when set, makes `assert_snapshot()` overwrite the snapshot files
+/// instead of comparing them.
+pub const UPDATE_SNAPSHOTS: &'static str = "RELM_UPDATE_SNAPSHOTS";
+
+/// Properties of the widgets written in the snapshots, when the widget has them.
+const PROPERTIES: &'static [&'static str] = &["active", "label", "orientation", "sensitive", "text", "title", "visible"];
+
+/// Compare the snapshot of `widget` to the content of the file at `path`.
+///
+/// Panics if the snapshot is different or if the file does not exist.
+/// The file is written instead when the `RELM_UPDATE_SNAPSHOTS` environment variable is set, to
+/// create or update the snapshot.
+pub fn assert_snapshot<W: IsA<gtk::Widget>, P: AsRef<Path>>(widget: &W, path: P) {
+    let update = env::var_os(UPDATE_SNAPSHOTS).is_some();
+    compare_snapshot(&snapshot(widget), path.as_ref(), update);
+}
+
+fn compare_snapshot(actual: &str, path: &Path, update: bool) {
+    if !update {
+        let mut file = File::open(path)
+            .unwrap_or_else(|error| panic!("cannot open the snapshot {}: {} (set {}=1 to create it)", path.display(),
+                error, UPDATE_SNAPSHOTS));
+        let mut expected = String::new();
+        file.read_to_string(&mut expected)
+            .unwrap_or_else(|error| panic!("cannot read the snapshot {}: {}", path.display(), error));
+        if actual != expected {
+            panic!("the snapshot {} does not match (set {}=1 to update it):\n{}", path.display(),
+                UPDATE_SNAPSHOTS, diff(&expected, actual));
+        }
+        return;
+    }
+    if let Some(directory) = path.parent() {
+        let _ = fs::create_dir_all(directory);
+    }
+    File::create(path)
+        .and_then(|mut file| file.write_all(actual.as_bytes()))
+        .unwrap_or_else(|error| panic!("cannot write the snapshot {}: {}", path.display(), error));
+}
+
+/// Serialize the tree of `widget`, one widget per line, indented by its depth.
+///
+/// A line contains the type of the widget, followed by its name after a `#` if it was set with
+/// `WidgetExt::set_name()`, its main properties and, between brackets, the child properties set
+/// by its parent:
+/// ```text
+/// GtkBox#buttons orientation=GTK_ORIENTATION_VERTICAL sensitive=TRUE visible=TRUE
+///   GtkButton label="+" sensitive=TRUE visible=TRUE [expand=FALSE fill=TRUE pack-type=GTK_PACK_START padding=0 position=0]
+/// ```
+pub fn snapshot<W: IsA<gtk::Widget>>(widget: &W) -> String {
+    let mut result = String::new();
+    let widget: *mut gtk_sys::GtkWidget = widget.to_glib_none().0;
+    let widget = unsafe { gtk::Widget::from_glib_none(widget) };
+    write_widget(&widget, None, 0, &mut result);
+    result
+}
+
+fn write_widget(widget: &gtk::Widget, parent: Option<&gtk::Container>, depth: usize, result: &mut String) {
+    let object: *mut GObject = widget.to_glib_none().0;
+    for _ in 0..depth {
+        result.push_str("  ");
+    }
+    let type_name = unsafe {
+        let name = gobject_sys::g_type_name_from_instance(object as *mut GTypeInstance);
+        CStr::from_ptr(name).to_string_lossy().into_owned()
+    };
+    result.push_str(&type_name);
+    let name = unsafe { from_c_string(gtk_sys::gtk_widget_get_name(object as *mut _)) };
+    // The name defaults to the type name.
+    if let Some(name) = name {
+        if name != type_name {
+            result.push('#');
+            result.push_str(&name);
+        }
+    }
+
+    let class = unsafe { (*(object as *mut GTypeInstance)).g_class as *mut GObjectClass };
+    for &property in PROPERTIES {
+        let property = CString::new(property).unwrap();
+        let pspec = unsafe { gobject_sys::g_object_class_find_property(class, property.as_ptr()) };
+        if !pspec.is_null() {
+            let value = unsafe {
+                value_contents(pspec, |value| gobject_sys::g_object_get_property(object, property.as_ptr(), value))
+            };
+            result.push_str(&format!(" {}={}", property.to_string_lossy(), value));
+        }
+    }
+
+    if let Some(parent) = parent {
+        let child_properties = child_properties(parent, widget);
+        if !child_properties.is_empty() {
+            result.push_str(&format!(" [{}]", child_properties.join(" ")));
+        }
+    }
+    result.push('\n');
+
+    if let Ok(container) = widget.clone().downcast::<gtk::Container>() {
+        for child in container.get_children() {
+            write_widget(&child, Some(&container), depth + 1, result);
+        }
+    }
+}
+
+// Get the child properties of `child`, sorted by name.
+fn child_properties(parent: &gtk::Container, child: &gtk::Widget) -> Vec<String> {
+    let container: *mut gtk_sys::GtkContainer = parent.to_glib_none().0;
+    let child: *mut gtk_sys::GtkWidget = child.to_glib_none().0;
+    let mut properties = vec![];
+    unsafe {
+        let class = (*(container as *mut GTypeInstance)).g_class as *mut GObjectClass;
+        let mut count: c_uint = 0;
+        let pspecs = gtk_container_class_list_child_properties(class, &mut count);
+        for i in 0..count as isize {
+            let pspec = *pspecs.offset(i);
+            let name = (*pspec).name;
+            let value = value_contents(pspec,
+                |value| gtk_sys::gtk_container_child_get_property(container, child, name, value));
+            properties.push(format!("{}={}", CStr::from_ptr(name).to_string_lossy(), value));
+        }
+        glib_sys::g_free(pspecs as *mut _);
+    }
+    properties.sort();
+    properties
+}
+
+// Show the first line which differs between the snapshots.
+fn diff(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => return String::new(),
+            (Some(expected), Some(actual)) if expected == actual => (),
+            (expected, actual) =>
+                return format!("line {}:\n- {}\n+ {}", line, expected.unwrap_or("<end of snapshot>"),
+                    actual.unwrap_or("<end of snapshot>")),
+        }
+        line += 1;
+    }
+}
+
+unsafe fn from_c_string(string: *const c_char) -> Option<String> {
+    if string.is_null() {
+        None
+    }
+    else {
+        Some(CStr::from_ptr(string).to_string_lossy().into_owned())
+    }
+}
+
+// Get the value of a property, as formatted by GLib, with `get` filling the value.
+unsafe fn value_contents<F: FnOnce(*mut GValue)>(pspec: *mut GParamSpec, get: F) -> String {
+    let mut value: GValue = mem::zeroed();
+    gobject_sys::g_value_init(&mut value, (*pspec).value_type);
+    get(&mut value);
+    let contents = gobject_sys::g_strdup_value_contents(&value);
+    let result = from_c_string(contents).unwrap_or_default();
+    glib_sys::g_free(contents as *mut _);
+    gobject_sys::g_value_unset(&mut value);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use super::compare_snapshot;
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("relm-test-{}.snap", name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    #[should_panic(expected = "(set RELM_UPDATE_SNAPSHOTS=1 to create it)")]
+    fn missing_snapshot() {
+        compare_snapshot("GtkLabel\n", &snapshot_path("missing"), false);
+    }
+
+    #[test]
+    fn create_snapshot() {
+        let path = snapshot_path("create");
+        compare_snapshot("GtkLabel\n", &path, true);
+        let mut content = String::new();
+        File::open(&path).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "GtkLabel\n");
+        compare_snapshot("GtkLabel\n", &path, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "line 2:\n-   GtkLabel\n+   GtkButton")]
+    fn different_snapshot() {
+        let path = snapshot_path("different");
+        compare_snapshot("GtkBox\n  GtkLabel\n", &path, true);
+        compare_snapshot("GtkBox\n  GtkButton\n", &path, false);
+    }
+}
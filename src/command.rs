@@ -0,0 +1,201 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use futures::{Future, Stream};
+
+use super::{DisplayVariant, Handle, Relm, SubscriptionHandle};
+use stream::ToStream;
+
+/// Batch of `Future`s and `Stream`s to spawn once the
+/// [`update_with_commands()`](trait.Widget.html#method.update_with_commands) method returns.
+pub struct Commands<MSG: DisplayVariant> {
+    futures: Vec<(Box<Future<Item=(), Error=()>>, SubscriptionHandle)>,
+    relm: Relm<MSG>,
+}
+
+impl<MSG: DisplayVariant + 'static> Commands<MSG> {
+    #[doc(hidden)]
+    pub fn new(relm: Relm<MSG>) -> Self {
+        Commands {
+            futures: vec![],
+            relm: relm,
+        }
+    }
+
+    /// Connect a `Future` or a `Stream` called `to_stream` to send the message `success_callback`
+    /// in case of success and `failure_callback` in case of failure.
+    /// The future is spawned after the update.
+    ///
+    /// The returned handle can be used to cancel the future.
+    pub fn connect<CALLBACK, FAILCALLBACK, STREAM, TOSTREAM>(&mut self, to_stream: TOSTREAM, success_callback: CALLBACK,
+            failure_callback: FAILCALLBACK) -> SubscriptionHandle
+        where CALLBACK: Fn(STREAM::Item) -> MSG + 'static,
+              FAILCALLBACK: Fn(STREAM::Error) -> MSG + 'static,
+              STREAM: Stream + 'static,
+              TOSTREAM: ToStream<STREAM, Item=STREAM::Item, Error=STREAM::Error> + 'static,
+    {
+        let future = self.relm.connect(to_stream, success_callback, failure_callback);
+        self.exec(future)
+    }
+
+    /// This function is the same as [`Commands::connect()`](struct.Commands.html#method.connect) except it does
+    /// not take a `failure_callback`; hence, it ignores the errors.
+    pub fn connect_ignore_err<CALLBACK, STREAM, TOSTREAM>(&mut self, to_stream: TOSTREAM, success_callback: CALLBACK)
+            -> SubscriptionHandle
+        where CALLBACK: Fn(STREAM::Item) -> MSG + 'static,
+              STREAM: Stream + 'static,
+              TOSTREAM: ToStream<STREAM, Item=STREAM::Item, Error=STREAM::Error> + 'static,
+    {
+        let future = self.relm.connect_ignore_err(to_stream, success_callback);
+        self.exec(future)
+    }
+
+    /// Add a future to spawn after the update.
+    ///
+    /// The returned handle can be used to cancel the future, even before it is spawned.
+    pub fn exec<FUTURE: Future<Item=(), Error=()> + 'static>(&mut self, future: FUTURE) -> SubscriptionHandle {
+        let handle = self.relm.executor.new_handle();
+        self.futures.push((Box::new(future), handle.clone()));
+        handle
+    }
+
+    /// Get a handle to the tokio reactor, to create I/O objects.
+    ///
+    /// The timers are created with [`Relm::timeout()`](struct.Relm.html#method.timeout) and
+    /// [`Relm::interval()`](struct.Relm.html#method.interval).
+    pub fn handle(&self) -> &Handle {
+        self.relm.handle()
+    }
+
+    /// Get the `Relm` used to connect the futures, to connect futures without spawning them.
+    pub fn relm(&self) -> &Relm<MSG> {
+        &self.relm
+    }
+
+    /// Check whether no future was added.
+    pub fn is_empty(&self) -> bool {
+        self.futures.is_empty()
+    }
+
+    /// Spawn all the futures of the batch on the GLib main loop.
+    pub fn spawn(self) {
+        for (future, handle) in self.futures {
+            self.relm.executor.spawn_with_handle(future, &handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{future, stream};
+    use gtk::Label;
+
+    use {Commands, DisplayVariant, Relm, RemoteRelm, TestRuntime, Widget, gtk_thread};
+    use self::Msg::*;
+
+    #[derive(Clone)]
+    enum Msg {
+        Failed(i32),
+        Start,
+        Value(i32),
+    }
+
+    impl DisplayVariant for Msg {
+        fn display_variant(&self) -> &'static str {
+            match *self {
+                Failed(_) => "Failed",
+                Start => "Start",
+                Value(_) => "Value",
+            }
+        }
+
+        fn clone_for_command(&self) -> Option<Self> {
+            Some(self.clone())
+        }
+    }
+
+    #[derive(Clone)]
+    struct Commanded {
+        label: Label,
+    }
+
+    impl Widget for Commanded {
+        type Model = Vec<i32>;
+        type ModelParam = ();
+        type Msg = Msg;
+        type Root = Label;
+
+        fn model(_: ()) -> Vec<i32> {
+            vec![]
+        }
+
+        fn root(&self) -> &Label {
+            &self.label
+        }
+
+        #[allow(deprecated)]
+        fn update_command(_relm: &Relm<Msg>, event: Msg, model: &mut Vec<i32>) {
+            if let Start = event {
+                model.push(0);
+            }
+        }
+
+        fn update_with_commands(&mut self, event: Msg, model: &mut Vec<i32>, commands: &mut Commands<Msg>) {
+            match event {
+                Failed(value) => model.push(-value),
+                Start => {
+                    let _ = commands.connect_ignore_err(future::ok::<_, ()>(1), Value);
+                    let _ = commands.connect(future::err::<i32, _>(2), Value, Failed);
+                    let _ = commands.connect_ignore_err(stream::iter_ok::<_, ()>(vec![3, 4]), Value);
+                    let stream = commands.relm().stream().clone();
+                    let _ = commands.exec(future::lazy(move || {
+                        stream.emit(Value(5));
+                        Ok(())
+                    }));
+                    // Cancelled before being spawned.
+                    commands.connect_ignore_err(future::ok::<_, ()>(6), Value).cancel();
+                },
+                Value(value) => model.push(value),
+            }
+        }
+
+        fn view(_relm: &RemoteRelm<Self>, _model: &Vec<i32>) -> Self {
+            Commanded {
+                label: Label::new(None),
+            }
+        }
+    }
+
+    #[test]
+    fn commands() {
+        gtk_thread::run(check_commands);
+    }
+
+    fn check_commands() {
+        let runtime = TestRuntime::new().unwrap();
+        let component = runtime.component::<Commanded>(());
+        component.send(Start);
+        // The commands are only spawned after the update.
+        assert_eq!(*component.model(), vec![0]);
+        runtime.run_until_stalled();
+        assert_eq!(*component.model(), vec![0, 1, -2, 3, 4, 5]);
+    }
+}
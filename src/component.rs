@@ -19,26 +19,90 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
+use std::rc::Rc;
 
-use super::{EventStream, Receiver, Widget};
+use gtk::{self, Continue};
+use relm_core::ObserverId;
+
+use super::{EventStream, Executor, Inspector, RemoteRelm, Widget};
 
-#[derive(Clone)]
 pub struct Comp<WIDGET: Widget> {
-    pub model: Arc<Mutex<WIDGET::Model>>,
-    pub _receiver: Arc<Receiver>,
+    pub executor: Executor,
+    pub inspectors: Rc<RefCell<Vec<Rc<Inspector<WIDGET>>>>>,
+    pub model: Rc<RefCell<WIDGET::Model>>,
     pub stream: EventStream<WIDGET::Msg>,
     pub widget: WIDGET,
+    // Shared by the clones of the component, so that it is only destroyed when the last one is dropped.
+    _teardown: Rc<Teardown>,
+}
+
+// Not derived, since the derive would require the model and the messages to be Clone.
+impl<WIDGET: Widget> Clone for Comp<WIDGET> {
+    fn clone(&self) -> Self {
+        Comp {
+            executor: self.executor.clone(),
+            inspectors: self.inspectors.clone(),
+            model: self.model.clone(),
+            stream: self.stream.clone(),
+            widget: self.widget.clone(),
+            _teardown: self._teardown.clone(),
+        }
+    }
+}
+
+impl<WIDGET: Widget + 'static> Comp<WIDGET> {
+    pub fn new(executor: Executor, model: Rc<RefCell<WIDGET::Model>>, stream: EventStream<WIDGET::Msg>,
+        widget: WIDGET) -> Self
+    {
+        let teardown = {
+            let executor = executor.clone();
+            let model = model.clone();
+            let stream = stream.clone();
+            let mut widget = widget.clone();
+            Box::new(move || {
+                match model.try_borrow_mut() {
+                    Ok(mut model) => widget.on_destroy(&mut *model),
+                    // The model is borrowed when the component is dropped from its own update() method.
+                    Err(_) => return false,
+                }
+                executor.cancel_all();
+                stream.close();
+                true
+            })
+        };
+        Comp {
+            executor: executor,
+            inspectors: Rc::new(RefCell::new(vec![])),
+            model: model,
+            stream: stream,
+            widget: widget,
+            _teardown: Rc::new(Teardown(Some(teardown))),
+        }
+    }
 }
 
-impl<WIDGET: Widget> Drop for Comp<WIDGET> {
+// Destroy the component, returning false when it must be retried later.
+struct Teardown(Option<Box<FnMut() -> bool>>);
+
+impl Drop for Teardown {
     fn drop(&mut self) {
-        let _ = self.stream.close();
+        if let Some(mut teardown) = self.0.take() {
+            if !teardown() {
+                // Retry once the update is over.
+                let _ = gtk::idle_add(move || Continue(!teardown()));
+            }
+        }
     }
 }
 
 /// Widget that was added by the `ContainerWidget::add_widget()` method.
 ///
+/// When the last clone of a component is dropped, the [`on_destroy()`](trait.Widget.html#method.on_destroy)
+/// method of its widget is called, its futures are cancelled and its event stream is closed.
+/// When the component is dropped from its own [`update()`](trait.Widget.html#method.update) method, this is
+/// done once the update is over.
+///
 /// ## Warning
 /// You must keep your components as long as you want them to send/receive events.
 /// Common practice is to store `Component`s in the `Widget` struct (see the [communication
@@ -46,30 +110,437 @@ impl<WIDGET: Widget> Drop for Comp<WIDGET> {
 /// The `#[widget]` attribute takes care of storing them in the struct automatically (see the
 /// [communication-attribute example](https://github.com/antoyo/relm/blob/master/examples/communication-attribute.rs)).
 #[must_use]
-#[derive(Clone)]
-pub struct Component<WIDGET: Widget>(Comp<WIDGET>)
-    where WIDGET::Model: Clone;
+pub struct Component<WIDGET: Widget>(Comp<WIDGET>);
 
-impl<WIDGET: Widget> Component<WIDGET>
-    where WIDGET::Model: Clone
-{
+impl<WIDGET: Widget> Clone for Component<WIDGET> {
+    fn clone(&self) -> Self {
+        Component(self.0.clone())
+    }
+}
+
+impl<WIDGET: Widget> Component<WIDGET> {
     #[doc(hidden)]
     pub fn new(component: Comp<WIDGET>) -> Self {
         Component(component)
     }
 }
 
-impl<WIDGET: Widget> Component<WIDGET>
-    where WIDGET::Model: Clone
-{
+impl<WIDGET: Widget> Component<WIDGET> {
+    /// Send a message to this component.
+    pub fn emit(&self, msg: WIDGET::Msg)
+        where WIDGET::Msg: 'static,
+    {
+        self.0.stream.emit(msg);
+    }
+
+    /// Send the messages of this component to the component `other`.
+    /// The message returned by `mapper` is sent, unless it is `None`.
+    ///
+    /// The observer is removed when `other` is destroyed.
+    pub fn forward_to_component<OTHER, MAPPER>(&self, other: &Component<OTHER>, mapper: MAPPER)
+            -> ObserverHandle<WIDGET::Msg>
+        where OTHER: Widget,
+              OTHER::Msg: 'static,
+              MAPPER: Fn(&WIDGET::Msg) -> Option<OTHER::Msg> + 'static,
+              WIDGET::Msg: 'static,
+    {
+        self.forward_to_stream(other.stream(), mapper)
+    }
+
+    /// Send the messages of this component to the widget of `relm`, usually its parent.
+    /// The message returned by `mapper` is sent, unless it is `None`.
+    ///
+    /// The observer is removed when the widget of `relm` is destroyed.
+    pub fn forward_to<PARENT, MAPPER>(&self, relm: &RemoteRelm<PARENT>, mapper: MAPPER) -> ObserverHandle<WIDGET::Msg>
+        where PARENT: Widget,
+              PARENT::Msg: 'static,
+              MAPPER: Fn(&WIDGET::Msg) -> Option<PARENT::Msg> + 'static,
+              WIDGET::Msg: 'static,
+    {
+        self.forward_to_stream(relm.stream(), mapper)
+    }
+
+    #[doc(hidden)]
+    pub fn model(&self) -> &Rc<RefCell<WIDGET::Model>> {
+        &self.0.model
+    }
+
+    /// Call the methods of `inspector` around every update of this component.
+    pub fn inspect<INSPECTOR: Inspector<WIDGET> + 'static>(&self, inspector: INSPECTOR) {
+        self.0.inspectors.borrow_mut().push(Rc::new(inspector));
+    }
+
     /// Get the event stream of the widget.
     /// This is used internally by the library.
     pub fn stream(&self) -> &EventStream<WIDGET::Msg> {
         &self.0.stream
     }
 
+    /// Call `callback` for every message sent to this component.
+    ///
+    /// The returned handle can be used to unsubscribe.
+    pub fn subscribe<CALLBACK>(&self, callback: CALLBACK) -> ObserverHandle<WIDGET::Msg>
+        where CALLBACK: Fn(&WIDGET::Msg) + 'static,
+              WIDGET::Msg: 'static,
+    {
+        let id = self.0.stream.observe(callback);
+        ObserverHandle::new(self.0.stream.clone(), id)
+    }
+
     /// Get the widget of this component.
     pub fn widget(&self) -> &WIDGET {
         &self.0.widget
     }
+
+    fn forward_to_stream<MSG, MAPPER>(&self, stream: &EventStream<MSG>, mapper: MAPPER) -> ObserverHandle<WIDGET::Msg>
+        where MAPPER: Fn(&WIDGET::Msg) -> Option<MSG> + 'static,
+              MSG: 'static,
+              WIDGET::Msg: 'static,
+    {
+        let handle = {
+            let stream = stream.clone();
+            self.subscribe(move |msg| {
+                if let Some(msg) = mapper(msg) {
+                    stream.emit(msg);
+                }
+            })
+        };
+        {
+            let handle = handle.clone();
+            stream.on_close(move || handle.unsubscribe());
+        }
+        handle
+    }
+}
+
+/// Handle to an observer of the messages of a component, which can be used to unsubscribe.
+///
+/// Dropping the handle does not unsubscribe.
+pub struct ObserverHandle<MSG> {
+    id: ObserverId,
+    stream: EventStream<MSG>,
+}
+
+impl<MSG> Clone for ObserverHandle<MSG> {
+    fn clone(&self) -> Self {
+        ObserverHandle {
+            id: self.id,
+            stream: self.stream.clone(),
+        }
+    }
+}
+
+impl<MSG: 'static> ObserverHandle<MSG> {
+    fn new(stream: EventStream<MSG>, id: ObserverId) -> Self {
+        ObserverHandle {
+            id: id,
+            stream: stream,
+        }
+    }
+
+    /// Stop observing the messages.
+    pub fn unsubscribe(&self) {
+        self.stream.unobserve(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use futures::{Async, Future, Poll};
+    use gtk::Label;
+
+    use {Core, DisplayVariant, EventStream, RemoteRelm, TestRuntime, Widget, create_widget_test, gtk_thread};
+    use executor::Executor;
+    use super::{Comp, Component};
+    use self::Msg::{Destroy, Value};
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Msg {
+        Destroy,
+        Value(i32),
+    }
+
+    impl DisplayVariant for Msg {
+        fn display_variant(&self) -> &'static str {
+            match *self {
+                Destroy => "Destroy",
+                Value(_) => "Value",
+            }
+        }
+    }
+
+    // Future never completing, which records when it is dropped.
+    struct Pending(Rc<Cell<bool>>);
+
+    impl Future for Pending {
+        type Item = ();
+        type Error = ();
+
+        fn poll(&mut self) -> Poll<(), ()> {
+            Ok(Async::NotReady)
+        }
+    }
+
+    impl Drop for Pending {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    // Widget without a view, since the messages are only observed, never dispatched.
+    #[derive(Clone)]
+    struct Test {
+        destroyed: Rc<Cell<usize>>,
+    }
+
+    impl Widget for Test {
+        type Model = ();
+        type ModelParam = ();
+        type Msg = Msg;
+        type Root = Label;
+
+        fn model(_: ()) -> () {
+        }
+
+        fn on_destroy(&mut self, _model: &mut ()) {
+            self.destroyed.set(self.destroyed.get() + 1);
+        }
+
+        fn root(&self) -> &Label {
+            unreachable!()
+        }
+
+        fn view(_relm: &RemoteRelm<Self>, _model: &()) -> Self {
+            unreachable!()
+        }
+    }
+
+    fn component() -> Component<Test> {
+        let core = Core::new_virtual().unwrap();
+        let widget = Test {
+            destroyed: Rc::new(Cell::new(0)),
+        };
+        Component::new(Comp::new(Executor::new(&core), Rc::new(RefCell::new(())), EventStream::new(), widget))
+    }
+
+    fn record(component: &Component<Test>) -> Rc<RefCell<Vec<Msg>>> {
+        let messages = Rc::new(RefCell::new(vec![]));
+        {
+            let messages = messages.clone();
+            let _ = component.subscribe(move |msg| messages.borrow_mut().push(msg.clone()));
+        }
+        messages
+    }
+
+    #[test]
+    fn emit_and_subscribe() {
+        let component = component();
+        let messages = Rc::new(RefCell::new(vec![]));
+        let handle = {
+            let messages = messages.clone();
+            component.subscribe(move |msg| messages.borrow_mut().push(msg.clone()))
+        };
+        component.emit(Value(1));
+        component.emit(Value(2));
+        assert_eq!(*messages.borrow(), vec![Value(1), Value(2)]);
+
+        handle.unsubscribe();
+        component.emit(Value(3));
+        assert_eq!(*messages.borrow(), vec![Value(1), Value(2)]);
+    }
+
+    #[test]
+    fn forward_to_component() {
+        let child = component();
+        let parent = component();
+        let messages = record(&parent);
+        let child_messages = record(&child);
+        let _ = child.forward_to_component(&parent, |msg|
+            match *msg {
+                Value(value) if value > 0 => Some(Value(value * 10)),
+                _ => None,
+            }
+        );
+        child.emit(Value(1));
+        child.emit(Value(-1));
+        child.emit(Value(2));
+        assert_eq!(*messages.borrow(), vec![Value(10), Value(20)]);
+
+        // Destroying the parent removes the observer.
+        drop(parent);
+        child.emit(Value(3));
+        assert_eq!(*messages.borrow(), vec![Value(10), Value(20)]);
+        assert_eq!(*child_messages.borrow(), vec![Value(1), Value(-1), Value(2), Value(3)]);
+    }
+
+    #[test]
+    fn dropped_clone() {
+        let component = component();
+        let messages = record(&component);
+        // The component is only destroyed when its last clone is dropped.
+        drop(component.clone());
+        component.emit(Value(1));
+        assert_eq!(*messages.borrow(), vec![Value(1)]);
+    }
+
+    #[test]
+    fn destroy_last_clone() {
+        let component = component();
+        let destroyed = component.widget().destroyed.clone();
+        let cancelled = Rc::new(Cell::new(false));
+        let handle = component.0.executor.spawn(Pending(cancelled.clone()));
+        let messages = record(&component);
+
+        let clone = component.clone();
+        drop(component);
+        assert_eq!(destroyed.get(), 0);
+        assert!(handle.is_running());
+
+        let stream = clone.stream().clone();
+        drop(clone);
+        assert_eq!(destroyed.get(), 1);
+        assert!(cancelled.get());
+        assert!(!handle.is_running());
+        // The stream is closed.
+        stream.emit(Value(1));
+        assert!(messages.borrow().is_empty());
+    }
+
+    thread_local! {
+        static DESTROYED: Cell<usize> = Cell::new(0);
+        static SELF_DROPPED: RefCell<Option<Component<SelfDropped>>> = RefCell::new(None);
+    }
+
+    // Widget dropping its own component from its update() method.
+    #[derive(Clone)]
+    struct SelfDropped {
+        label: Label,
+    }
+
+    impl Widget for SelfDropped {
+        type Model = ();
+        type ModelParam = ();
+        type Msg = Msg;
+        type Root = Label;
+
+        fn model(_: ()) -> () {
+        }
+
+        fn on_destroy(&mut self, _model: &mut ()) {
+            DESTROYED.with(|destroyed| destroyed.set(destroyed.get() + 1));
+        }
+
+        fn root(&self) -> &Label {
+            &self.label
+        }
+
+        fn update(&mut self, event: Msg, _model: &mut ()) {
+            if let Destroy = event {
+                let component = SELF_DROPPED.with(|component| component.borrow_mut().take());
+                drop(component);
+                // The model is borrowed by this update, so the teardown is deferred.
+                assert_eq!(DESTROYED.with(|destroyed| destroyed.get()), 0);
+            }
+        }
+
+        fn view(_relm: &RemoteRelm<Self>, _model: &()) -> Self {
+            SelfDropped {
+                label: Label::new(None),
+            }
+        }
+    }
+
+    #[test]
+    fn destroy_during_update() {
+        gtk_thread::run(check_destroy_during_update);
+    }
+
+    fn check_destroy_during_update() {
+        let runtime = TestRuntime::new().unwrap();
+        let component = create_widget_test::<SelfDropped>(runtime.core(), ());
+        let stream = component.stream().clone();
+        SELF_DROPPED.with(|self_dropped| *self_dropped.borrow_mut() = Some(component));
+
+        stream.emit(Destroy);
+        stream.flush();
+        assert_eq!(DESTROYED.with(|destroyed| destroyed.get()), 0);
+
+        // The teardown is retried once the update is over.
+        runtime.run_until_stalled();
+        assert_eq!(DESTROYED.with(|destroyed| destroyed.get()), 1);
+        runtime.run_until_stalled();
+        assert_eq!(DESTROYED.with(|destroyed| destroyed.get()), 1);
+    }
+
+    // Message which is not Clone, to check that it is moved from emit() to update().
+    enum ChunkMsg {
+        ImageChunk(Vec<u8>),
+    }
+
+    impl DisplayVariant for ChunkMsg {
+        fn display_variant(&self) -> &'static str {
+            match *self {
+                ChunkMsg::ImageChunk(_) => "ImageChunk",
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct Image {
+        label: Label,
+    }
+
+    impl Widget for Image {
+        type Model = Vec<u8>;
+        type ModelParam = ();
+        type Msg = ChunkMsg;
+        type Root = Label;
+
+        fn model(_: ()) -> Vec<u8> {
+            vec![]
+        }
+
+        fn root(&self) -> &Label {
+            &self.label
+        }
+
+        fn update(&mut self, event: ChunkMsg, model: &mut Vec<u8>) {
+            match event {
+                ChunkMsg::ImageChunk(mut chunk) => model.append(&mut chunk),
+            }
+        }
+
+        fn view(_relm: &RemoteRelm<Self>, _model: &Vec<u8>) -> Self {
+            Image {
+                label: Label::new(None),
+            }
+        }
+    }
+
+    #[test]
+    fn non_clone_message() {
+        gtk_thread::run(check_non_clone_message);
+    }
+
+    fn check_non_clone_message() {
+        let runtime = TestRuntime::new().unwrap();
+        let component = create_widget_test::<Image>(runtime.core(), ());
+        let chunk_lengths = Rc::new(RefCell::new(vec![]));
+        {
+            let chunk_lengths = chunk_lengths.clone();
+            let _ = component.subscribe(move |msg| match *msg {
+                ChunkMsg::ImageChunk(ref chunk) => chunk_lengths.borrow_mut().push(chunk.len()),
+            });
+        }
+
+        component.emit(ChunkMsg::ImageChunk(vec![1, 2, 3]));
+        component.emit(ChunkMsg::ImageChunk(vec![4, 5]));
+        component.stream().flush();
+        runtime.run_until_stalled();
+        assert_eq!(*chunk_lengths.borrow(), vec![3, 2]);
+        assert_eq!(*component.model().borrow(), vec![1, 2, 3, 4, 5]);
+    }
 }
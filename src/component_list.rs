@@ -0,0 +1,183 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::rc::Rc;
+
+use gtk;
+use gtk::{BoxExt, Cast, ContainerExt, IsA, Object, WidgetExt};
+
+use component::Component;
+use container::ContainerWidget;
+use super::{DisplayVariant, RemoteRelm};
+use widget::Widget;
+
+/// Trait for GTK+ containers whose children can be moved, used by `ComponentList`.
+pub trait ListContainer: Clone + ContainerExt + IsA<gtk::Widget> + IsA<Object> + 'static {
+    /// Remove the child `widget` from this container.
+    fn remove_child<W: IsA<gtk::Widget> + IsA<Object>>(&self, widget: &W) {
+        self.remove(widget);
+    }
+
+    /// Move the child `widget` to `position`.
+    fn reorder_child<W: IsA<gtk::Widget> + IsA<Object>>(&self, widget: &W, position: usize);
+}
+
+impl ListContainer for gtk::Box {
+    fn reorder_child<W: IsA<gtk::Widget> + IsA<Object>>(&self, widget: &W, position: usize) {
+        BoxExt::reorder_child(self, widget, position as i32);
+    }
+}
+
+// The widgets added to a ListBox are wrapped in a ListBoxRow, so the row is removed or moved instead.
+impl ListContainer for gtk::ListBox {
+    fn remove_child<W: IsA<gtk::Widget> + IsA<Object>>(&self, widget: &W) {
+        if let Some(row) = widget.get_parent() {
+            self.remove(&row);
+        }
+    }
+
+    fn reorder_child<W: IsA<gtk::Widget> + IsA<Object>>(&self, widget: &W, position: usize) {
+        if let Some(row) = widget.get_parent().and_then(|row| row.downcast::<gtk::ListBoxRow>().ok()) {
+            // Moving a row removes it, which loses its focus and selection.
+            if row.get_index() != position as i32 {
+                self.remove(&row);
+                self.insert(&row, position as i32);
+            }
+        }
+    }
+}
+
+/// List of components identified by a key, created from a collection of the model.
+///
+/// Calling `update()` with the keys of the collection creates the components for the new keys,
+/// destroys the components whose key was removed and reorders the others, so that the children
+/// of the container follow the order of the keys.
+/// The messages of the components are sent to the parent widget, tagged with their key.
+///
+/// ## Warning
+/// The container must only contain the children added by this list.
+pub struct ComponentList<KEY, WIDGET: Widget, CONTAINER = gtk::Box> {
+    children: Vec<(KEY, Component<WIDGET>)>,
+    container: CONTAINER,
+    create: Rc<Fn(&KEY, WIDGET::ModelParam) -> Component<WIDGET>>,
+}
+
+impl<KEY: Clone, WIDGET: Widget, CONTAINER: Clone> Clone for ComponentList<KEY, WIDGET, CONTAINER> {
+    fn clone(&self) -> Self {
+        ComponentList {
+            children: self.children.clone(),
+            container: self.container.clone(),
+            create: self.create.clone(),
+        }
+    }
+}
+
+impl<KEY, WIDGET, CONTAINER> ComponentList<KEY, WIDGET, CONTAINER>
+    where KEY: Clone + Eq + Hash + 'static,
+          WIDGET: Widget + 'static,
+          WIDGET::Msg: DisplayVariant + 'static,
+          WIDGET::Root: IsA<gtk::Widget> + IsA<Object> + WidgetExt,
+          CONTAINER: ListContainer,
+{
+    /// Create an empty list adding its components in `container`.
+    ///
+    /// The messages of the components are converted by `mapper` and sent to the widget of `relm`,
+    /// unless `mapper` returns `None`.
+    pub fn new<PARENT, MAPPER>(container: &CONTAINER, relm: &RemoteRelm<PARENT>, mapper: MAPPER) -> Self
+        where PARENT: Widget + 'static,
+              PARENT::Msg: DisplayVariant + 'static,
+              MAPPER: Fn(&KEY, &WIDGET::Msg) -> Option<PARENT::Msg> + 'static,
+    {
+        let mapper = Rc::new(mapper);
+        let create = {
+            let container = container.clone();
+            let relm = relm.clone();
+            move |key: &KEY, model_param| {
+                let component = container.add_widget::<WIDGET, _>(&relm, model_param);
+                let key = key.clone();
+                let mapper = mapper.clone();
+                let _ = component.forward_to(&relm, move |msg| mapper(&key, msg));
+                component
+            }
+        };
+        ComponentList {
+            children: vec![],
+            container: container.clone(),
+            create: Rc::new(create),
+        }
+    }
+
+    /// Get the component identified by `key`.
+    pub fn get(&self, key: &KEY) -> Option<&Component<WIDGET>> {
+        self.children.iter()
+            .find(|&&(ref child_key, _)| child_key == key)
+            .map(|&(_, ref component)| component)
+    }
+
+    /// Check whether the list has no components.
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Iterate over the keys and components, in the order of the container.
+    pub fn iter(&self) -> ::std::slice::Iter<(KEY, Component<WIDGET>)> {
+        self.children.iter()
+    }
+
+    /// Get the number of components.
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Synchronize the components with `keys`.
+    ///
+    /// A component is created with the model parameter returned by `model_param` for every new key.
+    /// The components of the removed keys are removed from the container and destroyed.
+    /// If a key appears multiple times, a component is created for every occurrence.
+    pub fn update<ITER, PARAM>(&mut self, keys: ITER, model_param: PARAM)
+        where ITER: IntoIterator<Item=KEY>,
+              PARAM: Fn(&KEY) -> WIDGET::ModelParam,
+    {
+        let mut old_children: HashMap<KEY, VecDeque<Component<WIDGET>>> = HashMap::new();
+        for (key, component) in self.children.drain(..) {
+            old_children.entry(key).or_insert_with(VecDeque::new).push_back(component);
+        }
+
+        for (position, key) in keys.into_iter().enumerate() {
+            let old_component = old_children.get_mut(&key).and_then(|components| components.pop_front());
+            let component =
+                match old_component {
+                    Some(component) => component,
+                    None => (self.create)(&key, model_param(&key)),
+                };
+            self.container.reorder_child(component.widget().root(), position);
+            self.children.push((key, component));
+        }
+
+        // The stale components are now after the others in the container.
+        for component in old_children.into_iter().flat_map(|(_, components)| components) {
+            component.widget().on_remove(self.container.clone());
+            self.container.remove_child(component.widget().root());
+        }
+    }
+}
@@ -0,0 +1,64 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+
+/// Last value of a `#[computed]` function of a `#[widget]`.
+///
+/// The properties using this value are only set when `update()` returns `true`.
+pub struct Computed<T> {
+    value: Rc<RefCell<T>>,
+}
+
+impl<T> Clone for Computed<T> {
+    fn clone(&self) -> Self {
+        Computed {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq> Computed<T> {
+    /// Create a cache containing `value`.
+    pub fn new(value: T) -> Self {
+        Computed {
+            value: Rc::new(RefCell::new(value)),
+        }
+    }
+
+    /// Get the last value.
+    pub fn get(&self) -> Ref<T> {
+        self.value.borrow()
+    }
+
+    /// Save `value` and return whether it is different from the last value.
+    pub fn update(&self, value: T) -> bool {
+        let mut current = self.value.borrow_mut();
+        if *current == value {
+            false
+        }
+        else {
+            *current = value;
+            true
+        }
+    }
+}
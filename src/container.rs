@@ -59,14 +59,14 @@ pub trait ContainerWidget {
     fn add_widget<CHILDWIDGET, WIDGET: Widget>(&self, relm: &RemoteRelm<WIDGET>,
             model_param: CHILDWIDGET::ModelParam) -> Component<CHILDWIDGET>
         where CHILDWIDGET: Widget + 'static,
-              CHILDWIDGET::Model: Clone + Send,
-              CHILDWIDGET::Msg: Clone + DisplayVariant + Send + 'static,
+              CHILDWIDGET::Msg: DisplayVariant + 'static,
               CHILDWIDGET::Root: IsA<gtk::Widget> + IsA<Object> + WidgetExt;
 
     /// Remove a relm `Widget` from the current GTK+ container.
+    ///
+    /// The component is destroyed afterward.
     fn remove_widget<CHILDWIDGET>(&self, component: Component<CHILDWIDGET>)
         where CHILDWIDGET: Widget,
-              CHILDWIDGET::Model: Clone,
               CHILDWIDGET::Root: IsA<gtk::Widget>;
 }
 
@@ -74,22 +74,21 @@ impl<W: Clone + ContainerExt + IsA<gtk::Widget> + IsA<Object>> ContainerWidget f
     fn add_widget<CHILDWIDGET, WIDGET: Widget>(&self, relm: &RemoteRelm<WIDGET>,
             model_param: CHILDWIDGET::ModelParam) -> Component<CHILDWIDGET>
         where CHILDWIDGET: Widget + 'static,
-              CHILDWIDGET::Model: Clone + Send,
-              CHILDWIDGET::Msg: Clone + DisplayVariant + Send + 'static,
+              CHILDWIDGET::Msg: DisplayVariant + 'static,
               CHILDWIDGET::Root: IsA<gtk::Widget> + IsA<Object> + WidgetExt,
     {
-        let component = create_widget::<CHILDWIDGET>(&relm.remote, model_param);
+        let component = create_widget::<CHILDWIDGET>(&relm.core, model_param);
         self.add(component.widget.root());
         component.widget.on_add(self.clone());
-        init_component::<CHILDWIDGET>(&component, &relm.remote);
+        init_component::<CHILDWIDGET>(&component);
         Component::new(component)
     }
 
     fn remove_widget<WIDGET>(&self, component: Component<WIDGET>)
         where WIDGET: Widget,
-              WIDGET::Model: Clone,
               WIDGET::Root: IsA<gtk::Widget>,
     {
+        component.widget().on_remove(self.clone());
         self.remove(component.widget().root());
     }
 }
@@ -103,8 +102,6 @@ pub trait RelmContainer {
     fn add_widget<CHILDWIDGET, WIDGET>(&self, relm: &RemoteRelm<WIDGET>,
             model_param: CHILDWIDGET::ModelParam) -> Component<CHILDWIDGET>
         where CHILDWIDGET: Widget + 'static,
-              CHILDWIDGET::Model: Clone + Send,
-              CHILDWIDGET::Msg: Send,
               WIDGET: Widget;
 
     // TODO: add delete methods?
@@ -113,7 +110,6 @@ pub trait RelmContainer {
 impl<WIDGET> RelmContainer for Component<WIDGET>
     where WIDGET: Container + Widget,
           WIDGET::Container: Clone + ContainerExt + IsA<gtk::Widget> + IsA<Object>,
-          WIDGET::Model: Clone,
 {
     fn add<W: IsA<gtk::Widget>>(&self, widget: &W) {
         self.widget().add(widget);
@@ -122,14 +118,12 @@ impl<WIDGET> RelmContainer for Component<WIDGET>
     fn add_widget<CHILDWIDGET, PARENTWIDGET>(&self, relm: &RemoteRelm<PARENTWIDGET>,
             model_param: CHILDWIDGET::ModelParam) -> Component<CHILDWIDGET>
         where CHILDWIDGET: Widget + 'static,
-              CHILDWIDGET::Model: Clone + Send,
-              CHILDWIDGET::Msg: Send,
               PARENTWIDGET: Widget
     {
-        let component = create_widget::<CHILDWIDGET>(&relm.remote, model_param);
+        let component = create_widget::<CHILDWIDGET>(&relm.core, model_param);
         let container = self.widget().add_widget(&component.widget);
         component.widget.on_add(container.clone());
-        init_component::<CHILDWIDGET>(&component, &relm.remote);
+        init_component::<CHILDWIDGET>(&component);
         Component::new(component)
     }
 }
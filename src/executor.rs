@@ -0,0 +1,136 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::rc::Rc;
+
+use futures::Future;
+use relm_core::{Core, Handle, TaskId};
+
+/// Spawn the futures of a component on the `Core` and keep track of them to cancel them when the
+/// component is destroyed.
+#[derive(Clone)]
+pub struct Executor {
+    core: Core,
+    spawned: Rc<Cell<usize>>,
+    tasks: Rc<RefCell<Vec<TaskId>>>,
+}
+
+impl Executor {
+    pub fn new(core: &Core) -> Self {
+        Executor {
+            core: core.clone(),
+            spawned: Rc::new(Cell::new(0)),
+            tasks: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        let tasks = mem::replace(&mut *self.tasks.borrow_mut(), vec![]);
+        for task in tasks {
+            let _ = self.core.cancel(task);
+        }
+    }
+
+    pub fn core(&self) -> &Core {
+        &self.core
+    }
+
+    pub fn handle(&self) -> &Handle {
+        self.core.handle()
+    }
+
+    /// Get the number of futures spawned by this executor so far.
+    #[doc(hidden)]
+    pub fn spawned_count(&self) -> usize {
+        self.spawned.get()
+    }
+
+    /// Create a handle for a future that will be spawned later with `spawn_with_handle()`.
+    pub fn new_handle(&self) -> SubscriptionHandle {
+        SubscriptionHandle::new(&self.core)
+    }
+
+    pub fn spawn<FUTURE: Future<Item=(), Error=()> + 'static>(&self, future: FUTURE) -> SubscriptionHandle {
+        let handle = self.new_handle();
+        self.spawn_with_handle(future, &handle);
+        handle
+    }
+
+    /// Spawn the future, unless the handle was already cancelled.
+    pub fn spawn_with_handle<FUTURE>(&self, future: FUTURE, handle: &SubscriptionHandle)
+        where FUTURE: Future<Item=(), Error=()> + 'static,
+    {
+        if handle.cancelled.get() {
+            return;
+        }
+        let id = self.core.spawn(future);
+        self.spawned.set(self.spawned.get() + 1);
+        handle.task.set(Some(id));
+        let mut tasks = self.tasks.borrow_mut();
+        // Forget about the completed futures.
+        tasks.retain(|&task| self.core.is_running(task));
+        tasks.push(id);
+    }
+}
+
+/// Handle to a future spawned by relm, which can be used to cancel it.
+///
+/// Dropping the handle does not cancel the future.
+#[derive(Clone)]
+pub struct SubscriptionHandle {
+    cancelled: Rc<Cell<bool>>,
+    core: Core,
+    task: Rc<Cell<Option<TaskId>>>,
+}
+
+impl SubscriptionHandle {
+    #[doc(hidden)]
+    pub fn new(core: &Core) -> Self {
+        SubscriptionHandle {
+            cancelled: Rc::new(Cell::new(false)),
+            core: core.clone(),
+            task: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /// Cancel the future by dropping it.
+    /// If the future was not spawned yet, it will never be.
+    pub fn cancel(&self) {
+        self.cancelled.set(true);
+        if let Some(task) = self.task.take() {
+            let _ = self.core.cancel(task);
+        }
+    }
+
+    /// Check whether the future is still running, i.e. it was neither cancelled nor completed.
+    pub fn is_running(&self) -> bool {
+        if self.cancelled.get() {
+            return false;
+        }
+        match self.task.get() {
+            Some(task) => self.core.is_running(task),
+            // Not spawned yet.
+            None => true,
+        }
+    }
+}
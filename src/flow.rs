@@ -0,0 +1,160 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+use gtk;
+use gtk::{BoxExt, Cast, ContainerExt, IsA, Object};
+
+enum Anchor {
+    Flow(Rc<RefCell<FlowState>>),
+    Widget(gtk::Widget),
+}
+
+struct FlowState {
+    container: gtk::Container,
+    previous: Option<Anchor>,
+    values: Vec<Box<Any>>,
+    widgets: Vec<gtk::Widget>,
+}
+
+impl FlowState {
+    // Get the last widget of this flow or of the widgets before it.
+    fn last_widget(&self) -> Option<gtk::Widget> {
+        self.widgets.last().cloned().or_else(|| self.previous_widget())
+    }
+
+    fn previous_widget(&self) -> Option<gtk::Widget> {
+        match self.previous {
+            Some(Anchor::Flow(ref flow)) => flow.borrow().last_widget(),
+            Some(Anchor::Widget(ref widget)) => Some(widget.clone()),
+            None => None,
+        }
+    }
+}
+
+/// Children of a widget created by an `if`, a `match` or a `for` in the `view!` macro.
+///
+/// The children are created by a builder function, which is called again by `rebuild()` when
+/// the model changes: all the previous children are then removed and destroyed, even the ones
+/// which are created again, so their state, like the focus or the text typed in an entry, is lost.
+/// Use a [`ComponentList`](struct.ComponentList.html) to keep the components of a list by key.
+pub struct FlowChildren<MODEL> {
+    builder: Rc<RefCell<Option<Rc<Fn(&FlowChildren<MODEL>, &MODEL)>>>>,
+    state: Rc<RefCell<FlowState>>,
+}
+
+impl<MODEL> Clone for FlowChildren<MODEL> {
+    fn clone(&self) -> Self {
+        FlowChildren {
+            builder: self.builder.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<MODEL> FlowChildren<MODEL> {
+    /// Create an empty list of children which will be added in `container`.
+    pub fn new<CONTAINER: Clone + IsA<gtk::Container> + IsA<Object>>(container: &CONTAINER) -> Self {
+        FlowChildren {
+            builder: Rc::new(RefCell::new(None)),
+            state: Rc::new(RefCell::new(FlowState {
+                container: container.clone().upcast(),
+                previous: None,
+                values: vec![],
+                widgets: vec![],
+            })),
+        }
+    }
+
+    /// Add a widget created by the builder.
+    /// The widget must be a child of the container.
+    pub fn add_widget<WIDGET: Clone + IsA<gtk::Widget> + IsA<Object>>(&self, widget: &WIDGET) {
+        self.state.borrow_mut().widgets.push(widget.clone().upcast());
+    }
+
+    /// Keep a value, like a `Component`, until the children are rebuilt.
+    pub fn keep<VALUE: Any>(&self, value: VALUE) {
+        self.state.borrow_mut().values.push(Box::new(value));
+    }
+
+    /// Remove and destroy the children, then call the builder to create the new children.
+    ///
+    /// The new children are placed after the previous sibling when the container is a `gtk::Box`.
+    pub fn rebuild(&self, model: &MODEL) {
+        self.clear();
+        let builder = self.builder.borrow().clone();
+        if let Some(builder) = builder {
+            builder(self, model);
+        }
+        self.reorder();
+    }
+
+    /// Set the function creating the children from the model.
+    pub fn set_builder<BUILDER>(&self, builder: BUILDER)
+        where BUILDER: Fn(&FlowChildren<MODEL>, &MODEL) + 'static,
+    {
+        *self.builder.borrow_mut() = Some(Rc::new(builder));
+    }
+
+    /// Place the children after the children of `flow`.
+    pub fn set_previous_flow(&self, flow: &FlowChildren<MODEL>) {
+        self.state.borrow_mut().previous = Some(Anchor::Flow(flow.state.clone()));
+    }
+
+    /// Place the children after `widget`.
+    pub fn set_previous_widget<WIDGET: Clone + IsA<gtk::Widget> + IsA<Object>>(&self, widget: &WIDGET) {
+        self.state.borrow_mut().previous = Some(Anchor::Widget(widget.clone().upcast()));
+    }
+
+    fn clear(&self) {
+        let (container, values, widgets) = {
+            let mut state = self.state.borrow_mut();
+            (state.container.clone(), mem::replace(&mut state.values, vec![]),
+                mem::replace(&mut state.widgets, vec![]))
+        };
+        for widget in &widgets {
+            container.remove(widget);
+        }
+        // The components are destroyed after their widget is removed.
+        drop(values);
+    }
+
+    fn reorder(&self) {
+        let state = self.state.borrow();
+        if let Ok(gtk_box) = state.container.clone().downcast::<gtk::Box>() {
+            let position =
+                match state.previous_widget() {
+                    Some(previous) => gtk_box.get_children().iter()
+                        .position(|child| *child == previous)
+                        .map(|position| position + 1)
+                        .unwrap_or(0),
+                    None => 0,
+                };
+            for (index, widget) in state.widgets.iter().enumerate() {
+                gtk_box.reorder_child(widget, (position + index) as i32);
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use gdk_sys::GdkFrameClock;
+use glib::translate::{FromGlibPtrNone, ToGlibPtr};
+use glib_sys::{GFALSE, gboolean, gpointer};
+use gtk::{self, IsA};
+use gtk_sys::{self, GtkWidget};
+use relm_core::EventStream;
+
+/// Dispatch the messages of a batched stream at the next tick of the frame clock of `widget`, so that
+/// the messages emitted during a frame are applied before it is drawn.
+///
+/// The tick callback is only added when a message is queued and it is removed once the messages
+/// are dispatched, so that an idle widget does not wake up the main loop at every frame.
+pub fn dispatch_per_frame<MSG: 'static, WIDGET: IsA<gtk::Widget>>(widget: &WIDGET, stream: EventStream<MSG>) {
+    let widget: *mut GtkWidget = widget.to_glib_none().0;
+    let widget = unsafe { gtk::Widget::from_glib_none(widget) };
+    let tick_stream = stream.clone();
+    // The scheduler is dropped when the stream is closed.
+    stream.set_batch_scheduler(move || add_tick_callback(&widget, tick_stream.clone()));
+}
+
+fn add_tick_callback<MSG: 'static>(widget: &gtk::Widget, stream: EventStream<MSG>) {
+    let data = Box::into_raw(Box::new(stream));
+    unsafe {
+        let _ = gtk_sys::gtk_widget_add_tick_callback(widget.to_glib_none().0, Some(tick::<MSG>), data as gpointer,
+            Some(destroy::<MSG>));
+    }
+}
+
+unsafe extern "C" fn destroy<MSG>(data: gpointer) {
+    drop(Box::from_raw(data as *mut EventStream<MSG>));
+}
+
+unsafe extern "C" fn tick<MSG: 'static>(_widget: *mut GtkWidget, _frame_clock: *mut GdkFrameClock, data: gpointer)
+    -> gboolean
+{
+    let stream = &*(data as *const EventStream<MSG>);
+    if !stream.is_closed() {
+        stream.flush();
+    }
+    // The queue is empty after a flush: the stream adds another tick callback for the next message.
+    GFALSE
+}
@@ -0,0 +1,58 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! GTK+ can only be used from the thread where it was initialized, while every test runs in its own
+//! thread: the tests using GTK+ send their body to a single GTK+ thread.
+
+use std::panic;
+use std::sync::{Mutex, ONCE_INIT, Once};
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+
+use super::init_gtk;
+
+type Job = (fn(), Sender<thread::Result<()>>);
+
+static INIT: Once = ONCE_INIT;
+static mut SENDER: *const Mutex<Sender<Job>> = 0 as *const _;
+
+/// Run `test` in the GTK+ thread, propagating its panic.
+pub fn run(test: fn()) {
+    INIT.call_once(|| {
+        let (sender, receiver) = channel::<Job>();
+        let _ = thread::spawn(move || {
+            init_gtk();
+            for (test, result) in receiver {
+                let _ = result.send(panic::catch_unwind(test));
+            }
+        });
+        unsafe {
+            SENDER = Box::into_raw(Box::new(Mutex::new(sender)));
+        }
+    });
+    let (result_sender, result) = channel();
+    unsafe {
+        (*SENDER).lock().unwrap().send((test, result_sender)).expect("the GTK+ thread stopped");
+    }
+    if let Err(error) = result.recv().expect("the GTK+ thread stopped") {
+        panic::resume_unwind(error);
+    }
+}
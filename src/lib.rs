@@ -42,20 +42,9 @@
 
 /*
  * TODO: look at how Elm works with the <canvas> element.
- * TODO: allow adding arbitrary methods in the impl for the #[widget] to allow updating the models
- * in method external to the trait.
  * TODO: support msg variant with multiple values?
-   TODO: after switching to futures-glib, remove the unnecessary Arc, Mutex and Clone.
- * FIXME: the widget-list example can trigger (and is broken) the following after removing widgets, adding new
- * widgets again and using these new widgets:
- * GLib-CRITICAL **: g_io_channel_read_unichar: assertion 'channel->is_readable' failed
- *
  * TODO: the widget names should start with __relm_field_.
  *
- * TODO: for the #[widget] attribute allow pattern matching by creating a function update(&mut
- * self, Quit: Msg, model: &mut Model) so that we can separate the update function in multiple
- * functions.
- *
  * TODO: reset widget name counters when creating new widget?
  *
  * TODO: refactor the code.
@@ -71,56 +60,77 @@
  * TODO: convert GTK+ callback to Stream (does not seem worth it, nor convenient since it will
  * still need to use USFC for the callback method).
  *
- * These probably won't be needed anymore when switching to futures-glib (single-threaded model).
  * TODO: use weak pointers to avoid leaking.
- * TODO: try to avoid having two update() functions by adding the futures to a struct that's
- * returned from the update() function and these futures will then be added to the tokio loop (that
- * probably requires boxing the futures, which we want to avoid).
- * TODO: should have a free function to delete the stream in connect_recv.
+ * TODO: avoid boxing the futures in Commands.
  * TODO: automatically create the update_command() function from the events present in the view
  * (or by splitting the update() fucntion).
- * TODO: try tk-easyloop in another branch.
  */
 
+#[macro_use]
 extern crate futures;
+extern crate gdk_sys;
 extern crate glib;
-extern crate glib_itc;
+extern crate glib_sys;
 extern crate gobject_sys;
 extern crate gtk;
 extern crate gtk_sys;
 #[macro_use]
 extern crate log;
 extern crate relm_core;
+#[cfg(test)]
+extern crate serde_json;
 
+mod command;
 mod component;
+mod component_list;
+mod computed;
 mod container;
+mod executor;
+mod flow;
+mod frame;
+#[cfg(test)]
+mod gtk_thread;
+mod inspector;
 mod macros;
+mod observer;
 mod stream;
+mod test_component;
+mod vdom;
 mod widget;
 
+use std::cell::RefCell;
 use std::ops::Deref;
-use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use futures::{Future, Stream};
-use glib::Continue;
 #[doc(hidden)]
 pub use glib::Cast;
 #[doc(hidden)]
 pub use glib::object::Downcast;
 #[doc(hidden)]
 pub use glib::translate::{FromGlibPtrNone, ToGlib};
-use glib_itc::{Receiver, channel};
 #[doc(hidden)]
 pub use gobject_sys::g_object_new;
-use relm_core::Core;
 #[doc(hidden)]
-pub use relm_core::{EventStream, Handle, Remote};
+pub use relm_core::{Core, EventStream, Handle};
+pub use relm_core::{Interval, Overflow, Policy, QueueMetrics, Timeout};
 
+pub use command::Commands;
 use component::Comp;
 pub use container::{Container, ContainerWidget, RelmContainer};
-pub use component::Component;
-use stream::ToStream;
+use executor::Executor;
+pub use executor::SubscriptionHandle;
+pub use flow::FlowChildren;
+pub use inspector::Inspector;
+use observer::{observe, observe_if};
+pub use observer::{ChromeTrace, Phase, UpdateObserver, add_update_observer, set_slow_update_threshold};
+pub use component::{Component, ObserverHandle};
+pub use component_list::{ComponentList, ListContainer};
+pub use computed::Computed;
+use stream::{Emit, ToStream};
+pub use test_component::{TestComponent, TestRuntime};
+pub use vdom::{PropertyValue, VDom, VWidget};
 pub use widget::Widget;
 
 /// Dummy macro to be used with `#[derive(Widget)]`.
@@ -135,31 +145,20 @@ macro_rules! impl_widget {
 
 macro_rules! relm_connect {
     ($_self:expr, $to_stream:expr, $success_callback:expr, $failure_callback:expr) => {{
-        let event_stream = $_self.stream.clone();
         let fail_event_stream = $_self.stream.clone();
         let stream = $to_stream.to_stream();
-        stream.map_err(move |error| {
-            fail_event_stream.emit($failure_callback(error));
-            ()
-        })
-            .for_each(move |result| {
-                event_stream.emit($success_callback(result));
-                Ok::<(), STREAM::Error>(())
-            }
-            .map_err(|_| ()))
+        Emit::new($_self.stream.clone(), stream.map($success_callback))
+            .map_err(move |error| {
+                fail_event_stream.emit($failure_callback(error));
+            })
     }};
 }
 
 macro_rules! relm_connect_ignore {
     ($_self:expr, $to_stream:expr, $success_callback:expr) => {{
-        let event_stream = $_self.stream.clone();
         let stream = $to_stream.to_stream();
-        stream.map_err(|_| ())
-            .for_each(move |result| {
-                event_stream.emit($success_callback(result));
-                Ok::<(), STREAM::Error>(())
-            }
-            .map_err(|_| ()))
+        Emit::new($_self.stream.clone(), stream.map($success_callback))
+            .map_err(|_| ())
     }};
 }
 
@@ -215,14 +214,23 @@ impl<T> Drop for ManuallyDrop<T> {
     }
 }
 
-/// Handle connection of futures to send messages to the [`update()`](trait.Widget.html#tymethod.update) and
-/// [`update_command()`](trait.Widget.html#method.update_command) methods.
-pub struct Relm<MSG: Clone + DisplayVariant> {
-    handle: Handle,
+/// Handle connection of futures to send messages to the [`update()`](trait.Widget.html#method.update) and
+/// [`update_with_commands()`](trait.Widget.html#method.update_with_commands) methods.
+pub struct Relm<MSG: DisplayVariant> {
+    executor: Executor,
     stream: EventStream<MSG>,
 }
 
-impl<MSG: Clone + DisplayVariant + Send + 'static> Relm<MSG> {
+impl<MSG: DisplayVariant> Clone for Relm<MSG> {
+    fn clone(&self) -> Self {
+        Relm {
+            executor: self.executor.clone(),
+            stream: self.stream.clone(),
+        }
+    }
+}
+
+impl<MSG: DisplayVariant + 'static> Relm<MSG> {
     #[cfg(feature = "use_impl_trait")]
     pub fn connect<CALLBACK, FAILCALLBACK, STREAM, TOSTREAM>(&self, to_stream: TOSTREAM,
             success_callback: CALLBACK, failure_callback: FAILCALLBACK) -> impl Future<Item=(), Error=()>
@@ -242,11 +250,6 @@ impl<MSG: Clone + DisplayVariant + Send + 'static> Relm<MSG> {
     /// This function does not spawn the future.
     /// You'll usually want to use [`Relm::connect_exec()`](struct.Relm.html#method.connect_exec) to both connect and
     /// spawn the future.
-    ///
-    /// ## Warning
-    /// This function **must** be executed of the tokio thread, i.e. in the
-    /// [`subscriptions()`](trait.Widget.html#method.subscriptions) or
-    /// [`update_command()`](trait.Widget.html#method.update_command) methods.
     pub fn connect<CALLBACK, FAILCALLBACK, STREAM, TOSTREAM>(&self, to_stream: TOSTREAM, success_callback: CALLBACK,
             failure_callback: FAILCALLBACK) -> Box<Future<Item=(), Error=()>>
         where CALLBACK: Fn(STREAM::Item) -> MSG + 'static,
@@ -271,11 +274,6 @@ impl<MSG: Clone + DisplayVariant + Send + 'static> Relm<MSG> {
     #[cfg(not(feature = "use_impl_trait"))]
     /// This function is the same as [`Relm::connect()`](struct.Relm.html#method.connect) except it does not take a
     /// `failure_callback`; hence, it ignores the errors.
-    ///
-    /// ## Warning
-    /// This function **must** be executed of the tokio thread, i.e. in the
-    /// [`subscriptions()`](trait.Widget.html#method.subscriptions) or
-    /// [`update_command()`](trait.Widget.html#method.update_command) methods.
     pub fn connect_ignore_err<CALLBACK, STREAM, TOSTREAM>(&self, to_stream: TOSTREAM, success_callback: CALLBACK) ->
             Box<Future<Item=(), Error=()>>
         where CALLBACK: Fn(STREAM::Item) -> MSG + 'static,
@@ -285,49 +283,96 @@ impl<MSG: Clone + DisplayVariant + Send + 'static> Relm<MSG> {
         Box::new(relm_connect_ignore!(self, to_stream, success_callback))
     }
 
-    /// Connect the future `to_stream` and spawn it on the tokio main loop.
+    /// Connect the future `to_stream` and spawn it on the GLib main loop.
     ///
-    /// ## Warning
-    /// This function **must** be executed of the tokio thread, i.e. in the
-    /// [`subscriptions()`](trait.Widget.html#method.subscriptions) or
-    /// [`update_command()`](trait.Widget.html#method.update_command) methods.
+    /// The returned handle can be used to cancel the future.
     pub fn connect_exec<CALLBACK, FAILCALLBACK, STREAM, TOSTREAM>(&self, to_stream: TOSTREAM, callback: CALLBACK,
-            failure_callback: FAILCALLBACK)
+            failure_callback: FAILCALLBACK) -> SubscriptionHandle
         where CALLBACK: Fn(STREAM::Item) -> MSG + 'static,
               FAILCALLBACK: Fn(STREAM::Error) -> MSG + 'static,
               STREAM: Stream + 'static,
               TOSTREAM: ToStream<STREAM, Item=STREAM::Item, Error=STREAM::Error> + 'static,
     {
-        self.exec(self.connect(to_stream, callback, failure_callback));
+        self.exec(self.connect(to_stream, callback, failure_callback))
     }
 
-    /// Connect the future `to_stream` and spawn it on the tokio main loop, ignoring any error.
+    /// Connect the future `to_stream` and spawn it on the GLib main loop, ignoring any error.
     ///
-    /// ## Warning
-    /// This function **must** be executed of the tokio thread, i.e. in the
-    /// [`subscriptions()`](trait.Widget.html#method.subscriptions) or
-    /// [`update_command()`](trait.Widget.html#method.update_command) methods.
+    /// The returned handle can be used to cancel the future.
     pub fn connect_exec_ignore_err<CALLBACK, STREAM, TOSTREAM>(&self, to_stream: TOSTREAM, callback: CALLBACK)
+            -> SubscriptionHandle
         where CALLBACK: Fn(STREAM::Item) -> MSG + 'static,
               STREAM: Stream + 'static,
               TOSTREAM: ToStream<STREAM, Item=STREAM::Item, Error=STREAM::Error> + 'static,
     {
-        self.exec(self.connect_ignore_err(to_stream, callback));
+        self.exec(self.connect_ignore_err(to_stream, callback))
+    }
+
+    /// Dispatch the messages emitted during a frame together, at the next tick of the frame clock of the root
+    /// widget, instead of at the next iteration of the main loop.
+    ///
+    /// This must be called in [`subscriptions()`](trait.Widget.html#method.subscriptions) and the messages are
+    /// only dispatched while the root widget is realized.
+    pub fn batch_per_frame(&self) {
+        self.stream.set_batched(true);
     }
 
-    /// Spawn a future in the tokio event loop.
+    /// Spawn a future in the GLib main loop.
     ///
-    /// ## Warning
-    /// This function **must** be executed of the tokio thread, i.e. in the
-    /// [`subscriptions()`](trait.Widget.html#method.subscriptions) or
-    /// [`update_command()`](trait.Widget.html#method.update_command) methods.
-    pub fn exec<FUTURE: Future<Item=(), Error=()> + 'static>(&self, future: FUTURE) {
-        self.handle.spawn(future);
+    /// The returned handle can be used to cancel the future.
+    /// The future is also cancelled when the component is destroyed.
+    pub fn exec<FUTURE: Future<Item=(), Error=()> + 'static>(&self, future: FUTURE) -> SubscriptionHandle {
+        self.executor.spawn(future)
     }
 
-    /// Get a handle to the tokio event loop.
+    /// Get a handle to the tokio reactor, to create I/O objects.
+    ///
+    /// The timers are created with [`Relm::timeout()`](struct.Relm.html#method.timeout) and
+    /// [`Relm::interval()`](struct.Relm.html#method.interval).
     pub fn handle(&self) -> &Handle {
-        &self.handle
+        self.executor.handle()
+    }
+
+    /// Create a stream yielding `()` every `period`.
+    ///
+    /// Unlike a tokio `Interval`, it uses the virtual clock of a [`TestRuntime`](struct.TestRuntime.html) in the
+    /// tests:
+    /// ```ignore
+    /// relm.connect_exec_ignore_err(relm.interval(Duration::from_secs(1)), Tick);
+    /// ```
+    pub fn interval(&self, period: Duration) -> Interval {
+        self.executor.core().interval(period)
+    }
+
+    /// Limit the number of pending messages of the widget to `capacity`, applying `overflow` to the messages
+    /// emitted when the queue is full.
+    ///
+    /// With [`Overflow::Block`](enum.Overflow.html#variant.Block), the futures connected with this `Relm` stop
+    /// being polled until the queue has room again.
+    /// The length of the queue can be monitored with [`EventStream::metrics()`](struct.EventStream.html#method.metrics).
+    pub fn set_capacity(&self, capacity: usize, overflow: Overflow<MSG>) {
+        self.stream.set_capacity(capacity, overflow);
+    }
+
+    /// Set how the messages of `variant`, the name given by [`DisplayVariant`](trait.DisplayVariant.html), are
+    /// dispatched to [`update()`](trait.Widget.html#method.update).
+    ///
+    /// This is useful for the messages emitted at a high frequency, like scroll events or the items of a
+    /// websocket stream:
+    /// ```ignore
+    /// relm.set_policy("Scroll", Policy::Throttle(Duration::from_millis(100)));
+    /// ```
+    pub fn set_policy(&self, variant: &'static str, policy: Policy) {
+        self.stream.set_variant(|event: &MSG| event.display_variant());
+        self.stream.set_policy(variant, policy);
+    }
+
+    /// Create a future completing after `duration`.
+    ///
+    /// Like [`Relm::interval()`](struct.Relm.html#method.interval), it uses the virtual clock of a
+    /// [`TestRuntime`](struct.TestRuntime.html) in the tests.
+    pub fn timeout(&self, duration: Duration) -> Timeout {
+        self.executor.core().timeout(duration)
     }
 
     /// Get the event stream of the widget.
@@ -337,17 +382,26 @@ impl<MSG: Clone + DisplayVariant + Send + 'static> Relm<MSG> {
     }
 }
 
-/// Handle to the tokio event loop, to be used from the GTK+ thread.
-#[derive(Clone)]
+/// Handle to the widget's model and event stream, to be used in the view.
 pub struct RemoteRelm<WIDGET: Widget> {
-    model: Arc<Mutex<WIDGET::Model>>,
-    remote: Remote,
+    core: Core,
+    model: Rc<RefCell<WIDGET::Model>>,
     stream: EventStream<WIDGET::Msg>,
 }
 
+impl<WIDGET: Widget> Clone for RemoteRelm<WIDGET> {
+    fn clone(&self) -> Self {
+        RemoteRelm {
+            core: self.core.clone(),
+            model: self.model.clone(),
+            stream: self.stream.clone(),
+        }
+    }
+}
+
 impl<WIDGET: Widget> RemoteRelm<WIDGET> {
     /// Get the shared model.
-    pub fn model(&self) -> &Arc<Mutex<WIDGET::Model>> {
+    pub fn model(&self) -> &Rc<RefCell<WIDGET::Model>> {
         &self.model
     }
 
@@ -358,13 +412,12 @@ impl<WIDGET: Widget> RemoteRelm<WIDGET> {
     }
 }
 
-fn create_widget_test<WIDGET>(remote: &Remote, model_param: WIDGET::ModelParam) -> Component<WIDGET>
+fn create_widget_test<WIDGET>(core: &Core, model_param: WIDGET::ModelParam) -> Component<WIDGET>
     where WIDGET: Widget + Clone + 'static,
-          WIDGET::Model: Clone + Send,
-          WIDGET::Msg: Clone + DisplayVariant + Send + 'static,
+          WIDGET::Msg: DisplayVariant + 'static,
 {
-    let component = create_widget(remote, model_param);
-    init_component::<WIDGET>(&component, remote);
+    let component = create_widget(core, model_param);
+    init_component::<WIDGET>(&component);
     Component::new(component)
 }
 
@@ -373,81 +426,80 @@ fn create_widget_test<WIDGET>(remote: &Remote, model_param: WIDGET::ModelParam)
 pub fn create_component<CHILDWIDGET, WIDGET>(relm: &RemoteRelm<WIDGET>,
         model_param: CHILDWIDGET::ModelParam) -> Component<CHILDWIDGET>
     where CHILDWIDGET: Widget + 'static,
-          CHILDWIDGET::Model: Clone + Send,
-          CHILDWIDGET::Msg: Clone + DisplayVariant + Send + 'static,
+          CHILDWIDGET::Msg: DisplayVariant + 'static,
           WIDGET: Widget,
 {
-    let component = create_widget::<CHILDWIDGET>(&relm.remote, model_param);
-    init_component::<CHILDWIDGET>(&component, &relm.remote);
+    let component = create_widget::<CHILDWIDGET>(&relm.core, model_param);
+    init_component::<CHILDWIDGET>(&component);
     Component::new(component)
 }
 
-fn create_widget<WIDGET>(remote: &Remote, model_param: WIDGET::ModelParam) -> Comp<WIDGET>
+fn create_widget<WIDGET>(core: &Core, model_param: WIDGET::ModelParam) -> Comp<WIDGET>
     where WIDGET: Widget + 'static,
-          WIDGET::Msg: Clone + DisplayVariant + 'static,
+          WIDGET::Msg: DisplayVariant + 'static,
 {
-    let (sender, mut receiver) = channel();
-    let stream = EventStream::new(Arc::new(Mutex::new(sender)));
+    let stream = EventStream::new();
 
-    let (widget, model) = {
-        let model = Arc::new(Mutex::new(WIDGET::model(model_param)));
+    let model = Rc::new(RefCell::new(WIDGET::model(model_param)));
+    let widget = {
         let relm = RemoteRelm {
-            model: model,
-            remote: remote.clone(),
+            core: core.clone(),
+            model: model.clone(),
             stream: stream.clone(),
         };
-        let view = {
-            let model_guard = relm.model.lock().unwrap();
-            WIDGET::view(&relm, &*model_guard)
-        };
-        (view, relm.model)
+        let model = model.borrow();
+        observe(WIDGET::type_name(), Phase::View, None, || WIDGET::view(&relm, &*model))
     };
-    {
-        let mut model_guard = model.lock().unwrap();
-        widget.init_view(&mut *model_guard);
-    }
-
-    {
-        let mut widget = widget.clone();
-        let stream = stream.clone();
-        let model = model.clone();
-        receiver.connect_recv(move || {
-            if let Some(event) = stream.pop_ui_events() {
-                let mut model = model.lock().unwrap();
-                update_widget(&mut widget, event, &mut *model);
-            }
-            Continue(true)
-        });
-    }
+    observe(WIDGET::type_name(), Phase::InitView, None, || widget.init_view(&mut *model.borrow_mut()));
 
-    Comp {
-        model: model,
-        _receiver: Arc::new(receiver),
-        stream: stream,
-        widget: widget,
-    }
+    Comp::new(Executor::new(core), model, stream, widget)
 }
 
-fn init_component<WIDGET>(component: &Comp<WIDGET>, remote: &Remote)
+fn init_component<WIDGET>(component: &Comp<WIDGET>)
     where WIDGET: Widget + 'static,
-          WIDGET::Model: Send,
-          WIDGET::Msg: Clone + DisplayVariant + Send + 'static,
+          WIDGET::Msg: DisplayVariant + 'static,
 {
-    let stream = component.stream.clone();
+    let relm = Relm {
+        executor: component.executor.clone(),
+        stream: component.stream.clone(),
+    };
+    observe(WIDGET::type_name(), Phase::Subscriptions, None, || WIDGET::subscriptions(&relm));
+    if component.stream.is_batched() {
+        frame::dispatch_per_frame(component.widget.root(), component.stream.clone());
+    }
+
+    // The callback of the stream is a Fn.
+    let widget = RefCell::new(component.widget.clone());
+    let inspectors = component.inspectors.clone();
     let model = component.model.clone();
-    remote.spawn(move |handle| {
-        let relm = Relm {
-            handle: handle.clone(),
-            stream: stream.clone(),
-        };
-        WIDGET::subscriptions(&relm);
-        let event_future = stream.for_each(move |event| {
-            let mut model = model.lock().unwrap();
-            WIDGET::update_command(&relm, event, &mut *model);
-            Ok(())
-        });
-        handle.spawn(event_future);
-        Ok(())
+    component.stream.set_callback(move |event| {
+        let variant = event.display_variant();
+        let mut commands = Commands::new(relm.clone());
+        {
+            let mut model = model.borrow_mut();
+            // Clone the list since an inspector could be added during the update.
+            let inspectors = inspectors.borrow().clone();
+            for inspector in &inspectors {
+                inspector.before_update(&event, &*model);
+            }
+            let time = Instant::now();
+            if let Some(command_event) = event.clone_for_command() {
+                #[allow(deprecated)]
+                let update_command = WIDGET::update_command;
+                let spawned = relm.executor.spawned_count();
+                observe_if(WIDGET::type_name(), Phase::UpdateCommand, Some(variant),
+                    || update_command(&relm, command_event, &mut *model),
+                    || relm.executor.spawned_count() != spawned);
+            }
+            update_widget(&mut *widget.borrow_mut(), event, &mut *model, &mut commands);
+            let duration = time.elapsed();
+            for inspector in &inspectors {
+                inspector.after_update(&*model, duration);
+            }
+        }
+        if !commands.is_empty() {
+            observe(WIDGET::type_name(), Phase::UpdateCommand, Some(variant), || commands.spawn());
+        }
     });
 }
 
@@ -512,26 +564,24 @@ fn init_gtk() {
 /// ```
 pub fn init_test<WIDGET>(model_param: WIDGET::ModelParam) -> Result<Component<WIDGET>, ()>
     where WIDGET: Widget + Clone + 'static,
-          WIDGET::Model: Clone + Send,
-          WIDGET::Msg: Clone + DisplayVariant + Send + 'static
+          WIDGET::Msg: DisplayVariant + 'static
 {
     init_gtk();
 
-    let remote = Core::run();
-    let component = create_widget_test::<WIDGET>(&remote, model_param);
+    let core = Core::new().map_err(|_| ())?;
+    let component = create_widget_test::<WIDGET>(&core, model_param);
     Ok(component)
 }
 
 fn init<WIDGET>(model_param: WIDGET::ModelParam) -> Result<Component<WIDGET>, ()>
     where WIDGET: Widget + 'static,
-          WIDGET::Model: Clone + Send,
-          WIDGET::Msg: Clone + DisplayVariant + Send + 'static
+          WIDGET::Msg: DisplayVariant + 'static
 {
     gtk::init()?;
 
-    let remote = Core::run();
-    let component = create_widget::<WIDGET>(&remote, model_param);
-    init_component::<WIDGET>(&component, &remote);
+    let core = Core::new().map_err(|_| ())?;
+    let component = create_widget::<WIDGET>(&core, model_param);
+    init_component::<WIDGET>(&component);
     Ok(Component::new(component))
 }
 
@@ -588,39 +638,20 @@ fn init<WIDGET>(model_param: WIDGET::ModelParam) -> Result<Component<WIDGET>, ()
 /// ```
 pub fn run<WIDGET>(model_param: WIDGET::ModelParam) -> Result<(), ()>
     where WIDGET: Widget + 'static,
-          WIDGET::Model: Clone + Send,
           WIDGET::ModelParam: Default,
-          WIDGET::Msg: Send,
 {
     let _component = init::<WIDGET>(model_param)?;
     gtk::main();
     Ok(())
 }
 
-fn update_widget<WIDGET>(widget: &mut WIDGET, event: WIDGET::Msg, model: &mut WIDGET::Model)
+fn update_widget<WIDGET>(widget: &mut WIDGET, event: WIDGET::Msg, model: &mut WIDGET::Model,
+        commands: &mut Commands<WIDGET::Msg>)
     where WIDGET: Widget,
+          WIDGET::Msg: DisplayVariant + 'static,
 {
-    if cfg!(debug_assertions) {
-        let time = SystemTime::now();
-        let debug = event.display_variant();
-        let debug =
-            if debug.len() > 100 {
-                format!("{}…", &debug[..100])
-            }
-            else {
-                debug.to_string()
-            };
-        widget.update(event, model);
-        if let Ok(duration) = time.elapsed() {
-            let ms = duration.subsec_nanos() as u64 / 1_000_000 + duration.as_secs() * 1000;
-            if ms >= 200 {
-                warn!("The update function was slow to execute for message {}: {}ms", debug, ms);
-            }
-        }
-    }
-    else {
-        widget.update(event, model)
-    }
+    let variant = event.display_variant();
+    observe(WIDGET::type_name(), Phase::Update, Some(variant), || widget.update_with_commands(event, model, commands))
 }
 
 /// Format trait for enum variants.
@@ -632,10 +663,25 @@ fn update_widget<WIDGET>(widget: &mut WIDGET, event: WIDGET::Msg, model: &mut WI
 pub trait DisplayVariant {
     /// Formats the current variant of the enum.
     fn display_variant(&self) -> &'static str;
+
+    /// Clone the message given to the deprecated
+    /// [`update_command()`](trait.Widget.html#method.update_command) method.
+    ///
+    /// This is implemented by `#[derive(Msg)]`, which also implements `Clone`.
+    #[doc(hidden)]
+    fn clone_for_command(&self) -> Option<Self>
+        where Self: Sized,
+    {
+        None
+    }
 }
 
 impl DisplayVariant for () {
     fn display_variant(&self) -> &'static str {
         ""
     }
+
+    fn clone_for_command(&self) -> Option<Self> {
+        Some(())
+    }
 }
@@ -19,6 +19,30 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
+/// Assert that a message matching `$pattern` was emitted by the
+/// [`TestComponent`](struct.TestComponent.html) `$component`.
+///
+/// ```ignore
+/// component.send(Msg::Load);
+/// assert_emitted!(component, Msg::Loaded(_));
+/// ```
+#[macro_export]
+macro_rules! assert_emitted {
+    ($component:expr, $pattern:pat) => {{
+        #[allow(unreachable_patterns)]
+        let emitted = $component.has_emitted(|msg| match *msg {
+            $pattern => true,
+            _ => false,
+        });
+        if !emitted {
+            let messages: Vec<_> = $component.messages().iter()
+                .map(|msg| $crate::DisplayVariant::display_variant(msg))
+                .collect();
+            panic!("no message matching `{}` was emitted, emitted messages: {:?}", stringify!($pattern), messages);
+        }
+    }};
+}
+
 /// Connect events to sending a message.
 ///
 /// Rule #1:
@@ -36,6 +60,7 @@
 ///
 /// Rule #4:
 /// Send `$msg` to `$widget` when the `$message` is received on `$stream`.
+/// The messages of `$stream` are cloned to be matched against `$message`.
 #[macro_export]
 macro_rules! connect {
     // Connect to a GTK+ widget event, sending a message to another widget.
@@ -65,7 +90,7 @@ macro_rules! connect {
 
         let stream = $relm.stream().clone();
         $widget.$event(move |$($args),*| {
-            let $model = &mut *$model.lock().unwrap();
+            let $model = &mut *$model.borrow_mut();
             let (msg, return_value) = $msg;
             let msg: Option<_> = msg.into();
             if let Some(msg) = msg {
@@ -93,16 +118,19 @@ macro_rules! connect {
 
     // Connect to a message reception.
     // TODO: create another macro rule accepting multiple patterns.
+    // The observer is removed when the destination component is destroyed.
     ($src_component:ident @ $message:pat, $dst_component:ident, $msg:expr) => {
         let stream = $dst_component.stream().clone();
-        $src_component.stream().observe(move |msg| {
+        let src_stream = $src_component.stream().clone();
+        let observer_id = src_stream.observe(move |msg| {
             #[allow(unreachable_patterns)]
-            match msg {
+            match msg.clone() {
                 $message =>  {
                     stream.emit($msg);
                 },
                 _ => (),
             }
         });
+        $dst_component.stream().on_close(move || src_stream.unobserve(observer_id));
     };
 }
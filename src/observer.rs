@@ -0,0 +1,404 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Method of a [`Widget`](trait.Widget.html) called by relm, given to the `UpdateObserver`s.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Phase {
+    /// [`Widget::init_view()`](trait.Widget.html#method.init_view).
+    InitView,
+    /// [`Widget::subscriptions()`](trait.Widget.html#method.subscriptions).
+    Subscriptions,
+    /// [`Widget::update_with_commands()`](trait.Widget.html#method.update_with_commands), which
+    /// calls [`Widget::update()`](trait.Widget.html#method.update) by default.
+    Update,
+    /// [`Widget::update_command()`](trait.Widget.html#method.update_command), or the spawning of
+    /// the `Commands` added by `update_with_commands()`.
+    /// Only recorded when a future is actually spawned.
+    UpdateCommand,
+    /// [`Widget::view()`](trait.Widget.html#tymethod.view).
+    View,
+}
+
+impl Display for Phase {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let name =
+            match *self {
+                Phase::InitView => "init_view",
+                Phase::Subscriptions => "subscriptions",
+                Phase::Update => "update",
+                Phase::UpdateCommand => "update_command",
+                Phase::View => "view",
+            };
+        write!(formatter, "{}", name)
+    }
+}
+
+/// Observer of the calls to the methods of the widgets, added with
+/// [`add_update_observer()`](fn.add_update_observer.html) before calling [`run()`](fn.run.html), to profile or
+/// trace an application.
+///
+/// `component` is the name returned by [`Widget::type_name()`](trait.Widget.html#method.type_name) and
+/// `message` is the variant of the message for the updates.
+pub trait UpdateObserver {
+    /// Method called before the method `phase` of the widget.
+    ///
+    /// For `Phase::UpdateCommand`, it is called after the method, right before `end()`, since this
+    /// phase is only recorded when a future was spawned.
+    fn start(&self, component: &'static str, phase: Phase, message: Option<&'static str>);
+
+    /// Method called after the method `phase` of the widget, with the time spent in this method.
+    fn end(&self, component: &'static str, phase: Phase, message: Option<&'static str>, duration: Duration);
+}
+
+thread_local! {
+    static OBSERVERS: RefCell<Vec<Rc<UpdateObserver>>> = RefCell::new(vec![]);
+    static SLOW_THRESHOLD: Cell<Option<Duration>> = Cell::new(
+        if cfg!(debug_assertions) {
+            Some(Duration::from_millis(200))
+        }
+        else {
+            None
+        });
+}
+
+/// Add an observer of the calls to the methods of the widgets created on this thread.
+pub fn add_update_observer<OBSERVER: UpdateObserver + 'static>(observer: OBSERVER) {
+    OBSERVERS.with(|observers| observers.borrow_mut().push(Rc::new(observer)));
+}
+
+/// Set the duration after which a warning is logged for a slow method of a widget, or `None` to
+/// disable the warning.
+///
+/// The default is 200ms in debug mode and `None` in release mode.
+pub fn set_slow_update_threshold(threshold: Option<Duration>) {
+    SLOW_THRESHOLD.with(|slow_threshold| slow_threshold.set(threshold));
+}
+
+/*
+ * Call the method `phase` of the widget, notifying the observers.
+ */
+pub fn observe<CALLBACK, RESULT>(component: &'static str, phase: Phase, message: Option<&'static str>,
+        callback: CALLBACK) -> RESULT
+    where CALLBACK: FnOnce() -> RESULT,
+{
+    // Clone the list since an observer could be added during the call.
+    let observers = OBSERVERS.with(|observers| observers.borrow().clone());
+    let threshold = SLOW_THRESHOLD.with(Cell::get);
+    if observers.is_empty() && threshold.is_none() {
+        return callback();
+    }
+
+    for observer in &observers {
+        observer.start(component, phase, message);
+    }
+    let time = Instant::now();
+    let result = callback();
+    let duration = time.elapsed();
+    for observer in &observers {
+        observer.end(component, phase, message, duration);
+    }
+
+    warn_if_slow(threshold, component, phase, message, duration);
+    result
+}
+
+/*
+ * Call the method `phase` of the widget, notifying the observers only if `record` returns true once
+ * the method returned.
+ */
+pub fn observe_if<CALLBACK, RECORD, RESULT>(component: &'static str, phase: Phase, message: Option<&'static str>,
+        callback: CALLBACK, record: RECORD) -> RESULT
+    where CALLBACK: FnOnce() -> RESULT,
+          RECORD: FnOnce() -> bool,
+{
+    let observers = OBSERVERS.with(|observers| observers.borrow().clone());
+    let threshold = SLOW_THRESHOLD.with(Cell::get);
+    if observers.is_empty() && threshold.is_none() {
+        return callback();
+    }
+
+    let time = Instant::now();
+    let result = callback();
+    let duration = time.elapsed();
+    if !record() {
+        return result;
+    }
+    for observer in &observers {
+        observer.start(component, phase, message);
+    }
+    for observer in &observers {
+        observer.end(component, phase, message, duration);
+    }
+
+    warn_if_slow(threshold, component, phase, message, duration);
+    result
+}
+
+fn warn_if_slow(threshold: Option<Duration>, component: &'static str, phase: Phase, message: Option<&'static str>,
+        duration: Duration)
+{
+    if let Some(threshold) = threshold {
+        if duration >= threshold {
+            let message = message.map(|message| format!(" for message {}", message)).unwrap_or_default();
+            warn!("The {} method of {} was slow to execute{}: {}ms", phase, component, message, millis(duration));
+        }
+    }
+}
+
+/// `UpdateObserver` writing the calls to the methods of the widgets in the Chrome trace event
+/// format, to be opened in `chrome://tracing`.
+pub struct ChromeTrace<WRITER: Write> {
+    origin: Instant,
+    writer: RefCell<WRITER>,
+}
+
+impl<WRITER: Write> ChromeTrace<WRITER> {
+    /// Create an observer writing the trace to `writer`, usually a file.
+    ///
+    /// The trace is a JSON array which is not closed, as allowed by the format, so that the file
+    /// is valid even if the application does not exit normally.
+    pub fn new(mut writer: WRITER) -> Self {
+        if let Err(error) = writeln!(writer, "[") {
+            warn!("Cannot write the trace: {}", error);
+        }
+        ChromeTrace {
+            origin: Instant::now(),
+            writer: RefCell::new(writer),
+        }
+    }
+}
+
+impl<WRITER: Write> UpdateObserver for ChromeTrace<WRITER> {
+    fn start(&self, _component: &'static str, _phase: Phase, _message: Option<&'static str>) {
+    }
+
+    fn end(&self, component: &'static str, phase: Phase, message: Option<&'static str>, duration: Duration) {
+        let end = self.origin.elapsed();
+        let start = if end > duration { end - duration } else { Duration::from_secs(0) };
+        let name =
+            match message {
+                Some(message) => format!("{}::{}({})", component, phase, message),
+                None => format!("{}::{}", component, phase),
+            };
+        let result = writeln!(self.writer.borrow_mut(),
+            "{{\"name\": \"{}\", \"cat\": \"{}\", \"ph\": \"X\", \"ts\": {}, \"dur\": {}, \"pid\": 1, \"tid\": 1}},",
+            escape_json(&name), phase, micros(start), micros(duration));
+        if let Err(error) = result {
+            warn!("Cannot write the trace: {}", error);
+        }
+    }
+}
+
+fn escape_json(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + duration.subsec_nanos() as u64 / 1000
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + duration.subsec_nanos() as u64 / 1_000_000
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::{ONCE_INIT, Once};
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::future;
+    use gtk::Label;
+    use log::{self, Log, LogLevelFilter, LogMetadata, LogRecord};
+    use serde_json::{self, Value};
+
+    use {Commands, DisplayVariant, RemoteRelm, TestRuntime, Widget, create_widget_test, gtk_thread};
+    use super::{ChromeTrace, OBSERVERS, Phase, UpdateObserver, add_update_observer, observe,
+        set_slow_update_threshold};
+
+    type Call = (&'static str, &'static str, Phase, Option<&'static str>);
+
+    struct Recorder(Rc<RefCell<Vec<Call>>>);
+
+    impl UpdateObserver for Recorder {
+        fn start(&self, component: &'static str, phase: Phase, message: Option<&'static str>) {
+            self.0.borrow_mut().push(("start", component, phase, message));
+        }
+
+        fn end(&self, component: &'static str, phase: Phase, message: Option<&'static str>, _duration: Duration) {
+            self.0.borrow_mut().push(("end", component, phase, message));
+        }
+    }
+
+    enum Msg {
+        Value(i32),
+    }
+
+    impl DisplayVariant for Msg {
+        fn display_variant(&self) -> &'static str {
+            match *self {
+                Msg::Value(_) => "Value",
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct Observed {
+        label: Label,
+    }
+
+    impl Widget for Observed {
+        type Model = i32;
+        type ModelParam = ();
+        type Msg = Msg;
+        type Root = Label;
+
+        fn model(_: ()) -> i32 {
+            0
+        }
+
+        fn root(&self) -> &Label {
+            &self.label
+        }
+
+        fn type_name() -> &'static str {
+            "Observed"
+        }
+
+        fn update_with_commands(&mut self, event: Msg, model: &mut i32, commands: &mut Commands<Msg>) {
+            match event {
+                Msg::Value(value) => {
+                    *model = value;
+                    let _ = commands.exec(future::ok(()));
+                },
+            }
+        }
+
+        fn view(_relm: &RemoteRelm<Self>, _model: &i32) -> Self {
+            Observed {
+                label: Label::new(None),
+            }
+        }
+    }
+
+    #[test]
+    fn phases() {
+        gtk_thread::run(check_phases);
+    }
+
+    fn check_phases() {
+        let calls = Rc::new(RefCell::new(vec![]));
+        add_update_observer(Recorder(calls.clone()));
+        let runtime = TestRuntime::new().unwrap();
+        let component = create_widget_test::<Observed>(runtime.core(), ());
+        component.emit(Msg::Value(1));
+        component.stream().flush();
+        runtime.run_until_stalled();
+        // The observers are shared by the tests running in the GTK+ thread.
+        OBSERVERS.with(|observers| observers.borrow_mut().clear());
+
+        assert_eq!(*calls.borrow(), vec![
+            ("start", "Observed", Phase::View, None),
+            ("end", "Observed", Phase::View, None),
+            ("start", "Observed", Phase::InitView, None),
+            ("end", "Observed", Phase::InitView, None),
+            ("start", "Observed", Phase::Subscriptions, None),
+            ("end", "Observed", Phase::Subscriptions, None),
+            ("start", "Observed", Phase::Update, Some("Value")),
+            ("end", "Observed", Phase::Update, Some("Value")),
+            ("start", "Observed", Phase::UpdateCommand, Some("Value")),
+            ("end", "Observed", Phase::UpdateCommand, Some("Value")),
+        ]);
+        assert_eq!(*component.model().borrow(), 1);
+    }
+
+    thread_local! {
+        static WARNINGS: RefCell<Vec<String>> = RefCell::new(vec![]);
+    }
+
+    // Logger recording the warnings of the current thread.
+    struct Warnings;
+
+    impl Log for Warnings {
+        fn enabled(&self, _metadata: &LogMetadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &LogRecord) {
+            WARNINGS.with(|warnings| warnings.borrow_mut().push(record.args().to_string()));
+        }
+    }
+
+    static LOGGER: Once = ONCE_INIT;
+
+    #[test]
+    fn slow_update() {
+        LOGGER.call_once(|| {
+            log::set_logger(|max_level| {
+                max_level.set(LogLevelFilter::Warn);
+                Box::new(Warnings)
+            }).expect("logger");
+        });
+
+        set_slow_update_threshold(Some(Duration::from_millis(10)));
+        observe("Win", Phase::Update, Some("Fast"), || ());
+        assert!(WARNINGS.with(|warnings| warnings.borrow().is_empty()));
+
+        observe("Win", Phase::Update, Some("Slow"), || thread::sleep(Duration::from_millis(20)));
+        let warnings = WARNINGS.with(|warnings| warnings.borrow().clone());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("The update method of Win was slow to execute for message Slow: "));
+
+        set_slow_update_threshold(None);
+        observe("Win", Phase::Update, Some("Slow"), || thread::sleep(Duration::from_millis(20)));
+        assert_eq!(WARNINGS.with(|warnings| warnings.borrow().len()), 1);
+    }
+
+    #[test]
+    fn chrome_trace() {
+        let trace = ChromeTrace::new(vec![]);
+        trace.start("Win", Phase::View, None);
+        trace.end("Win", Phase::View, None, Duration::from_millis(2));
+        trace.start("Win", Phase::Update, Some("Quoted\"\\"));
+        trace.end("Win", Phase::Update, Some("Quoted\"\\"), Duration::from_millis(3));
+        let trace = String::from_utf8(trace.writer.into_inner()).unwrap();
+
+        // The array is left open, with a trailing comma, for the trace to be valid after a crash.
+        let trace = format!("{}]", trace.trim_end().trim_end_matches(','));
+        let events: Value = serde_json::from_str(&trace).expect("JSON trace");
+        let events = events.as_array().expect("array of events");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["name"], "Win::view");
+        assert_eq!(events[0]["cat"], "view");
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["dur"], 2000);
+        assert_eq!(events[1]["name"], "Win::update(Quoted\"\\)");
+        assert_eq!(events[1]["dur"], 3000);
+    }
+}
@@ -19,7 +19,44 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use futures::{Future, IntoStream, Poll, Stream};
+use futures::{Async, Future, IntoStream, Poll, Stream};
+use relm_core::EventStream;
+
+/// Future emitting the items of a stream on an `EventStream`.
+///
+/// The stream is not polled while the `EventStream` is full with the `Overflow::Block` policy.
+pub struct Emit<STREAM: Stream> {
+    event_stream: EventStream<STREAM::Item>,
+    stream: STREAM,
+}
+
+impl<STREAM: Stream> Emit<STREAM> {
+    pub fn new(event_stream: EventStream<STREAM::Item>, stream: STREAM) -> Self {
+        Emit {
+            event_stream: event_stream,
+            stream: stream,
+        }
+    }
+}
+
+impl<STREAM: Stream> Future for Emit<STREAM>
+    where STREAM::Item: 'static,
+{
+    type Item = ();
+    type Error = STREAM::Error;
+
+    fn poll(&mut self) -> Poll<(), STREAM::Error> {
+        loop {
+            if let Async::NotReady = self.event_stream.poll_ready() {
+                return Ok(Async::NotReady);
+            }
+            match try_ready!(self.stream.poll()) {
+                Some(item) => self.event_stream.emit(item),
+                None => return Ok(Async::Ready(())),
+            }
+        }
+    }
+}
 
 pub struct RelmStream<ERROR, ITEM, STREAM: Stream<Item=ITEM, Error=ERROR>> {
     stream: STREAM,
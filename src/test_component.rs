@@ -0,0 +1,314 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+use std::cell::{Ref, RefCell};
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use gtk;
+
+use super::{Component, Core, DisplayVariant, Widget, create_widget_test, init_gtk};
+
+/// Component created for a test with [`TestComponent::new()`](struct.TestComponent.html#method.new), recording
+/// every message emitted on its event stream.
+///
+/// The messages are dispatched synchronously by [`send()`](struct.TestComponent.html#method.send) and
+/// [`process()`](struct.TestComponent.html#method.process), without running the main loop.
+pub struct TestComponent<WIDGET: Widget> {
+    component: Component<WIDGET>,
+    messages: Rc<RefCell<Vec<WIDGET::Msg>>>,
+    // The runtime created by new(), dropped after the component.
+    runtime: Option<TestRuntime>,
+}
+
+impl<WIDGET> TestComponent<WIDGET>
+    where WIDGET: Widget + 'static,
+          WIDGET::Msg: Clone + DisplayVariant + 'static,
+{
+    /// Initialize GTK+ and create the widget on its own [`TestRuntime`](struct.TestRuntime.html).
+    ///
+    /// Its futures are run by [`process()`](struct.TestComponent.html#method.process), but its timers never
+    /// fire: create the component with [`TestRuntime::component()`](struct.TestRuntime.html#method.component)
+    /// to advance their clock.
+    pub fn new(model_param: WIDGET::ModelParam) -> Result<Self, ()> {
+        let runtime = TestRuntime::new().map_err(|_| ())?;
+        let mut component = runtime.component::<WIDGET>(model_param);
+        component.runtime = Some(runtime);
+        Ok(component)
+    }
+
+    fn from_component(component: Component<WIDGET>) -> Self {
+        let messages = Rc::new(RefCell::new(vec![]));
+        {
+            let messages = messages.clone();
+            // Observers are called as soon as a message is emitted, even if it is not dispatched yet.
+            let _ = component.subscribe(move |msg| messages.borrow_mut().push(msg.clone()));
+        }
+        TestComponent {
+            component: component,
+            messages: messages,
+            runtime: None,
+        }
+    }
+
+    /// Forget the messages recorded so far.
+    pub fn clear_messages(&self) {
+        self.messages.borrow_mut().clear();
+    }
+
+    /// Get the component.
+    pub fn component(&self) -> &Component<WIDGET> {
+        &self.component
+    }
+
+    /// Check whether a message for which `predicate` returns `true` was emitted.
+    pub fn has_emitted<PREDICATE: Fn(&WIDGET::Msg) -> bool>(&self, predicate: PREDICATE) -> bool {
+        self.messages.borrow().iter().any(predicate)
+    }
+
+    /// Get the messages emitted since the creation of the component or the last call to
+    /// [`clear_messages()`](struct.TestComponent.html#method.clear_messages).
+    pub fn messages(&self) -> Vec<WIDGET::Msg> {
+        self.messages.borrow().clone()
+    }
+
+    /// Get the model.
+    ///
+    /// The returned reference must be dropped before sending a message.
+    pub fn model(&self) -> Ref<WIDGET::Model> {
+        self.component.model().borrow()
+    }
+
+    /// Dispatch the pending messages, like the ones emitted by a GTK+ event, to the update methods.
+    ///
+    /// The messages emitted during these updates are also dispatched.
+    /// The futures of a component created with [`new()`](struct.TestComponent.html#method.new) are also run
+    /// until they are stalled.
+    pub fn process(&self) {
+        self.component.stream().flush();
+        if let Some(ref runtime) = self.runtime {
+            runtime.run_until_stalled();
+        }
+    }
+
+    /// Send `msg` to the component and dispatch it right away.
+    pub fn send(&self, msg: WIDGET::Msg) {
+        self.component.emit(msg);
+        self.process();
+    }
+
+    /// Get the widget.
+    pub fn widget(&self) -> &WIDGET {
+        self.component.widget()
+    }
+}
+
+/// Deterministic runtime for the tests.
+///
+/// The futures of its components, like the ones spawned in
+/// [`subscriptions()`](trait.Widget.html#method.subscriptions) or
+/// [`update_command()`](trait.Widget.html#method.update_command), are only polled by
+/// [`run_until_stalled()`](struct.TestRuntime.html#method.run_until_stalled) and
+/// [`advance()`](struct.TestRuntime.html#method.advance), and the timers created with
+/// [`Relm::interval()`](struct.Relm.html#method.interval) and [`Relm::timeout()`](struct.Relm.html#method.timeout)
+/// use a virtual clock:
+/// ```ignore
+/// let runtime = TestRuntime::new().unwrap();
+/// let clock = runtime.component::<Win>(());
+/// runtime.advance(Duration::from_secs(3));
+/// assert_eq!(clock.messages().len(), 3);
+/// ```
+///
+/// Dropping the runtime drops the remaining futures, so that nothing is left running for the next test.
+/// It should thus be created before its components, so that they are dropped first.
+pub struct TestRuntime {
+    core: Core,
+}
+
+impl TestRuntime {
+    /// Initialize GTK+ and create the runtime.
+    pub fn new() -> io::Result<Self> {
+        init_gtk();
+        Ok(TestRuntime {
+            core: Core::new_virtual()?,
+        })
+    }
+
+    /// Advance the virtual clock by `duration`, firing the timers in order and dispatching the
+    /// messages they emit.
+    pub fn advance(&self, duration: Duration) {
+        self.core.advance(duration);
+    }
+
+    /// Create a widget whose futures run on this runtime.
+    pub fn component<WIDGET>(&self, model_param: WIDGET::ModelParam) -> TestComponent<WIDGET>
+        where WIDGET: Widget + Clone + 'static,
+              WIDGET::Msg: Clone + DisplayVariant + 'static,
+    {
+        let component = create_widget_test::<WIDGET>(&self.core, model_param);
+        let component = TestComponent::from_component(component);
+        self.run_until_stalled();
+        component
+    }
+
+    /// Get the core, to create components with [`create_component()`](fn.create_component.html) for instance.
+    pub fn core(&self) -> &Core {
+        &self.core
+    }
+
+    /// Get the time elapsed on the virtual clock since the creation of the runtime.
+    pub fn now(&self) -> Duration {
+        self.core.now().unwrap_or_default()
+    }
+
+    /// Run the futures and dispatch the messages until there is nothing left to do without advancing the
+    /// clock.
+    pub fn run_until_stalled(&self) {
+        self.core.run_until_stalled();
+    }
+}
+
+impl Drop for TestRuntime {
+    fn drop(&mut self) {
+        self.core.shutdown();
+        // Run the idle callbacks still pending, like the ones of the destroyed widgets.
+        while gtk::events_pending() {
+            let _ = gtk::main_iteration_do(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use gtk::{self, Label};
+
+    use {DisplayVariant, EventStream, Relm, RemoteRelm, Widget, gtk_thread};
+    use super::{TestComponent, TestRuntime};
+    use self::Msg::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Msg {
+        Add(i32),
+        Double,
+        Tick(()),
+    }
+
+    impl DisplayVariant for Msg {
+        fn display_variant(&self) -> &'static str {
+            match *self {
+                Add(_) => "Add",
+                Double => "Double",
+                Tick(_) => "Tick",
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct Counter {
+        label: Label,
+        stream: EventStream<Msg>,
+    }
+
+    impl Widget for Counter {
+        type Model = i32;
+        type ModelParam = i32;
+        type Msg = Msg;
+        type Root = Label;
+
+        fn model(value: i32) -> i32 {
+            value
+        }
+
+        fn root(&self) -> &Label {
+            &self.label
+        }
+
+        fn subscriptions(relm: &Relm<Msg>) {
+            let stream = relm.interval(Duration::from_secs(1));
+            let _ = relm.connect_exec_ignore_err(stream, Tick);
+        }
+
+        fn update(&mut self, event: Msg, model: &mut i32) {
+            match event {
+                Add(value) => *model += value,
+                // Emitted from the update, to check that it is dispatched too.
+                Double => self.stream.emit(Add(*model)),
+                Tick(()) => *model += 100,
+            }
+        }
+
+        fn view(relm: &RemoteRelm<Self>, _model: &i32) -> Self {
+            Counter {
+                label: Label::new(None),
+                stream: relm.stream().clone(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_component() {
+        gtk_thread::run(check_test_component);
+    }
+
+    fn check_test_component() {
+        {
+            let component = TestComponent::<Counter>::new(1).unwrap();
+            component.send(Add(2));
+            assert_eq!(*component.model(), 3);
+            assert_eq!(component.messages(), vec![Add(2)]);
+
+            component.send(Double);
+            assert_eq!(*component.model(), 6);
+            assert_eq!(component.messages(), vec![Add(2), Double, Add(3)]);
+            assert!(component.has_emitted(|msg| *msg == Add(3)));
+            assert!(!component.has_emitted(|msg| *msg == Add(6)));
+
+            component.clear_messages();
+            assert!(component.messages().is_empty());
+
+            // The messages emitted outside of send() are dispatched by process().
+            component.component().emit(Add(4));
+            assert_eq!(*component.model(), 6);
+            assert_eq!(component.messages(), vec![Add(4)]);
+            component.process();
+            assert_eq!(*component.model(), 10);
+        }
+
+        let runtime = TestRuntime::new().unwrap();
+        let component = runtime.component::<Counter>(0);
+        assert_eq!(runtime.now(), Duration::from_secs(0));
+        runtime.advance(Duration::from_millis(2500));
+        assert_eq!(runtime.now(), Duration::from_millis(2500));
+        assert_eq!(*component.model(), 200);
+        assert_eq!(component.messages(), vec![Tick(()), Tick(())]);
+
+        component.send(Add(1));
+        runtime.advance(Duration::from_millis(500));
+        assert_eq!(*component.model(), 301);
+
+        while gtk::events_pending() {
+            let _ = gtk::main_iteration_do(false);
+        }
+    }
+}
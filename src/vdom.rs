@@ -0,0 +1,308 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Description of a view which is diffed against the previous one to patch the GTK+ widgets.
+//!
+//! This is used by the `#[widget(vdom)]` attribute: the `view!` macro is evaluated into a `VWidget`
+//! tree after every update and only the properties and children which changed are updated.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::mem;
+use std::os::raw::c_ulong;
+use std::ptr;
+use std::rc::Rc;
+
+use glib::{StaticType, Type};
+use glib::translate::{FromGlibPtrNone, ToGlib, ToGlibPtr};
+use glib::value::{ToValue, Value};
+use gobject_sys;
+use gtk;
+use gtk::{BoxExt, Cast, ContainerExt, IsA, WidgetExt};
+use gtk_sys;
+
+/// Value of a property in a `VWidget`, which can be compared to the previous value.
+pub trait PropertyValue: Any {
+    /// Get the value as `Any` to be able to downcast it.
+    fn as_any(&self) -> &Any;
+
+    /// Check whether `other` is equal to this value.
+    fn same_value(&self, other: &PropertyValue) -> bool;
+
+    /// Convert the value to set it on a GObject.
+    fn to_glib_value(&self) -> Value;
+}
+
+impl<T: Any + PartialEq + ToValue> PropertyValue for T {
+    fn as_any(&self) -> &Any {
+        self
+    }
+
+    fn same_value(&self, other: &PropertyValue) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+
+    fn to_glib_value(&self) -> Value {
+        ToValue::to_value(self)
+    }
+}
+
+struct Property {
+    name: &'static str,
+    value: Box<PropertyValue>,
+}
+
+/// Lightweight description of a GTK+ widget, its properties and its children.
+pub struct VWidget {
+    child_properties: Vec<Property>,
+    children: Vec<VWidget>,
+    events: Vec<Box<Fn(&gtk::Widget) -> u64>>,
+    properties: Vec<Property>,
+    typ: Type,
+}
+
+impl VWidget {
+    /// Create the description of a widget of type `WIDGET`.
+    pub fn new<WIDGET: IsA<gtk::Widget> + StaticType>() -> Self {
+        VWidget {
+            child_properties: vec![],
+            children: vec![],
+            events: vec![],
+            properties: vec![],
+            typ: WIDGET::static_type(),
+        }
+    }
+
+    /// Add a child at the end of the children of this widget.
+    pub fn add_child(&mut self, child: VWidget) {
+        self.children.push(child);
+    }
+
+    /// Set the child property `name` of this widget in its parent.
+    pub fn child_property<VALUE: PropertyValue>(&mut self, name: &'static str, value: VALUE) {
+        self.child_properties.push(Property {
+            name: name,
+            value: Box::new(value),
+        });
+    }
+
+    /// Call `callback` to connect an event of the widget, returning the id of the signal handler.
+    ///
+    /// The handlers of a widget are disconnected and `callback` is called again every time the
+    /// widget is patched, since the closures capture values which can change between two updates.
+    pub fn connect<WIDGET, CALLBACK>(&mut self, callback: CALLBACK)
+        where WIDGET: IsA<gtk::Widget>,
+              CALLBACK: Fn(&WIDGET) -> u64 + 'static,
+    {
+        self.events.push(Box::new(move |widget| {
+            let widget: WIDGET = widget.clone().downcast()
+                .unwrap_or_else(|_| panic!("the widget should have the type of its description"));
+            callback(&widget)
+        }));
+    }
+
+    /// Set the GObject property `name` of this widget.
+    pub fn property<VALUE: PropertyValue>(&mut self, name: &'static str, value: VALUE) {
+        self.properties.push(Property {
+            name: name,
+            value: Box::new(value),
+        });
+    }
+}
+
+struct Node {
+    child_properties: Vec<Property>,
+    children: Vec<Node>,
+    handlers: Vec<u64>,
+    properties: Vec<Property>,
+    typ: Type,
+    widget: gtk::Widget,
+}
+
+/// Tree of GTK+ widgets created from a `VWidget`, patched when a new description is given.
+#[derive(Clone)]
+pub struct VDom {
+    root: Rc<RefCell<Node>>,
+}
+
+impl VDom {
+    /// Create the widgets described by `description`.
+    pub fn new(description: VWidget) -> Self {
+        VDom {
+            root: Rc::new(RefCell::new(create(description))),
+        }
+    }
+
+    /// Update the widgets so that they match `description`.
+    ///
+    /// Only the properties whose value changed are set, while the events are always connected again.
+    /// The children are compared by position: a child whose type changed is replaced, the
+    /// children which are not in the description anymore are removed and the new ones are created.
+    pub fn patch(&self, description: VWidget) {
+        let mut root = self.root.borrow_mut();
+        assert!(root.typ == description.typ, "the type of the root widget cannot change");
+        patch(&mut root, description);
+    }
+
+    /// Get the root widget.
+    pub fn root(&self) -> gtk::Widget {
+        self.root.borrow().widget.clone()
+    }
+}
+
+fn changed_properties<'a>(old: &[Property], new: &'a [Property]) -> Vec<&'a Property> {
+    new.iter()
+        .filter(|property| !old.iter()
+            .any(|old| old.name == property.name && old.value.same_value(&*property.value)))
+        .collect()
+}
+
+fn create(description: VWidget) -> Node {
+    let widget: gtk::Widget = unsafe {
+        gtk::Widget::from_glib_none(gobject_sys::g_object_new(description.typ.to_glib(), ptr::null())
+            as *mut gtk_sys::GtkWidget)
+    };
+    // Show the widget before setting the properties so that the visible property is respected.
+    widget.show();
+    for property in &description.properties {
+        set_property(&widget, property);
+    }
+    let handlers = connect(&widget, &description.events);
+    let children =
+        if description.children.is_empty() {
+            vec![]
+        }
+        else {
+            let container = to_container(&widget);
+            description.children.into_iter()
+                .enumerate()
+                .map(|(position, child)| insert(&container, child, position))
+                .collect()
+        };
+    Node {
+        child_properties: description.child_properties,
+        children: children,
+        handlers: handlers,
+        properties: description.properties,
+        typ: description.typ,
+        widget: widget,
+    }
+}
+
+fn connect(widget: &gtk::Widget, events: &[Box<Fn(&gtk::Widget) -> u64>]) -> Vec<u64> {
+    events.iter()
+        .map(|event| event(widget))
+        .collect()
+}
+
+// c_ulong is only u64 on some platforms.
+#[allow(trivial_numeric_casts)]
+fn disconnect(widget: &gtk::Widget, handlers: Vec<u64>) {
+    let object: *mut gobject_sys::GObject = widget.to_glib_none().0;
+    for handler in handlers {
+        unsafe {
+            gobject_sys::g_signal_handler_disconnect(object, handler as c_ulong);
+        }
+    }
+}
+
+fn insert(container: &gtk::Container, description: VWidget, position: usize) -> Node {
+    let node = create(description);
+    container.add(&node.widget);
+    if let Ok(gtk_box) = container.clone().downcast::<gtk::Box>() {
+        gtk_box.reorder_child(&node.widget, position as i32);
+    }
+    for property in &node.child_properties {
+        set_child_property(container, &node.widget, property);
+    }
+    node
+}
+
+fn patch(node: &mut Node, description: VWidget) {
+    for property in changed_properties(&node.properties, &description.properties) {
+        set_property(&node.widget, property);
+    }
+    node.properties = description.properties;
+    // The closures of the events cannot be compared, so the handlers are replaced, in case a node
+    // is reused for a widget sending different messages.
+    let old_handlers = mem::replace(&mut node.handlers, vec![]);
+    disconnect(&node.widget, old_handlers);
+    node.handlers = connect(&node.widget, &description.events);
+    let old_children = mem::replace(&mut node.children, vec![]);
+    node.children = patch_children(&node.widget, old_children, description.children);
+}
+
+fn patch_children(parent: &gtk::Widget, old_children: Vec<Node>, children: Vec<VWidget>) -> Vec<Node> {
+    if old_children.is_empty() && children.is_empty() {
+        return vec![];
+    }
+    let container = to_container(parent);
+    let mut old_children = old_children.into_iter();
+    let mut nodes = vec![];
+    for (position, mut description) in children.into_iter().enumerate() {
+        let node =
+            match old_children.next() {
+                Some(mut node) => {
+                    if node.typ == description.typ {
+                        let child_properties = mem::replace(&mut description.child_properties, vec![]);
+                        for property in changed_properties(&node.child_properties, &child_properties) {
+                            set_child_property(&container, &node.widget, property);
+                        }
+                        node.child_properties = child_properties;
+                        patch(&mut node, description);
+                        node
+                    }
+                    else {
+                        container.remove(&node.widget);
+                        insert(&container, description, position)
+                    }
+                },
+                None => insert(&container, description, position),
+            };
+        nodes.push(node);
+    }
+    for node in old_children {
+        container.remove(&node.widget);
+    }
+    nodes
+}
+
+fn set_child_property(container: &gtk::Container, widget: &gtk::Widget, property: &Property) {
+    let value = property.value.to_glib_value();
+    unsafe {
+        gtk_sys::gtk_container_child_set_property(container.to_glib_none().0, widget.to_glib_none().0,
+            property.name.to_glib_none().0, value.to_glib_none().0);
+    }
+}
+
+fn set_property(widget: &gtk::Widget, property: &Property) {
+    let value = property.value.to_glib_value();
+    let object: *mut gobject_sys::GObject = widget.to_glib_none().0;
+    unsafe {
+        gobject_sys::g_object_set_property(object,
+            property.name.to_glib_none().0, value.to_glib_none().0);
+    }
+}
+
+fn to_container(widget: &gtk::Widget) -> gtk::Container {
+    widget.clone().downcast()
+        .unwrap_or_else(|_| panic!("a widget with children should be a gtk::Container"))
+}
@@ -21,19 +21,19 @@
 
 use gtk::{self, IsA, Object};
 
-use super::{DisplayVariant, Relm, RemoteRelm, run};
+use super::{Commands, DisplayVariant, Relm, RemoteRelm, run};
 
 /// Trait to implement to manage widget's events.
 pub trait Widget
     where Self: Clone,
           Self::Root: Clone + IsA<gtk::Widget>,
-          Self::Msg: Clone + DisplayVariant,
+          Self::Msg: DisplayVariant,
 {
     /// The type of the model.
     type Model;
     /// The type of the parameter of the model() function used to initialize the model.
     type ModelParam: Sized;
-    /// The type of the messages sent to the [`update()`](trait.Widget.html#tymethod.update) method.
+    /// The type of the messages sent to the [`update()`](trait.Widget.html#method.update) method.
     type Msg;
     /// The type of the root widget.
     type Root;
@@ -51,6 +51,16 @@ pub trait Widget
     fn on_add<W: IsA<gtk::Widget> + IsA<Object>>(&self, _parent: W) {
     }
 
+    /// Method called when the widget is destroyed, i.e. when the last clone of its component is dropped.
+    /// Its futures are cancelled and its event stream is closed right after this call.
+    fn on_destroy(&mut self, _model: &mut Self::Model) {
+    }
+
+    /// Method called when the widget is removed from its parent by
+    /// [`ContainerWidget::remove_widget()`](trait.ContainerWidget.html#tymethod.remove_widget).
+    fn on_remove<W: IsA<gtk::Widget> + IsA<Object>>(&self, _parent: W) {
+    }
+
     /// Get the parent ID.
     /// This is useful for custom Container implementation: when you implement the
     /// [`Container::add_widget()`](trait.Container.html#tymethod.add_widget), you might want to
@@ -65,21 +75,23 @@ pub trait Widget
     /// Get the root widget of the view.e. the root widget of the view.
     fn root(&self) -> &Self::Root;
 
+    /// Get the name of the widget type, given to the [`UpdateObserver`](trait.UpdateObserver.html)s.
+    ///
+    /// This method is implemented by the `#[widget]` attribute.
+    fn type_name() -> &'static str {
+        "Widget"
+    }
+
     /// Create the window from this widget and start the main loop.
     fn run(model_param: Self::ModelParam) -> Result<(), ()>
         where Self: 'static,
-              Self::Model: Clone + Send,
               Self::ModelParam: Default,
-              Self::Msg: Send,
     {
         run::<Self>(model_param)
     }
 
     /// Connect the subscriptions.
     /// Subscriptions are `Future`/`Stream` that are spawn when the widget is created.
-    ///
-    /// ## Note
-    /// This method is called in the tokio thread, so that you can spawn `Future`s and `Stream`s.
     fn subscriptions(_relm: &Relm<Self::Msg>) {
     }
 
@@ -87,16 +99,48 @@ pub trait Widget
     ///
     /// ## Note
     /// This method is called in the GTK+ thread, so that you can update widgets.
-    fn update(&mut self, event: Self::Msg, model: &mut Self::Model);
+    /// It is not called when [`update_with_commands()`](trait.Widget.html#method.update_with_commands) is
+    /// implemented, so that it does not need to be implemented in this case.
+    fn update(&mut self, _event: Self::Msg, _model: &mut Self::Model) {
+    }
 
     /// Connect `Future`s or `Stream`s when receiving an event.
     ///
-    /// ## Warning
-    /// This method is executed in the tokio thread: hence, you **must** spawn any futures in this
-    /// method, not in `Widget::update()`.
+    /// ## Note
+    /// This method is called in the GTK+ thread, right before
+    /// [`update()`](trait.Widget.html#method.update), with a clone of the message given to it.
+    /// The message is only cloned when its `DisplayVariant` is implemented by `#[derive(Msg)]`: this method is
+    /// not called for the other messages.
+    ///
+    /// ## Migration
+    /// Move the code of this method to the matching branch of
+    /// [`update_with_commands()`](trait.Widget.html#method.update_with_commands) and replace the calls to
+    /// `relm.connect_exec()` by `commands.connect()`:
+    /// ```ignore
+    /// fn update_with_commands(&mut self, event: Msg, model: &mut Model, commands: &mut Commands<Msg>) {
+    ///     match event {
+    ///         Send(message) => {
+    ///             commands.connect_ignore_err(send(&message), Sent);
+    ///         },
+    ///         // …
+    ///     }
+    /// }
+    /// ```
+    #[deprecated(note = "use update_with_commands")]
     fn update_command(_relm: &Relm<Self::Msg>, _event: Self::Msg, _model: &mut Self::Model) {
     }
 
+    /// Method called when a message is received from an event, which can add `Future`s and `Stream`s to
+    /// `commands`.
+    /// These are spawned once this method returns.
+    ///
+    /// By default, this calls [`update()`](trait.Widget.html#method.update), which is not called when
+    /// this method is implemented.
+    fn update_with_commands(&mut self, event: Self::Msg, model: &mut Self::Model, _commands: &mut Commands<Self::Msg>)
+    {
+        self.update(event, model);
+    }
+
     /// Create the initial view.
     fn view(relm: &RemoteRelm<Self>, model: &Self::Model) -> Self;
 }
@@ -0,0 +1,165 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gtk;
+extern crate relm;
+#[macro_use]
+extern crate relm_derive;
+
+use gtk::{Cast, ContainerExt};
+use relm::{ComponentList, RemoteRelm, TestComponent, Widget};
+
+use self::ListMsg::*;
+
+#[derive(Clone)]
+struct Item {
+    label: gtk::Label,
+}
+
+#[derive(Msg)]
+enum ItemMsg {
+}
+
+impl Widget for Item {
+    type Model = u32;
+    type ModelParam = u32;
+    type Msg = ItemMsg;
+    type Root = gtk::Label;
+
+    fn model(key: u32) -> u32 {
+        key
+    }
+
+    fn root(&self) -> &Self::Root {
+        &self.label
+    }
+
+    fn update(&mut self, _event: ItemMsg, _model: &mut u32) {
+    }
+
+    fn view(_relm: &RemoteRelm<Self>, model: &u32) -> Self {
+        Item {
+            label: gtk::Label::new(Some(model.to_string().as_str())),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct List {
+    items: ComponentList<u32, Item, gtk::ListBox>,
+    list_box: gtk::ListBox,
+}
+
+#[derive(Msg)]
+enum ListMsg {
+    Set(Vec<u32>),
+}
+
+impl Widget for List {
+    type Model = ();
+    type ModelParam = ();
+    type Msg = ListMsg;
+    type Root = gtk::ListBox;
+
+    fn model(_: ()) -> () {
+        ()
+    }
+
+    fn root(&self) -> &Self::Root {
+        &self.list_box
+    }
+
+    fn update(&mut self, event: ListMsg, _model: &mut ()) {
+        match event {
+            Set(keys) => self.items.update(keys, |&key| key),
+        }
+    }
+
+    fn view(relm: &RemoteRelm<Self>, _model: &()) -> Self {
+        let list_box = gtk::ListBox::new();
+        let items = ComponentList::new(&list_box, relm, |_, _| None);
+        List {
+            items: items,
+            list_box: list_box,
+        }
+    }
+}
+
+fn rows(list: &TestComponent<List>) -> Vec<gtk::ListBoxRow> {
+    list.widget().list_box.get_children().into_iter()
+        .map(|row| row.downcast::<gtk::ListBoxRow>().unwrap())
+        .collect()
+}
+
+// Get the keys of the rows, in the order of the list.
+fn keys(list: &TestComponent<List>) -> Vec<String> {
+    rows(list).iter()
+        .map(|row| {
+            let label = row.get_children()[0].clone().downcast::<gtk::Label>().unwrap();
+            label.get_text().unwrap()
+        })
+        .collect()
+}
+
+fn insert() {
+    let list = TestComponent::<List>::new(()).unwrap();
+    list.send(Set(vec![1, 2]));
+    let old_rows = rows(&list);
+    list.send(Set(vec![1, 3, 2]));
+    assert_eq!(keys(&list), vec!["1", "3", "2"]);
+    let new_rows = rows(&list);
+    assert_eq!(new_rows[0], old_rows[0]);
+    assert_eq!(new_rows[2], old_rows[1]);
+}
+
+fn remove() {
+    let list = TestComponent::<List>::new(()).unwrap();
+    list.send(Set(vec![1, 2, 3]));
+    let old_rows = rows(&list);
+    list.send(Set(vec![1, 3]));
+    assert_eq!(keys(&list), vec!["1", "3"]);
+    let new_rows = rows(&list);
+    assert_eq!(new_rows[0], old_rows[0]);
+    assert_eq!(new_rows[1], old_rows[2]);
+    list.send(Set(vec![]));
+    assert!(keys(&list).is_empty());
+}
+
+fn reorder() {
+    let list = TestComponent::<List>::new(()).unwrap();
+    list.send(Set(vec![1, 2, 3]));
+    let old_rows = rows(&list);
+    list.widget().list_box.select_row(Some(&old_rows[1]));
+    list.send(Set(vec![3, 1, 2]));
+    assert_eq!(keys(&list), vec!["3", "1", "2"]);
+    assert_eq!(rows(&list), vec![old_rows[2].clone(), old_rows[0].clone(), old_rows[1].clone()]);
+    // The rows already at their position are not moved, so they stay selected.
+    assert_eq!(list.widget().list_box.get_selected_row(), Some(old_rows[1].clone()));
+}
+
+// GTK+ can only be used from the thread where it was initialized and every test runs in its own
+// thread, so there is a single test.
+#[test]
+fn keyed_rows() {
+    insert();
+    remove();
+    reorder();
+}
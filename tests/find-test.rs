@@ -0,0 +1,134 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gtk;
+#[macro_use]
+extern crate relm;
+#[macro_use]
+extern crate relm_derive;
+extern crate relm_test;
+
+use std::time::Duration;
+
+use gtk::Continue;
+use gtk::{ButtonExt, Cast, ContainerExt, WidgetExt};
+use gtk::Orientation::Vertical;
+use relm::{RemoteRelm, Widget};
+use relm_test::{find_all_by_type, find_by_label, find_by_name, find_by_type, wait_until};
+
+#[derive(Msg)]
+enum Msg {
+    Increment,
+}
+
+#[derive(Clone)]
+struct Win {
+    inc_button: gtk::Button,
+    label: gtk::Label,
+    window: gtk::Window,
+}
+
+impl Widget for Win {
+    type Model = i32;
+    type ModelParam = ();
+    type Msg = Msg;
+    type Root = gtk::Window;
+
+    fn model(_: ()) -> i32 {
+        0
+    }
+
+    fn root(&self) -> &Self::Root {
+        &self.window
+    }
+
+    fn update(&mut self, event: Msg, model: &mut i32) {
+        match event {
+            Msg::Increment => {
+                *model += 1;
+                self.label.set_text(&model.to_string());
+            },
+        }
+    }
+
+    fn view(relm: &RemoteRelm<Self>, _model: &i32) -> Self {
+        let vbox = gtk::Box::new(Vertical, 0);
+        let inc_button = gtk::Button::new_with_label("+");
+        inc_button.set_name("inc_button");
+        vbox.add(&inc_button);
+        let label = gtk::Label::new(Some("0"));
+        vbox.add(&label);
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.add(&vbox);
+        window.show_all();
+
+        connect!(relm, inc_button, connect_clicked(_), Msg::Increment);
+
+        Win {
+            inc_button: inc_button,
+            label: label,
+            window: window,
+        }
+    }
+}
+
+fn find_widgets() {
+    let component = relm::init_test::<Win>(()).unwrap();
+    let widgets = component.widget();
+    let window = widgets.root();
+
+    assert_eq!(find_by_type::<gtk::Label, _>(window), Some(widgets.label.clone()));
+    assert_eq!(find_all_by_type::<gtk::Button, _>(window), vec![widgets.inc_button.clone()]);
+    let inc_button: gtk::Widget = widgets.inc_button.clone().upcast();
+    assert_eq!(find_by_name(window, "inc_button"), Some(inc_button.clone()));
+    assert_eq!(find_by_label(window, "+"), Some(inc_button));
+    let label: gtk::Widget = widgets.label.clone().upcast();
+    assert_eq!(find_by_label(window, "0"), Some(label));
+    assert_eq!(find_by_label(window, "*"), None);
+    assert_eq!(find_by_name(window, "dec_button"), None);
+}
+
+fn wait_for_timer() {
+    let component = relm::init_test::<Win>(()).unwrap();
+    let widgets = component.widget();
+
+    let inc_button = widgets.inc_button.clone();
+    gtk::timeout_add(10, move || {
+        inc_button.clicked();
+        Continue(false)
+    });
+    let label = widgets.label.clone();
+    assert!(wait_until(|| label.get_text().unwrap() == "1", Duration::from_secs(5)));
+}
+
+fn wait_until_timeout() {
+    let _component = relm::init_test::<Win>(()).unwrap();
+    assert!(!wait_until(|| false, Duration::from_millis(10)));
+}
+
+// GTK+ can only be used from the thread where it was initialized and every test runs in its own
+// thread, so there is a single test.
+#[test]
+fn find_and_wait() {
+    find_widgets();
+    wait_for_timer();
+    wait_until_timeout();
+}
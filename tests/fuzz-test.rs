@@ -0,0 +1,100 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gtk;
+extern crate relm;
+#[macro_use]
+extern crate relm_derive;
+extern crate relm_test;
+
+use std::panic;
+
+use relm::{RemoteRelm, Widget};
+use relm_test::{Fuzzer, Rng};
+
+use self::Msg::*;
+
+#[derive(Msg)]
+enum Msg {
+    Decrement,
+    Increment,
+}
+
+#[derive(Clone)]
+struct Counter {
+    label: gtk::Label,
+}
+
+impl Widget for Counter {
+    type Model = i32;
+    type ModelParam = ();
+    type Msg = Msg;
+    type Root = gtk::Label;
+
+    fn model(_: ()) -> i32 {
+        0
+    }
+
+    fn root(&self) -> &Self::Root {
+        &self.label
+    }
+
+    fn update(&mut self, event: Msg, model: &mut i32) {
+        match event {
+            Decrement => *model -= 1,
+            Increment => *model += 1,
+        }
+        self.label.set_text(&model.to_string());
+    }
+
+    fn view(_relm: &RemoteRelm<Self>, _model: &i32) -> Self {
+        Counter {
+            label: gtk::Label::new(Some("0")),
+        }
+    }
+}
+
+fn increment_or_decrement(rng: &mut Rng) -> Msg {
+    rng.choose(&[Increment, Decrement]).clone()
+}
+
+// GTK+ can only be used from the thread where it was initialized and every test runs in its own
+// thread, so there is a single test.
+#[test]
+fn invariants() {
+    Fuzzer::<Counter>::new(())
+        .cases(10)
+        .max_length(20)
+        .invariant("counter bounded by the number of messages", |model| model.abs() <= 20)
+        .run(increment_or_decrement);
+
+    // Any sequence reaching 3 is shrunk to the three increments.
+    let result = panic::catch_unwind(|| {
+        Fuzzer::<Counter>::new(())
+            .invariant("counter below 3", |model| *model < 3)
+            .run(increment_or_decrement);
+    });
+    let message = result.expect_err("the invariant should be broken").downcast::<String>()
+        .expect("the panic message should be a String");
+    assert!(message.starts_with(
+        "invariant \"counter below 3\" broken after the messages [Increment, Increment, Increment] (seed "),
+        "unexpected panic message: {}", message);
+}
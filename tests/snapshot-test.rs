@@ -0,0 +1,95 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gtk;
+#[macro_use]
+extern crate relm;
+#[macro_use]
+extern crate relm_derive;
+#[macro_use]
+extern crate relm_test;
+
+use gtk::{ButtonExt, ContainerExt, WidgetExt};
+use gtk::Orientation::Vertical;
+use relm::{RemoteRelm, Widget};
+use relm_test::click;
+
+#[derive(Msg)]
+enum Msg {
+    Increment,
+}
+
+#[derive(Clone)]
+struct Counter {
+    inc_button: gtk::Button,
+    label: gtk::Label,
+    vbox: gtk::Box,
+}
+
+impl Widget for Counter {
+    type Model = i32;
+    type ModelParam = ();
+    type Msg = Msg;
+    type Root = gtk::Box;
+
+    fn model(_: ()) -> i32 {
+        0
+    }
+
+    fn root(&self) -> &Self::Root {
+        &self.vbox
+    }
+
+    fn update(&mut self, event: Msg, model: &mut i32) {
+        match event {
+            Msg::Increment => {
+                *model += 1;
+                self.label.set_text(&model.to_string());
+            },
+        }
+    }
+
+    fn view(relm: &RemoteRelm<Self>, _model: &i32) -> Self {
+        let vbox = gtk::Box::new(Vertical, 0);
+        let label = gtk::Label::new(Some("0"));
+        vbox.add(&label);
+        let inc_button = gtk::Button::new_with_label("+");
+        inc_button.set_name("inc_button");
+        vbox.add(&inc_button);
+        vbox.show_all();
+
+        connect!(relm, inc_button, connect_clicked(_), Msg::Increment);
+
+        Counter {
+            inc_button: inc_button,
+            label: label,
+            vbox: vbox,
+        }
+    }
+}
+
+#[test]
+fn counter_snapshot() {
+    let component = relm::init_test::<Counter>(()).unwrap();
+    let widgets = component.widget();
+    click(&widgets.inc_button);
+    assert_snapshot!(widgets.root(), "counter");
+}
@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) 2017 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+extern crate gtk;
+extern crate relm;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::{ButtonExt, Cast, ContainerExt};
+use relm::{VDom, VWidget};
+
+type Clicks = Rc<RefCell<Vec<&'static str>>>;
+
+fn button(clicks: &Clicks, msg: &'static str) -> VWidget {
+    let mut button = VWidget::new::<gtk::Button>();
+    button.property("label", msg.to_string());
+    let clicks = clicks.clone();
+    button.connect(move |button: &gtk::Button| {
+        let clicks = clicks.clone();
+        button.connect_clicked(move |_| clicks.borrow_mut().push(msg))
+    });
+    button
+}
+
+// Equivalent of `if increment { gtk::Button { clicked => Increment } } else { gtk::Button { clicked => Decrement } }`.
+fn view(clicks: &Clicks, increment: bool) -> VWidget {
+    let mut root = VWidget::new::<gtk::Box>();
+    if increment {
+        root.add_child(button(clicks, "Increment"));
+    }
+    else {
+        root.add_child(button(clicks, "Decrement"));
+    }
+    root
+}
+
+fn get_button(vdom: &VDom) -> gtk::Button {
+    let root: gtk::Box = vdom.root().downcast().unwrap();
+    root.get_children()[0].clone().downcast().unwrap()
+}
+
+#[test]
+fn swap_branches_with_different_events() {
+    gtk::init().unwrap();
+    let clicks = Rc::new(RefCell::new(vec![]));
+    let vdom = VDom::new(view(&clicks, true));
+    let button = get_button(&vdom);
+    button.clicked();
+
+    vdom.patch(view(&clicks, false));
+    // The button has the same type in both branches, so it is reused.
+    assert_eq!(get_button(&vdom), button);
+    button.clicked();
+
+    vdom.patch(view(&clicks, true));
+    button.clicked();
+
+    assert_eq!(*clicks.borrow(), vec!["Increment", "Decrement", "Increment"]);
+}